@@ -0,0 +1,65 @@
+//! Runs arbitrary ROMs on the `no_std` core for a bounded number of
+//! instructions. Errors are fine, panics and broken invariants are not.
+//!
+//! The first byte selects the platform, with its memory layout, instruction
+//! set and quirks, and the memory mode, the next two the held keys, and the
+//! rest is the ROM.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rusty_chip8::{
+    bus::NoAudio,
+    chip::{Chip8, MemoryMode},
+    keypad::Keypad,
+    platform::Platform,
+    screen::Screen,
+};
+
+const MAX_INSTRUCTIONS: usize = 10_000;
+const INSTRUCTIONS_PER_FRAME: usize = 100;
+
+fuzz_target!(|data: &[u8]| {
+    let &[setup, keys_high, keys_low, ref rom @ ..] = data else {
+        return;
+    };
+
+    let platform = Platform::ALL[usize::from(setup & 0x0F) % Platform::ALL.len()];
+    let memory_mode = if setup & 0x40 != 0 {
+        MemoryMode::Strict
+    } else {
+        MemoryMode::Wrap
+    };
+    let config = platform.config().with_memory_mode(memory_mode);
+    let mut memory = vec![0; config.memory_size];
+    let mut chip = Chip8::with_memory(
+        config,
+        memory.as_mut_slice(),
+        Screen::new(),
+        Keypad::new(),
+        NoAudio,
+    );
+    chip.seed_rng(u64::from(setup));
+    chip.quirks = platform.quirks();
+    chip.keypad
+        .set_mask(u16::from_be_bytes([keys_high, keys_low]));
+    if chip.load_rom(rom).is_err() {
+        return;
+    }
+
+    for executed in 0..MAX_INSTRUCTIONS {
+        if executed % INSTRUCTIONS_PER_FRAME == 0 {
+            chip.tick_timers();
+        }
+        if chip.step().is_err() {
+            break;
+        }
+        assert!(usize::from(chip.sp) <= chip.config().stack_depth);
+        // Strict memory keeps a program counter that ran off the end, for the
+        // next fetch to fail.
+        assert!(
+            usize::from(chip.pc) < chip.config().memory_size
+                || (memory_mode == MemoryMode::Strict && chip.fetch().is_err())
+        );
+    }
+});
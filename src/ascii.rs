@@ -0,0 +1,18 @@
+//! Text art of the framebuffer, for a quick look from the terminal and for
+//! golden-text snapshots.
+
+use crate::screen::Screen;
+
+/// Character of lit pixels. Unlit ones are spaces.
+pub const LIT: char = '█';
+
+/// Draws the screen one line per row, with lit pixels as [`LIT`] in any plane.
+/// Rows keep their trailing spaces, so every line is as wide as the screen.
+pub fn encode(screen: &Screen) -> String {
+    let mut text = String::with_capacity((screen.width() * LIT.len_utf8() + 1) * screen.height());
+    for row in screen.rows() {
+        text.extend(row.iter().map(|&pixel| if pixel != 0 { LIT } else { ' ' }));
+        text.push('\n');
+    }
+    text
+}
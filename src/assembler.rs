@@ -0,0 +1,629 @@
+//! Assembler for a subset of Octo, the assembly language of the Octo IDE.
+//!
+//! Supported are labels (`: name`), constants (`:const name 5`), register
+//! aliases (`:alias x v3`), `:org`, `:byte`, numbers written on their own as
+//! data bytes such as sprites, the statements that map to one instruction,
+//! `if ... then`, and the structured `if ... begin ... else ... end` and
+//! `loop ... while ... again`. Macros, `:calc` and the comparisons `<`, `>`,
+//! `<=` and `>=`, which Octo builds from several instructions, are not.
+
+use std::collections::HashMap;
+
+use crate::{
+    error::{AppError, AppResult},
+    instruction::Instruction,
+};
+
+/// Assembles `source` into a ROM loaded at `start`.
+///
+/// Execution begins at the `main` label, with a jump to it at `start` unless
+/// the program begins with it. Programs without `main` begin at the top.
+pub fn assemble(source: &str, start: u16) -> AppResult<Vec<u8>> {
+    let tokens = tokenize(source);
+    let mut assembler = Assembler::new(&tokens, start);
+    // The first pass finds the addresses of labels, so that the second one can
+    // use them before they are defined.
+    assembler.run()?;
+    assembler.final_pass = true;
+    assembler.run()?;
+    Ok(assembler.rom)
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Token<'a> {
+    text: &'a str,
+    line: usize,
+}
+
+fn tokenize(source: &str) -> Vec<Token<'_>> {
+    source
+        .lines()
+        .enumerate()
+        .flat_map(|(index, line)| {
+            let code = line.split('#').next().unwrap_or_default();
+            code.split_whitespace().map(move |text| Token {
+                text,
+                line: index + 1,
+            })
+        })
+        .collect()
+}
+
+fn parse_number(token: &str) -> Option<i32> {
+    let (negative, digits) = match token.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, token),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i32::from_str_radix(hex, 16).ok()?
+    } else if let Some(binary) = digits.strip_prefix("0b") {
+        i32::from_str_radix(binary, 2).ok()?
+    } else {
+        digits.parse().ok()?
+    };
+    Some(if negative { -value } else { value })
+}
+
+/// Index of a register named like `v3` or `vA`.
+fn parse_register(token: &str) -> Option<u8> {
+    let digit = token.strip_prefix(['v', 'V'])?;
+    if digit.len() == 1 {
+        u8::from_str_radix(digit, 16).ok()
+    } else {
+        None
+    }
+}
+
+/// Right-hand side of an assignment or comparison.
+enum Operand {
+    Register(u8),
+    Value(i32),
+}
+
+/// Open `begin`, `else` or `loop`, with the jumps to patch once it ends.
+enum Block {
+    /// Jump to the `else` or `end` taken when the condition doesn't hold.
+    If {
+        jump: u16,
+    },
+    /// Jump over the `else` branch at the end of the `begin` branch.
+    Else {
+        jump: u16,
+    },
+    Loop {
+        start: u16,
+        exits: Vec<u16>,
+    },
+}
+
+struct Assembler<'a> {
+    tokens: &'a [Token<'a>],
+    position: usize,
+    line: usize,
+    start: u16,
+    jump_to_main: bool,
+    final_pass: bool,
+    /// Kept from the first pass, unlike everything else.
+    labels: HashMap<&'a str, u16>,
+    constants: HashMap<&'a str, i32>,
+    aliases: HashMap<&'a str, u8>,
+    blocks: Vec<Block>,
+    rom: Vec<u8>,
+    here: u32,
+}
+
+impl<'a> Assembler<'a> {
+    fn new(tokens: &'a [Token<'a>], start: u16) -> Self {
+        let defines_main = tokens
+            .windows(2)
+            .any(|pair| pair[0].text == ":" && pair[1].text == "main");
+        let starts_with_main =
+            tokens.len() >= 2 && tokens[0].text == ":" && tokens[1].text == "main";
+        Self {
+            tokens,
+            position: 0,
+            line: 1,
+            start,
+            jump_to_main: defines_main && !starts_with_main,
+            final_pass: false,
+            labels: HashMap::new(),
+            constants: HashMap::new(),
+            aliases: HashMap::new(),
+            blocks: Vec::new(),
+            rom: Vec::new(),
+            here: start as u32,
+        }
+    }
+
+    fn run(&mut self) -> AppResult<()> {
+        self.position = 0;
+        self.line = 1;
+        self.constants.clear();
+        self.aliases.clear();
+        self.blocks.clear();
+        self.rom.clear();
+        self.here = self.start as u32;
+
+        if self.jump_to_main {
+            let main = self.labels.get("main").copied().unwrap_or_default();
+            self.emit(Instruction::JumpAddr(main))?;
+        }
+        while self.position < self.tokens.len() {
+            self.statement()?;
+        }
+        match self.blocks.last() {
+            Some(Block::Loop { .. }) => Err(self.error("`loop` without `again`")),
+            Some(_) => Err(self.error("`begin` without `end`")),
+            None => Ok(()),
+        }
+    }
+
+    fn error(&self, message: impl Into<String>) -> AppError {
+        AppError::Assembly {
+            line: self.line,
+            message: message.into(),
+        }
+    }
+
+    fn next(&mut self) -> AppResult<&'a str> {
+        let Some(token) = self.tokens.get(self.position) else {
+            return Err(self.error("unexpected end of file"));
+        };
+        self.position += 1;
+        self.line = token.line;
+        Ok(token.text)
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.position).map(|token| token.text)
+    }
+
+    fn expect(&mut self, expected: &str) -> AppResult<()> {
+        let token = self.next()?;
+        if token == expected {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected `{}`, found `{}`", expected, token)))
+        }
+    }
+
+    fn statement(&mut self) -> AppResult<()> {
+        let token = self.next()?;
+        let instruction = match token {
+            ":" => {
+                let name = self.name()?;
+                if !self.final_pass && self.labels.insert(name, self.here as u16).is_some() {
+                    return Err(self.error(format!("label `{}` is defined twice", name)));
+                }
+                return Ok(());
+            }
+            ":const" => {
+                let name = self.name()?;
+                let value = self.value()?;
+                self.constants.insert(name, value);
+                return Ok(());
+            }
+            ":alias" => {
+                let name = self.name()?;
+                let x = self.register()?;
+                self.aliases.insert(name, x);
+                return Ok(());
+            }
+            ":org" => {
+                let address = self.value()?;
+                if address < self.start as i32 || address > u16::MAX as i32 {
+                    return Err(
+                        self.error(format!("`:org` outside of the program: {:#X}", address))
+                    );
+                }
+                self.here = address as u32;
+                return Ok(());
+            }
+            ":byte" => {
+                let value = self.value()?;
+                let byte = self.byte(value)?;
+                return self.emit_byte(byte);
+            }
+            "clear" => Instruction::ClearScreen,
+            "return" | ";" => Instruction::Return,
+            "exit" => Instruction::Exit,
+            "hires" => Instruction::HighRes,
+            "lores" => Instruction::LowRes,
+            "scroll-left" => Instruction::ScrollLeft,
+            "scroll-right" => Instruction::ScrollRight,
+            "scroll-down" => Instruction::ScrollDown { n: self.nibble()? },
+            "scroll-up" => Instruction::ScrollUp { n: self.nibble()? },
+            "jump" => Instruction::JumpAddr(self.address()?),
+            "jump0" => Instruction::JumpV0Addr(self.address()?),
+            ":call" => Instruction::CallAddr(self.address()?),
+            "i" => self.assign_i()?,
+            "delay" => {
+                self.expect(":=")?;
+                Instruction::LoadDelayReg {
+                    x: self.register()?,
+                }
+            }
+            "buzzer" => {
+                self.expect(":=")?;
+                Instruction::LoadSoundReg {
+                    x: self.register()?,
+                }
+            }
+            "pitch" => {
+                self.expect(":=")?;
+                Instruction::LoadPitch {
+                    x: self.register()?,
+                }
+            }
+            "sprite" => Instruction::Draw {
+                x: self.register()?,
+                y: self.register()?,
+                n: self.nibble()?,
+            },
+            "load" | "save" => {
+                let x = self.register()?;
+                let range = if self.peek() == Some("-") {
+                    self.next()?;
+                    Some(self.register()?)
+                } else {
+                    None
+                };
+                match (token == "load", range) {
+                    (true, None) => Instruction::LoadRegs { x },
+                    (false, None) => Instruction::StoreRegs { x },
+                    (true, Some(y)) => Instruction::LoadRange { x, y },
+                    (false, Some(y)) => Instruction::StoreRange { x, y },
+                }
+            }
+            "bcd" => Instruction::StoreBcd {
+                x: self.register()?,
+            },
+            "saveflags" => Instruction::StoreFlags {
+                x: self.register()?,
+            },
+            "loadflags" => Instruction::LoadFlags {
+                x: self.register()?,
+            },
+            "plane" => Instruction::SelectPlanes { n: self.nibble()? },
+            "audio" => Instruction::LoadAudio,
+            "if" => return self.conditional(),
+            "else" => {
+                let Some(Block::If { jump }) = self.blocks.pop() else {
+                    return Err(self.error("`else` without `begin`"));
+                };
+                let end = self.here as u16;
+                self.emit(Instruction::JumpAddr(0))?;
+                self.patch(jump)?;
+                self.blocks.push(Block::Else { jump: end });
+                return Ok(());
+            }
+            "end" => {
+                let Some(Block::If { jump } | Block::Else { jump }) = self.blocks.pop() else {
+                    return Err(self.error("`end` without `begin`"));
+                };
+                return self.patch(jump);
+            }
+            "loop" => {
+                self.blocks.push(Block::Loop {
+                    start: self.here as u16,
+                    exits: Vec::new(),
+                });
+                return Ok(());
+            }
+            "while" => {
+                let (skip_if_true, _) = self.condition()?;
+                self.emit(skip_if_true)?;
+                let exit = self.here as u16;
+                self.emit(Instruction::JumpAddr(0))?;
+                let Some(Block::Loop { exits, .. }) = self
+                    .blocks
+                    .iter_mut()
+                    .rev()
+                    .find(|block| matches!(block, Block::Loop { .. }))
+                else {
+                    return Err(self.error("`while` outside of a loop"));
+                };
+                exits.push(exit);
+                return Ok(());
+            }
+            "again" => {
+                let Some(Block::Loop { start, exits }) = self.blocks.pop() else {
+                    return Err(self.error("`again` without `loop`"));
+                };
+                self.emit(Instruction::JumpAddr(start))?;
+                for exit in exits {
+                    self.patch(exit)?;
+                }
+                return Ok(());
+            }
+            _ => {
+                if let Some(x) = self.find_register(token) {
+                    self.assign_register(x)?
+                } else if let Some(value) = self.find_constant(token) {
+                    let byte = self.byte(value)?;
+                    return self.emit_byte(byte);
+                } else {
+                    // Any other name calls the subroutine at its label.
+                    let address = self.label(token)?;
+                    Instruction::CallAddr(self.check_address(address)?)
+                }
+            }
+        };
+        self.emit(instruction)
+    }
+
+    /// Statements starting with a register, like `v0 += 1` or `v1 := random 7`.
+    fn assign_register(&mut self, x: u8) -> AppResult<Instruction> {
+        let operator = self.next()?;
+        let instruction = match operator {
+            ":=" => match self.peek() {
+                Some("random") => {
+                    self.next()?;
+                    let value = self.value()?;
+                    Instruction::RandomRegByte {
+                        x,
+                        kk: self.byte(value)?,
+                    }
+                }
+                Some("delay") => {
+                    self.next()?;
+                    Instruction::LoadRegDelay { x }
+                }
+                Some("key") => {
+                    self.next()?;
+                    Instruction::WaitKey { x }
+                }
+                _ => match self.operand()? {
+                    Operand::Register(y) => Instruction::LoadRegReg { x, y },
+                    Operand::Value(value) => Instruction::LoadRegByte {
+                        x,
+                        kk: self.byte(value)?,
+                    },
+                },
+            },
+            "+=" => match self.operand()? {
+                Operand::Register(y) => Instruction::AddRegReg { x, y },
+                Operand::Value(value) => Instruction::AddRegByte {
+                    x,
+                    kk: self.byte(value)?,
+                },
+            },
+            "-=" => match self.operand()? {
+                Operand::Register(y) => Instruction::SubRegReg { x, y },
+                Operand::Value(value) => Instruction::AddRegByte {
+                    x,
+                    kk: self.byte(-value)?,
+                },
+            },
+            "=-" => Instruction::SubnRegReg {
+                x,
+                y: self.register()?,
+            },
+            "|=" => Instruction::OrRegReg {
+                x,
+                y: self.register()?,
+            },
+            "&=" => Instruction::AndRegReg {
+                x,
+                y: self.register()?,
+            },
+            "^=" => Instruction::XorRegReg {
+                x,
+                y: self.register()?,
+            },
+            ">>=" => Instruction::ShiftRight {
+                x,
+                y: self.register()?,
+            },
+            "<<=" => Instruction::ShiftLeft {
+                x,
+                y: self.register()?,
+            },
+            _ => return Err(self.error(format!("unknown operator `{}`", operator))),
+        };
+        Ok(instruction)
+    }
+
+    /// `i := label`, `i := long label`, `i := hex vX`, `i := bighex vX` or `i += vX`.
+    fn assign_i(&mut self) -> AppResult<Instruction> {
+        let operator = self.next()?;
+        let instruction = match operator {
+            ":=" => match self.peek() {
+                Some("long") => {
+                    self.next()?;
+                    let value = self.value()?;
+                    if !(0..=u16::MAX as i32).contains(&value) {
+                        return Err(self.error(format!("address out of range: {:#X}", value)));
+                    }
+                    Instruction::LoadILong(value as u16)
+                }
+                Some("hex") => {
+                    self.next()?;
+                    Instruction::LoadFont {
+                        x: self.register()?,
+                    }
+                }
+                Some("bighex") => {
+                    self.next()?;
+                    Instruction::LoadBigFont {
+                        x: self.register()?,
+                    }
+                }
+                _ => Instruction::LoadIAddr(self.address()?),
+            },
+            "+=" => Instruction::AddIReg {
+                x: self.register()?,
+            },
+            _ => return Err(self.error(format!("unknown operator `{}` for `i`", operator))),
+        };
+        Ok(instruction)
+    }
+
+    /// `if COND then STATEMENT` or `if COND begin`.
+    fn conditional(&mut self) -> AppResult<()> {
+        let (skip_if_true, skip_if_false) = self.condition()?;
+        match self.next()? {
+            "then" => self.emit(skip_if_false),
+            "begin" => {
+                self.emit(skip_if_true)?;
+                let jump = self.here as u16;
+                self.emit(Instruction::JumpAddr(0))?;
+                self.blocks.push(Block::If { jump });
+                Ok(())
+            }
+            token => Err(self.error(format!("expected `then` or `begin`, found `{}`", token))),
+        }
+    }
+
+    /// Parses `vX == 5`, `vX != vY`, `vX key` or `vX -key`, returning the
+    /// instructions skipping when it holds and when it doesn't.
+    fn condition(&mut self) -> AppResult<(Instruction, Instruction)> {
+        let x = self.register()?;
+        let operator = self.next()?;
+        let pressed = Instruction::SkipKeyPressed { x };
+        let not_pressed = Instruction::SkipKeyNotPressed { x };
+        let (equal, not_equal) = match operator {
+            "key" => return Ok((pressed, not_pressed)),
+            "-key" => return Ok((not_pressed, pressed)),
+            "==" | "!=" => match self.operand()? {
+                Operand::Register(y) => (
+                    Instruction::SkipEqRegReg { x, y },
+                    Instruction::SkipNeRegReg { x, y },
+                ),
+                Operand::Value(value) => {
+                    let kk = self.byte(value)?;
+                    (
+                        Instruction::SkipEqRegByte { x, kk },
+                        Instruction::SkipNeRegByte { x, kk },
+                    )
+                }
+            },
+            _ => return Err(self.error(format!("unsupported comparison `{}`", operator))),
+        };
+        Ok(if operator == "==" {
+            (equal, not_equal)
+        } else {
+            (not_equal, equal)
+        })
+    }
+
+    fn find_register(&self, token: &str) -> Option<u8> {
+        parse_register(token).or_else(|| self.aliases.get(token).copied())
+    }
+
+    fn find_constant(&self, token: &str) -> Option<i32> {
+        parse_number(token).or_else(|| self.constants.get(token).copied())
+    }
+
+    fn register(&mut self) -> AppResult<u8> {
+        let token = self.next()?;
+        self.find_register(token)
+            .ok_or_else(|| self.error(format!("expected a register, found `{}`", token)))
+    }
+
+    /// A new name for a label, constant or alias.
+    fn name(&mut self) -> AppResult<&'a str> {
+        let token = self.next()?;
+        if parse_number(token).is_some() || parse_register(token).is_some() {
+            Err(self.error(format!("`{}` can't be used as a name", token)))
+        } else {
+            Ok(token)
+        }
+    }
+
+    /// A number, constant or label.
+    fn value(&mut self) -> AppResult<i32> {
+        let token = self.next()?;
+        match self.find_constant(token) {
+            Some(value) => Ok(value),
+            None => self.label(token).map(i32::from),
+        }
+    }
+
+    fn operand(&mut self) -> AppResult<Operand> {
+        match self.peek().and_then(|token| self.find_register(token)) {
+            Some(y) => {
+                self.next()?;
+                Ok(Operand::Register(y))
+            }
+            None => Ok(Operand::Value(self.value()?)),
+        }
+    }
+
+    /// Address of a label, which is unknown while the first pass hasn't reached it.
+    fn label(&self, name: &str) -> AppResult<u16> {
+        match self.labels.get(name) {
+            Some(&address) => Ok(address),
+            None if !self.final_pass => Ok(0),
+            None => Err(self.error(format!("undefined name `{}`", name))),
+        }
+    }
+
+    /// A 12-bit address for jumps, calls and `i := label`.
+    fn address(&mut self) -> AppResult<u16> {
+        let value = self.value()?;
+        self.check_address(value)
+    }
+
+    fn check_address(&self, value: impl Into<i32>) -> AppResult<u16> {
+        let value = value.into();
+        if (0..=0xFFF).contains(&value) {
+            Ok(value as u16)
+        } else {
+            Err(self.error(format!("address out of range: {:#X}", value)))
+        }
+    }
+
+    /// Accepts negative bytes, as two's complement.
+    fn byte(&self, value: i32) -> AppResult<u8> {
+        if (-128..=255).contains(&value) {
+            Ok(value as u8)
+        } else {
+            Err(self.error(format!("byte out of range: {}", value)))
+        }
+    }
+
+    fn nibble(&mut self) -> AppResult<u8> {
+        let value = self.value()?;
+        if (0..=0xF).contains(&value) {
+            Ok(value as u8)
+        } else {
+            Err(self.error(format!("nibble out of range: {}", value)))
+        }
+    }
+
+    fn emit(&mut self, instruction: Instruction) -> AppResult<()> {
+        let [high, low] = instruction.encode().to_be_bytes();
+        self.emit_byte(high)?;
+        self.emit_byte(low)?;
+        if let Instruction::LoadILong(address) = instruction {
+            let [high, low] = address.to_be_bytes();
+            self.emit_byte(high)?;
+            self.emit_byte(low)?;
+        }
+        Ok(())
+    }
+
+    fn emit_byte(&mut self, byte: u8) -> AppResult<()> {
+        if self.here > u16::MAX as u32 {
+            return Err(self.error("program doesn't fit in memory"));
+        }
+        let offset = (self.here - self.start as u32) as usize;
+        if self.rom.len() <= offset {
+            self.rom.resize(offset + 1, 0);
+        }
+        self.rom[offset] = byte;
+        self.here += 1;
+        Ok(())
+    }
+
+    /// Points the jump emitted at `jump` to the current address.
+    fn patch(&mut self, jump: u16) -> AppResult<()> {
+        let target = self.check_address(self.here as i32)?;
+        let [high, low] = Instruction::JumpAddr(target).encode().to_be_bytes();
+        let offset = (jump - self.start) as usize;
+        self.rom[offset] = high;
+        self.rom[offset + 1] = low;
+        Ok(())
+    }
+}
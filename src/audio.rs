@@ -0,0 +1,462 @@
+//! Sound output for the buzzer, mixed with rodio and played with cpal.
+//!
+//! The buzzer plays the beep while the sound timer runs, or the XO-CHIP
+//! audio pattern at its pitch once a program loads one. Mega-Chip sounds
+//! play over it.
+
+use std::{
+    fs::File,
+    io::BufReader,
+    path::Path,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use log::error;
+use rodio::{
+    cpal::{
+        self,
+        traits::{DeviceTrait, HostTrait, StreamTrait},
+        BufferSize, FromSample, SampleFormat, SizedSample, SupportedBufferSize,
+    },
+    dynamic_mixer::{self, DynamicMixer, DynamicMixerController},
+    Decoder, Source,
+};
+
+use crate::{
+    bus::{AudioBus, AUDIO_PATTERN_SIZE, DEFAULT_PITCH},
+    error::{AppError, AppResult},
+};
+
+/// Default frequency of the buzzer, in Hz.
+pub const DEFAULT_BEEP_FREQUENCY: f32 = 440.0;
+/// Default time the buzzer takes to fade in and out, short enough to sound
+/// instant but avoiding the pop of a wave starting or stopping abruptly.
+pub const DEFAULT_BEEP_ENVELOPE: Duration = Duration::from_millis(5);
+/// Amplitude of the tone, from 0 to 1.
+const VOLUME: f32 = 0.2;
+const SAMPLE_RATE: u32 = 44_100;
+/// Bits in the XO-CHIP audio pattern, played in a loop.
+const PATTERN_BITS: f32 = (AUDIO_PATTERN_SIZE * 8) as f32;
+
+/// Shape of the buzzer's sound wave.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Waveform {
+    Sine,
+    /// The harsh sound of the original hardware's buzzer.
+    #[default]
+    Square,
+    Triangle,
+    /// Random levels changing at twice the frequency, a hiss pitched by it.
+    Noise,
+}
+
+impl FromStr for Waveform {
+    type Err = &'static str;
+
+    /// Parses `sine`, `square`, `triangle` or `noise`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sine" => Ok(Self::Sine),
+            "square" => Ok(Self::Square),
+            "triangle" => Ok(Self::Triangle),
+            "noise" => Ok(Self::Noise),
+            _ => Err("expected one of `sine`, `square`, `triangle` or `noise`"),
+        }
+    }
+}
+
+/// Recorded sound the buzzer plays instead of a wave, mixed down to mono.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BeepSample {
+    samples: Arc<[f32]>,
+    sample_rate: u32,
+}
+
+impl BeepSample {
+    /// Decodes a WAV, FLAC, Ogg Vorbis or MP3 file.
+    pub fn load(path: &Path) -> AppResult<Self> {
+        let invalid = |message: String| {
+            AppError::InvalidArgument(format!("beep sample {}: {}", path.display(), message))
+        };
+        let file = File::open(path).map_err(|err| invalid(err.to_string()))?;
+        let decoder = Decoder::new(BufReader::new(file)).map_err(|err| invalid(err.to_string()))?;
+        let channels = decoder.channels().max(1) as usize;
+        let sample_rate = decoder.sample_rate();
+        let samples: Vec<i16> = decoder.collect();
+        let samples: Arc<[f32]> = samples
+            .chunks(channels)
+            .map(|frame| {
+                frame
+                    .iter()
+                    .map(|&sample| sample as f32 / 32768.0)
+                    .sum::<f32>()
+                    / channels as f32
+            })
+            .collect();
+        if samples.is_empty() {
+            return Err(invalid("no sound in it".to_owned()));
+        }
+        Ok(Self {
+            samples,
+            sample_rate,
+        })
+    }
+}
+
+/// How the buzzer sounds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Beep {
+    /// Frequency in Hz.
+    pub frequency: f32,
+    pub waveform: Waveform,
+    /// Time taken to fade in when the tone starts and out when it stops,
+    /// zero to start and stop at once.
+    pub envelope: Duration,
+    /// Played in a loop instead of the wave, from its start every time the
+    /// buzzer starts.
+    pub sample: Option<BeepSample>,
+}
+
+impl Default for Beep {
+    fn default() -> Self {
+        Self {
+            frequency: DEFAULT_BEEP_FREQUENCY,
+            waveform: Waveform::default(),
+            envelope: DEFAULT_BEEP_ENVELOPE,
+            sample: None,
+        }
+    }
+}
+
+/// Open audio output device, which the buzzers given to
+/// [`AudioOutput::play`] play on while it is kept.
+///
+/// It may not be sent to other threads, unlike the buzzers.
+pub struct AudioOutput {
+    _stream: cpal::Stream,
+    mixer: Arc<DynamicMixerController<f32>>,
+}
+
+impl AudioOutput {
+    /// Opens the default output device, buffering about `latency` of sound
+    /// if given, within what the device supports, or as much as the device
+    /// buffers by default. Smaller buffers let beeps start sooner, but slow
+    /// machines may not refill them in time, so the sound crackles.
+    pub fn open(latency: Option<Duration>) -> AppResult<Self> {
+        let device = cpal::default_host()
+            .default_output_device()
+            .ok_or_else(|| AppError::Internal("no audio output device".into()))?;
+        let supported = device.default_output_config()?;
+        let mut config = supported.config();
+        if let Some(latency) = latency {
+            let frames = (latency.as_secs_f64() * config.sample_rate.0 as f64).round() as u32;
+            let frames = match supported.buffer_size() {
+                SupportedBufferSize::Range { min, max } => frames.clamp(*min, *max),
+                SupportedBufferSize::Unknown => frames,
+            };
+            config.buffer_size = BufferSize::Fixed(frames.max(1));
+        }
+
+        let (mixer, source) = dynamic_mixer::mixer(config.channels, config.sample_rate.0);
+        let stream = match supported.sample_format() {
+            SampleFormat::F32 => build_stream::<f32>(&device, &config, source)?,
+            SampleFormat::I16 => build_stream::<i16>(&device, &config, source)?,
+            SampleFormat::U16 => build_stream::<u16>(&device, &config, source)?,
+            format => {
+                return Err(AppError::Internal(
+                    format!("unsupported audio sample format {}", format).into(),
+                ))
+            }
+        };
+        stream.play()?;
+        Ok(Self {
+            _stream: stream,
+            mixer,
+        })
+    }
+
+    /// Plays `buzzer` until it is dropped, beeping like `beep`.
+    pub fn play(&self, buzzer: &Buzzer, beep: Beep) {
+        self.mixer.add(Tone {
+            sound: Arc::clone(&buzzer.sound),
+            beep,
+            phase: 0.0,
+            noise: VOLUME,
+            level: 0.0,
+            position: 0,
+            bit: 0.0,
+            digitized: None,
+            digitized_generation: 0,
+            digitized_position: 0.0,
+        });
+    }
+}
+
+/// Stream playing what `source` mixes, in samples of type `T`.
+fn build_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    mut source: DynamicMixer<f32>,
+) -> Result<cpal::Stream, cpal::BuildStreamError>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], _| {
+            for sample in data {
+                *sample = T::from_sample(source.next().unwrap_or(0.0));
+            }
+        },
+        |err| error!("Audio output failed: {}", err),
+        None,
+    )
+}
+
+/// What the machine asks its buzzer to play, shared with the [`Tone`]
+/// playing it.
+#[derive(Debug)]
+struct Sound {
+    on: AtomicBool,
+    /// Keeps the tone silent, without affecting `on`.
+    muted: AtomicBool,
+    /// XO-CHIP audio pattern, its first and last 64 bits. All zero until a
+    /// program loads one, which plays the beep instead.
+    pattern: [AtomicU64; 2],
+    pitch: AtomicU8,
+    /// Mega-Chip sound playing, if any.
+    digitized: Mutex<Option<Arc<Digitized>>>,
+    /// Changes whenever `digitized` does, so the tone only locks it then.
+    digitized_generation: AtomicU64,
+}
+
+/// Sound played by a Mega-Chip program.
+#[derive(Debug)]
+struct Digitized {
+    /// 8-bit unsigned samples.
+    samples: Vec<u8>,
+    /// Samples per second.
+    rate: u16,
+    looping: bool,
+}
+
+/// Audio bus of the machine, sounding while turned on with
+/// [`AudioBus::set_tone`]. Silent until played with [`AudioOutput::play`].
+///
+/// The tone keeps playing silence while off, so it starts without waiting
+/// for the output to pick up a new sound.
+#[derive(Debug)]
+pub struct Buzzer {
+    sound: Arc<Sound>,
+}
+
+impl Buzzer {
+    pub fn new() -> Self {
+        Self {
+            sound: Arc::new(Sound {
+                on: AtomicBool::new(false),
+                muted: AtomicBool::new(false),
+                pattern: [AtomicU64::new(0), AtomicU64::new(0)],
+                pitch: AtomicU8::new(DEFAULT_PITCH),
+                digitized: Mutex::new(None),
+                digitized_generation: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Silences the buzzer, or lets it sound again, without affecting
+    /// whether the machine turned it on.
+    pub fn set_muted(&self, muted: bool) {
+        self.sound.muted.store(muted, Ordering::Relaxed);
+    }
+
+    fn set_digitized(&self, digitized: Option<Digitized>) {
+        *self.sound.digitized.lock().unwrap() = digitized.map(Arc::new);
+        self.sound
+            .digitized_generation
+            .fetch_add(1, Ordering::Release);
+    }
+}
+
+impl Default for Buzzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AudioBus for Buzzer {
+    fn set_tone(&mut self, on: bool) {
+        self.sound.on.store(on, Ordering::Relaxed);
+    }
+
+    fn set_pattern(&mut self, pattern: &[u8; AUDIO_PATTERN_SIZE]) {
+        for (half, bytes) in self.sound.pattern.iter().zip(pattern.chunks_exact(8)) {
+            let bytes = bytes.try_into().expect("chunks are 8 bytes");
+            half.store(u64::from_be_bytes(bytes), Ordering::Relaxed);
+        }
+    }
+
+    fn set_pitch(&mut self, pitch: u8) {
+        self.sound.pitch.store(pitch, Ordering::Relaxed);
+    }
+
+    fn play_sample(&mut self, samples: &[u8], rate: u16, looping: bool) {
+        self.set_digitized(Some(Digitized {
+            samples: samples.to_vec(),
+            rate,
+            looping,
+        }));
+    }
+
+    fn stop_sample(&mut self) {
+        self.set_digitized(None);
+    }
+}
+
+/// Wave silent while the sound is off or muted, ending once the buzzer is
+/// dropped.
+struct Tone {
+    sound: Arc<Sound>,
+    beep: Beep,
+    /// Position in the current period of the wave, from 0 to 1.
+    phase: f32,
+    /// Level of the noise waveform until it next changes.
+    noise: f32,
+    /// Volume along the envelope, from 0 to 1.
+    level: f32,
+    /// Index of the next sample of the beep sample.
+    position: usize,
+    /// Position in the audio pattern, in bits.
+    bit: f32,
+    /// Mega-Chip sound playing, as of `digitized_generation`.
+    digitized: Option<Arc<Digitized>>,
+    digitized_generation: u64,
+    /// Position in the Mega-Chip sound, in its samples.
+    digitized_position: f32,
+}
+
+impl Tone {
+    /// Next sample of the Mega-Chip sound, silent once a sound played once ends.
+    fn digitized(&mut self) -> f32 {
+        let generation = self.sound.digitized_generation.load(Ordering::Acquire);
+        if generation != self.digitized_generation {
+            if let Ok(digitized) = self.sound.digitized.try_lock() {
+                self.digitized = digitized.clone();
+                self.digitized_generation = generation;
+                self.digitized_position = 0.0;
+            }
+        }
+        let Some(digitized) = &self.digitized else {
+            return 0.0;
+        };
+        let len = digitized.samples.len();
+        let Some(&sample) = digitized.samples.get(self.digitized_position as usize) else {
+            return 0.0;
+        };
+        self.digitized_position += digitized.rate as f32 / self.sample_rate() as f32;
+        if digitized.looping {
+            self.digitized_position %= len as f32;
+        }
+        if self.sound.muted.load(Ordering::Relaxed) {
+            return 0.0;
+        }
+        (sample as f32 / 127.5 - 1.0) * VOLUME
+    }
+
+    /// Next sample of the beep or the audio pattern.
+    fn beep(&mut self) -> f32 {
+        let phase = self.phase;
+        self.phase = (phase + self.beep.frequency / SAMPLE_RATE as f32).fract();
+        // Every half period.
+        if self.beep.waveform == Waveform::Noise && (phase < 0.5) != (self.phase < 0.5) {
+            self.noise = if rand::random() { VOLUME } else { -VOLUME };
+        }
+        let target =
+            if self.sound.on.load(Ordering::Relaxed) && !self.sound.muted.load(Ordering::Relaxed) {
+                1.0
+            } else {
+                0.0
+            };
+        if self.level == 0.0 && target > 0.0 {
+            self.position = 0;
+            self.bit = 0.0;
+        }
+        let step = 1.0 / (self.beep.envelope.as_secs_f32() * self.sample_rate() as f32);
+        self.level = if step.is_finite() {
+            self.level + (target - self.level).clamp(-step, step)
+        } else {
+            target
+        };
+        if self.level == 0.0 {
+            return 0.0;
+        }
+        let pattern = [
+            self.sound.pattern[0].load(Ordering::Relaxed),
+            self.sound.pattern[1].load(Ordering::Relaxed),
+        ];
+        if pattern != [0, 0] {
+            // 4000 bits per second at the default pitch, an octave higher
+            // every 48 steps above it.
+            let pitch = self.sound.pitch.load(Ordering::Relaxed) as f32;
+            let rate = 4000.0 * 2f32.powf((pitch - DEFAULT_PITCH as f32) / 48.0);
+            let bit = self.bit as usize;
+            self.bit = (self.bit + rate / self.sample_rate() as f32) % PATTERN_BITS;
+            let sample = if pattern[bit / 64] << (bit % 64) >> 63 == 1 {
+                VOLUME
+            } else {
+                -VOLUME
+            };
+            return sample * self.level;
+        }
+        if let Some(sample) = &self.beep.sample {
+            let value = sample.samples[self.position];
+            self.position = (self.position + 1) % sample.samples.len();
+            return value * self.level;
+        }
+        let sample = match self.beep.waveform {
+            Waveform::Sine => VOLUME * (phase * std::f32::consts::TAU).sin(),
+            Waveform::Square if phase < 0.5 => VOLUME,
+            Waveform::Square => -VOLUME,
+            Waveform::Triangle => VOLUME * (4.0 * (phase - 0.5).abs() - 1.0),
+            Waveform::Noise => self.noise,
+        };
+        sample * self.level
+    }
+}
+
+impl Iterator for Tone {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if Arc::strong_count(&self.sound) == 1 {
+            return None;
+        }
+        Some(self.beep() + self.digitized())
+    }
+}
+
+impl Source for Tone {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.beep
+            .sample
+            .as_ref()
+            .map_or(SAMPLE_RATE, |sample| sample.sample_rate)
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
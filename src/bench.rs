@@ -0,0 +1,203 @@
+//! Interpreter throughput measurements, without any rendering.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    collections::HashMap,
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    bus::{AudioBus, DisplayBus, KeypadBus},
+    chip::Chip8,
+    core::error::ExecResult,
+};
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+
+/// The system allocator, counting allocations for [`run`] to report. Only
+/// counted if the binary installs it as its `#[global_allocator]`.
+pub struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+/// Allocations made through [`CountingAllocator`] so far.
+pub fn allocations() -> u64 {
+    ALLOCATIONS.load(Ordering::Relaxed)
+}
+
+/// Time spent executing one kind of instruction.
+#[derive(Debug, Clone)]
+pub struct InstructionTiming {
+    /// Opcode pattern, see [`Instruction::pattern`](crate::instruction::Instruction::pattern).
+    pub pattern: &'static str,
+    pub executed: u64,
+    pub time: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct BenchReport {
+    pub executed: u64,
+    pub elapsed: Duration,
+    /// Allocations while measuring throughput, which should stay at 0.
+    pub allocations: u64,
+    /// Why execution stopped before the time was up, if it did.
+    pub stopped: Option<&'static str>,
+    /// Slowest in total first.
+    pub timings: Vec<InstructionTiming>,
+}
+
+impl BenchReport {
+    /// Millions of instructions per second.
+    pub fn mips(&self) -> f64 {
+        self.executed as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON) / 1e6
+    }
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Executed {} instructions in {:.2} s: {:.2} MIPS",
+            self.executed,
+            self.elapsed.as_secs_f64(),
+            self.mips()
+        )?;
+        if let Some(reason) = self.stopped {
+            writeln!(f, "Stopped early: {}", reason)?;
+        }
+        writeln!(f, "Allocations: {}", self.allocations)?;
+
+        let total = self
+            .timings
+            .iter()
+            .map(|timing| timing.time)
+            .sum::<Duration>()
+            .as_secs_f64()
+            .max(f64::EPSILON);
+        writeln!(f, "\nInstructions, timed one at a time:")?;
+        writeln!(
+            f,
+            "  {:<9}  {:>12}  {:>8}  {:>7}",
+            "", "executed", "ns each", "time"
+        )?;
+        for timing in &self.timings {
+            writeln!(
+                f,
+                "  {:<9}  {:>12}  {:>8.1}  {:>6.2}%",
+                timing.pattern,
+                timing.executed,
+                timing.time.as_nanos() as f64 / timing.executed.max(1) as f64,
+                timing.time.as_secs_f64() * 100.0 / total
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Why `chip` can't execute another instruction right away.
+enum Stall {
+    /// Waiting for the next frame, after a `Dxyn` with the display wait quirk.
+    Frame,
+    /// It won't ever continue, for this reason.
+    Stop(&'static str),
+}
+
+fn stall<D: DisplayBus, K: KeypadBus, A: AudioBus>(chip: &Chip8<D, K, A>) -> Option<Stall> {
+    if chip.exited {
+        Some(Stall::Stop("the program exited"))
+    } else if chip.waiting_for_key.is_some() {
+        Some(Stall::Stop("waiting for a key"))
+    } else if chip.waiting_for_vblank {
+        Some(Stall::Frame)
+    } else {
+        None
+    }
+}
+
+/// Runs `chip` with `rom` loaded as fast as possible for `duration`, in frames
+/// of `instructions_per_frame`, then resets it, loads `rom` again and times
+/// every kind of instruction for another `duration`.
+///
+/// Nothing presses keys, so a program waiting for one ends the run early.
+pub fn run<D: DisplayBus, K: KeypadBus, A: AudioBus>(
+    chip: &mut Chip8<D, K, A>,
+    rom: &[u8],
+    duration: Duration,
+    instructions_per_frame: usize,
+) -> ExecResult<BenchReport> {
+    let mut executed = 0;
+    let mut stopped = None;
+    let allocations_before = allocations();
+    let start = Instant::now();
+    'frames: while start.elapsed() < duration {
+        for _ in 0..instructions_per_frame {
+            match stall(chip) {
+                Some(Stall::Frame) => break,
+                Some(Stall::Stop(reason)) => {
+                    stopped = Some(reason);
+                    break 'frames;
+                }
+                None => {}
+            }
+            chip.step()?;
+            executed += 1;
+        }
+        chip.tick_timers();
+    }
+    let elapsed = start.elapsed();
+    let allocations = allocations() - allocations_before;
+
+    chip.reset();
+    chip.load_rom(rom)?;
+    let mut timings = HashMap::<&'static str, InstructionTiming>::new();
+    let start = Instant::now();
+    'frames: while start.elapsed() < duration {
+        for _ in 0..instructions_per_frame {
+            match stall(chip) {
+                Some(Stall::Frame) => break,
+                Some(Stall::Stop(_)) => break 'frames,
+                None => {}
+            }
+            let instruction = chip.next_instruction()?;
+            let instruction_start = Instant::now();
+            chip.execute(instruction)?;
+            let time = instruction_start.elapsed();
+            let timing = timings
+                .entry(instruction.pattern())
+                .or_insert(InstructionTiming {
+                    pattern: instruction.pattern(),
+                    executed: 0,
+                    time: Duration::ZERO,
+                });
+            timing.executed += 1;
+            timing.time += time;
+        }
+        chip.tick_timers();
+    }
+    let mut timings = timings.into_values().collect::<Vec<_>>();
+    timings.sort_by(|a, b| b.time.cmp(&a.time).then(a.pattern.cmp(b.pattern)));
+
+    Ok(BenchReport {
+        executed,
+        elapsed,
+        allocations,
+        stopped,
+        timings,
+    })
+}
@@ -0,0 +1,266 @@
+//! Decorative bezel: an image drawn around the screen, such as the front of
+//! an arcade cabinet, with a transparent hole in the middle the screen fills.
+//!
+//! The image is scaled to fit the window keeping its aspect ratio, like the
+//! screen in fullscreen, with black bars where the aspect ratios differ.
+
+use std::{borrow::Cow, path::Path};
+
+use cgmath::Vector2;
+use wgpu::util::DeviceExt;
+
+use crate::{
+    camera::BezelLayout,
+    error::{AppError, AppResult},
+};
+
+const BEZEL_SHADER: &str = include_str!("shaders/bezel.wgsl");
+
+/// Bezel image, as 8-bit RGBA pixels.
+pub struct Bezel {
+    width: u32,
+    height: u32,
+    rgba: Vec<u8>,
+    /// Left and top edges, width and height of the hole, in image pixels.
+    hole: [u32; 4],
+}
+
+impl Bezel {
+    /// Reads a PNG image whose middle pixel is fully transparent. The hole is
+    /// as wide and high as the transparent run of pixels through the middle.
+    pub fn load(path: &Path) -> AppResult<Self> {
+        let invalid = |reason: &str| {
+            AppError::InvalidArgument(format!("bezel {}: {}", path.display(), reason))
+        };
+        let mut decoder = png::Decoder::new(std::fs::File::open(path)?);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info()?;
+        let mut data = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut data)?;
+        data.truncate(info.buffer_size());
+        let rgba: Vec<u8> = match info.color_type {
+            png::ColorType::Rgba => data,
+            png::ColorType::Rgb => data
+                .chunks_exact(3)
+                .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 0xFF])
+                .collect(),
+            png::ColorType::GrayscaleAlpha => data
+                .chunks_exact(2)
+                .flat_map(|gray| [gray[0], gray[0], gray[0], gray[1]])
+                .collect(),
+            png::ColorType::Grayscale => data
+                .iter()
+                .flat_map(|&gray| [gray, gray, gray, 0xFF])
+                .collect(),
+            png::ColorType::Indexed => return Err(invalid("unsupported color type")),
+        };
+
+        let (width, height) = (info.width, info.height);
+        let transparent = |x: u32, y: u32| rgba[((y * width + x) * 4 + 3) as usize] == 0;
+        let (center_x, center_y) = (width / 2, height / 2);
+        if !transparent(center_x, center_y) {
+            return Err(invalid("no transparent hole in the middle for the screen"));
+        }
+        let left = (0..center_x)
+            .rev()
+            .find(|&x| !transparent(x, center_y))
+            .map_or(0, |x| x + 1);
+        let right = (center_x..width)
+            .find(|&x| !transparent(x, center_y))
+            .unwrap_or(width);
+        let top = (0..center_y)
+            .rev()
+            .find(|&y| !transparent(center_x, y))
+            .map_or(0, |y| y + 1);
+        let bottom = (center_y..height)
+            .find(|&y| !transparent(center_x, y))
+            .unwrap_or(height);
+        Ok(Self {
+            width,
+            height,
+            rgba,
+            hole: [left, top, right - left, bottom - top],
+        })
+    }
+
+    pub fn layout(&self) -> BezelLayout {
+        BezelLayout {
+            size: Vector2::new(self.width as f32, self.height as f32),
+            hole: self.hole.map(|edge| edge as f32),
+        }
+    }
+}
+
+/// Draws a [`Bezel`] over the screen.
+pub struct BezelPass {
+    pipeline: wgpu::RenderPipeline,
+    rect_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl BezelPass {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+        bezel: &Bezel,
+    ) -> Self {
+        // Keep the colors of the image as they are, however the target stores them.
+        let texture_format = if format.is_srgb() {
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        } else {
+            wgpu::TextureFormat::Rgba8Unorm
+        };
+        let texture = device.create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                label: Some("Bezel Texture"),
+                size: wgpu::Extent3d {
+                    width: bezel.width,
+                    height: bezel.height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: texture_format,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            },
+            wgpu::util::TextureDataOrder::LayerMajor,
+            &bezel.rgba,
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Bezel Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let rect_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Bezel Rect"),
+            size: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Bezel Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Bezel Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: rect_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Bezel Shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(BEZEL_SHADER)),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Bezel Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Bezel Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            pipeline,
+            rect_buffer,
+            bind_group,
+        }
+    }
+
+    /// Draws the bezel over `target` in `rect`, see [`Camera::bezel_viewport`](crate::camera::Camera::bezel_viewport).
+    pub fn render(
+        &self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        rect: [f32; 4],
+    ) {
+        queue.write_buffer(&self.rect_buffer, 0, bytemuck::cast_slice(&rect));
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Bezel Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
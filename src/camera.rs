@@ -1,25 +1,130 @@
-use cgmath::{prelude::*, Matrix4, Vector2, Vector3};
+use std::str::FromStr;
 
-use crate::screen::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use cgmath::{Deg, Matrix4, SquareMatrix, Vector2, Vector3};
+
+/// Clockwise rotation of the displayed screen, for ROMs meant to be played
+/// with the display turned on its side.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    #[default]
+    None,
+    Quarter,
+    Half,
+    ThreeQuarters,
+}
+
+impl Rotation {
+    pub fn degrees(self) -> f32 {
+        match self {
+            Self::None => 0.0,
+            Self::Quarter => 90.0,
+            Self::Half => 180.0,
+            Self::ThreeQuarters => 270.0,
+        }
+    }
+
+    /// Whether the screen is turned on its side, swapping its width and height.
+    pub fn is_sideways(self) -> bool {
+        matches!(self, Self::Quarter | Self::ThreeQuarters)
+    }
+}
+
+impl FromStr for Rotation {
+    type Err = &'static str;
+
+    /// Parses `0`, `90`, `180` or `270` degrees.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "0" => Ok(Self::None),
+            "90" => Ok(Self::Quarter),
+            "180" => Ok(Self::Half),
+            "270" => Ok(Self::ThreeQuarters),
+            _ => Err("expected one of `0`, `90`, `180` or `270`"),
+        }
+    }
+}
 
 pub struct Camera {
     pub position: Vector3<f32>,
     pub size: Vector2<f32>,
+    /// Resolution of the emulated screen, in pixels.
+    pub resolution: Vector2<f32>,
+    /// Fit the screen in the window keeping its aspect ratio, at a whole
+    /// multiple of its resolution if it fits, instead of stretching it.
+    pub letterbox: bool,
+    pub rotation: Rotation,
+    /// Image drawn around the screen, which then fills its hole.
+    pub bezel: Option<BezelLayout>,
+}
+
+/// Size of a bezel image and the hole in it the screen fills, in image pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BezelLayout {
+    pub size: Vector2<f32>,
+    /// Left and top edges, width and height of the hole.
+    pub hole: [f32; 4],
 }
 
 impl Camera {
+    /// Area of the window the screen is drawn in, as its left and top edges,
+    /// width and height in window pixels.
+    pub fn viewport(&self) -> [f32; 4] {
+        if let Some(bezel) = &self.bezel {
+            let [x, y, width, _] = fit(self.size, bezel.size, false);
+            let scale = width / bezel.size.x;
+            let [left, top, width, height] = bezel.hole;
+            return [
+                x + left * scale,
+                y + top * scale,
+                width * scale,
+                height * scale,
+            ];
+        }
+        if !self.letterbox {
+            return [0.0, 0.0, self.size.x, self.size.y];
+        }
+        let resolution = if self.rotation.is_sideways() {
+            Vector2::new(self.resolution.y, self.resolution.x)
+        } else {
+            self.resolution
+        };
+        fit(self.size, resolution, true)
+    }
+
+    /// Area of the window the bezel is drawn in, like [`Camera::viewport`].
+    pub fn bezel_viewport(&self) -> Option<[f32; 4]> {
+        self.bezel.map(|bezel| fit(self.size, bezel.size, false))
+    }
+
     pub fn view_projection_matrix(&self) -> cgmath::Matrix4<f32> {
         let view = Matrix4::from_translation(self.position);
         let proj = cgmath::ortho(
             0.0f32,
-            SCREEN_WIDTH as f32,
-            SCREEN_HEIGHT as f32,
+            self.resolution.x,
+            self.resolution.y,
             0.0f32,
             -1.0f32,
             1.0f32,
         );
-        proj * view
+        // Turning clip space clockwise turns the screen with it.
+        Matrix4::from_angle_z(Deg(-self.rotation.degrees())) * proj * view
+    }
+}
+
+/// Largest area of `content`'s aspect ratio centered in a window of `size`,
+/// scaled by a whole number if `whole` and it fits.
+fn fit(size: Vector2<f32>, content: Vector2<f32>, whole: bool) -> [f32; 4] {
+    let mut scale = (size.x / content.x).min(size.y / content.y);
+    if whole && scale >= 1.0 {
+        scale = scale.floor();
     }
+    let (width, height) = (content.x * scale, content.y * scale);
+    [
+        ((size.x - width) / 2.0).floor(),
+        ((size.y - height) / 2.0).floor(),
+        width,
+        height,
+    ]
 }
 
 #[repr(C)]
@@ -39,3 +144,9 @@ impl CameraUniform {
         self.view_projection = camera.view_projection_matrix().into();
     }
 }
+
+impl Default for CameraUniform {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,241 @@
+//! Compatibility self-check running test ROMs headlessly on every checked
+//! platform and comparing their final screens with expected PBM images, as
+//! written by `--headless`.
+//!
+//! The test ROMs in `roms/check` are built into the emulator along with their
+//! expected screens: `flags` covers arithmetic, flags and skips, and `quirks`
+//! shows which quirks a platform has. Their Octo sources list what each
+//! digit on the screen means. A directory of other ROMs, such as the
+//! [Timendus CHIP-8 test suite](https://github.com/Timendus/chip8-test-suite),
+//! can be checked as well, with the expected image of each next to it:
+//! `<name>.<platform>.pbm`, falling back to `<name>.pbm`. ROMs without an
+//! expected image are reported as unknown.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    chip::Chip8,
+    error::{AppError, AppResult},
+    pbm,
+    platform::Platform,
+};
+
+/// Platforms checked by default, matching the profiles of the Timendus quirks test.
+pub const CHECKED_PLATFORMS: [Platform; 4] = [
+    Platform::Vip,
+    Platform::Chip48,
+    Platform::Schip,
+    Platform::XoChip,
+];
+
+/// A test ROM built into the emulator.
+struct BundledRom {
+    name: &'static str,
+    rom: &'static [u8],
+    /// Expected images by platform, with `None` for every other platform.
+    images: &'static [(Option<Platform>, &'static str)],
+}
+
+impl BundledRom {
+    fn expected_image(&self, platform: Platform) -> Option<&'static str> {
+        self.images
+            .iter()
+            .find(|(image_platform, _)| *image_platform == Some(platform))
+            .or_else(|| {
+                self.images
+                    .iter()
+                    .find(|(image_platform, _)| image_platform.is_none())
+            })
+            .map(|(_, image)| *image)
+    }
+}
+
+const BUNDLED_ROMS: [BundledRom; 2] = [
+    BundledRom {
+        name: "flags.ch8",
+        rom: include_bytes!("../roms/check/flags.ch8"),
+        images: &[(None, include_str!("../roms/check/flags.pbm"))],
+    },
+    BundledRom {
+        name: "quirks.ch8",
+        rom: include_bytes!("../roms/check/quirks.ch8"),
+        images: &[
+            (
+                Some(Platform::Vip),
+                include_str!("../roms/check/quirks.vip.pbm"),
+            ),
+            (
+                Some(Platform::Chip48),
+                include_str!("../roms/check/quirks.chip48.pbm"),
+            ),
+            (
+                Some(Platform::Schip),
+                include_str!("../roms/check/quirks.schip.pbm"),
+            ),
+            (
+                Some(Platform::XoChip),
+                include_str!("../roms/check/quirks.xochip.pbm"),
+            ),
+        ],
+    },
+];
+
+/// Address read by the Timendus quirks test to pick its platform without a menu.
+const PLATFORM_SELECT_ADDRESS: usize = 0x1FF;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Pass,
+    Fail,
+    /// The ROM stopped with an execution error.
+    Error,
+    /// No expected image to compare with.
+    Unknown,
+}
+
+impl Outcome {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Pass => "pass",
+            Self::Fail => "FAIL",
+            Self::Error => "error",
+            Self::Unknown => "?",
+        }
+    }
+}
+
+/// Outcomes of one ROM, in the order of the checked platforms.
+pub struct RomReport {
+    pub name: String,
+    pub outcomes: Vec<Outcome>,
+}
+
+/// Runs the test ROMs built into the emulator for `frames` frames of
+/// `instructions_per_frame` instructions on each of `platforms`.
+pub fn run_bundled(
+    platforms: &[Platform],
+    frames: usize,
+    instructions_per_frame: usize,
+) -> AppResult<Vec<RomReport>> {
+    let mut reports = Vec::with_capacity(BUNDLED_ROMS.len());
+    for bundled in &BUNDLED_ROMS {
+        let mut outcomes = Vec::with_capacity(platforms.len());
+        for &platform in platforms {
+            outcomes.push(check_rom(
+                bundled.name,
+                bundled.rom,
+                bundled.expected_image(platform),
+                platform,
+                frames,
+                instructions_per_frame,
+            )?);
+        }
+        reports.push(RomReport {
+            name: bundled.name.to_owned(),
+            outcomes,
+        });
+    }
+    Ok(reports)
+}
+
+/// Runs every `.ch8` ROM in `dir` for `frames` frames of `instructions_per_frame`
+/// instructions on each of `platforms`.
+pub fn run_suite(
+    dir: &Path,
+    platforms: &[Platform],
+    frames: usize,
+    instructions_per_frame: usize,
+) -> AppResult<Vec<RomReport>> {
+    let mut roms = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<PathBuf>, _>>()?;
+    roms.retain(|path| path.extension().is_some_and(|extension| extension == "ch8"));
+    roms.sort();
+    if roms.is_empty() {
+        return Err(AppError::InvalidArgument(format!(
+            "no .ch8 test ROMs in {}",
+            dir.display()
+        )));
+    }
+
+    let mut reports = Vec::with_capacity(roms.len());
+    for path in roms {
+        let rom = fs::read(&path)?;
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let mut outcomes = Vec::with_capacity(platforms.len());
+        for &platform in platforms {
+            let expected = expected_image(&path, platform)?;
+            outcomes.push(check_rom(
+                &name,
+                &rom,
+                expected.as_deref(),
+                platform,
+                frames,
+                instructions_per_frame,
+            )?);
+        }
+        reports.push(RomReport { name, outcomes });
+    }
+    Ok(reports)
+}
+
+fn check_rom(
+    name: &str,
+    rom: &[u8],
+    expected: Option<&str>,
+    platform: Platform,
+    frames: usize,
+    instructions_per_frame: usize,
+) -> AppResult<Outcome> {
+    let Some(expected) = expected else {
+        return Ok(Outcome::Unknown);
+    };
+    let Some((width, height, pixels)) = pbm::decode(expected) else {
+        return Err(AppError::InvalidArgument(format!(
+            "malformed expected image for {} on {}",
+            name, platform
+        )));
+    };
+
+    let mut chip = Chip8::with_config(platform.config());
+    chip.quirks = platform.quirks();
+    chip.seed_rng(0);
+    // The other test ROMs leave this byte alone.
+    chip.memory[PLATFORM_SELECT_ADDRESS] = match platform {
+        Platform::Schip => 2,
+        Platform::XoChip => 3,
+        _ => 1,
+    };
+    chip.load_rom(rom)?;
+    for _ in 0..frames {
+        if chip.run_frame(instructions_per_frame).is_err() {
+            return Ok(Outcome::Error);
+        }
+    }
+
+    Ok(if pbm::matches(&chip.display, width, height, &pixels) {
+        Outcome::Pass
+    } else {
+        Outcome::Fail
+    })
+}
+
+/// Reads the expected image of the ROM at `path` for `platform`, if there is one.
+fn expected_image(path: &Path, platform: Platform) -> AppResult<Option<String>> {
+    let candidates = [
+        path.with_extension(format!("{}.pbm", platform)),
+        path.with_extension("pbm"),
+    ];
+    for candidate in candidates {
+        if candidate.exists() {
+            return Ok(Some(fs::read_to_string(candidate)?));
+        }
+    }
+    Ok(None)
+}
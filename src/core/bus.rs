@@ -0,0 +1,120 @@
+//! Interfaces the interpreter uses to talk to its peripherals.
+//!
+//! Implement these to plug the core into a custom frontend (terminal, embedded
+//! display, test harness) without touching the instruction loop.
+
+/// Size of the XO-CHIP audio pattern buffer, 128 one-bit samples.
+pub const AUDIO_PATTERN_SIZE: usize = 16;
+/// Pitch at which the audio pattern plays at 4000 samples per second.
+pub const DEFAULT_PITCH: u8 = 64;
+
+use crate::core::{megachip::BlendMode, screen::Resolution};
+
+/// Framebuffer the interpreter draws sprites into.
+///
+/// Only [`DisplayBus::clear`] and [`DisplayBus::toggle`] are required; the
+/// other methods back the SUPER-CHIP, XO-CHIP and Mega-Chip extensions and do
+/// nothing by default.
+pub trait DisplayBus {
+    /// Turns every pixel of the selected planes off.
+    fn clear(&mut self);
+
+    /// Flips the pixel at (`x`, `y`) and returns whether it was previously on.
+    fn toggle(&mut self, x: u8, y: u8) -> bool;
+
+    /// Flips the pixel at (`x`, `y`) in bit plane `plane` (XO-CHIP).
+    fn toggle_plane(&mut self, _plane: u8, x: u8, y: u8) -> bool {
+        self.toggle(x, y)
+    }
+
+    /// Selects the bit planes, as a mask, affected by drawing, clearing and
+    /// scrolling (XO-CHIP).
+    fn select_planes(&mut self, _planes: u8) {}
+
+    fn selected_planes(&self) -> u8 {
+        1
+    }
+
+    /// Switches resolution, clearing the display. Displays that only support
+    /// 64×32 ignore it.
+    fn set_resolution(&mut self, _resolution: Resolution) {}
+
+    fn resolution(&self) -> Resolution {
+        Resolution::Low
+    }
+
+    /// Width and height of the display in its current resolution.
+    fn size(&self) -> (usize, usize) {
+        self.resolution().size()
+    }
+
+    /// Switches the CHIP-8X color board on or off, resetting its colors.
+    fn set_color_mode(&mut self, _enabled: bool) {}
+
+    /// Advances the CHIP-8X background to the next color (`02A0`).
+    fn cycle_background(&mut self) {}
+
+    /// Sets the CHIP-8X foreground `color` of the 8×1 pixel zones from column
+    /// `left` to `right` and row `top` to `bottom`, inclusive.
+    fn set_foreground(&mut self, _left: u8, _right: u8, _top: u8, _bottom: u8, _color: u8) {}
+
+    /// Scrolls the picture down by `rows` pixels.
+    fn scroll_down(&mut self, _rows: u8) {}
+
+    /// Scrolls the picture up by `rows` pixels (XO-CHIP).
+    fn scroll_up(&mut self, _rows: u8) {}
+
+    /// Scrolls the picture right by `columns` pixels.
+    fn scroll_right(&mut self, _columns: u8) {}
+
+    /// Scrolls the picture left by `columns` pixels.
+    fn scroll_left(&mut self, _columns: u8) {}
+
+    /// Sets entry `index` of the Mega-Chip palette to `color`, as `0xAARRGGBB` (`02nn`).
+    fn set_palette(&mut self, _index: u8, _color: u32) {}
+
+    /// Mixes palette entry `index` into the Mega-Chip back buffer at (`x`, `y`)
+    /// by `mode`, and returns the entry drawn there before.
+    fn blend(&mut self, _x: u8, _y: u8, _index: u8, _mode: BlendMode) -> u8 {
+        0
+    }
+
+    /// Shows the Mega-Chip back buffer over the picture with opacity `alpha`,
+    /// and clears it for the next one (`00E0`).
+    fn present(&mut self, _alpha: u8) {}
+}
+
+/// Hexadecimal keypad with keys `0x0` through `0xF`.
+pub trait KeypadBus {
+    fn is_pressed(&self, key: u8) -> bool;
+}
+
+/// Buzzer driven by the sound timer.
+pub trait AudioBus {
+    /// Called whenever the buzzer should start (`true`) or stop (`false`) sounding.
+    fn set_tone(&mut self, on: bool);
+
+    /// Called when an XO-CHIP program loads a new 1-bit sample pattern (`F002`).
+    fn set_pattern(&mut self, _pattern: &[u8; AUDIO_PATTERN_SIZE]) {}
+
+    /// Called when an XO-CHIP program changes the playback pitch (`Fx3A`).
+    ///
+    /// The pattern plays at `4000 * 2^((pitch - 64) / 48)` bits per second.
+    fn set_pitch(&mut self, _pitch: u8) {}
+
+    /// Called when a Mega-Chip program plays a sound (`060n`): 8-bit unsigned
+    /// `samples` at `rate` samples per second, repeated if `looping`. It
+    /// replaces any sound playing.
+    fn play_sample(&mut self, _samples: &[u8], _rate: u16, _looping: bool) {}
+
+    /// Called when a Mega-Chip program stops its sound (`0700`), and on reset.
+    fn stop_sample(&mut self) {}
+}
+
+/// Audio bus that ignores the buzzer.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoAudio;
+
+impl AudioBus for NoAudio {
+    fn set_tone(&mut self, _on: bool) {}
+}
@@ -0,0 +1,1208 @@
+use crate::core::{
+    bus::{AudioBus, DisplayBus, KeypadBus, NoAudio, AUDIO_PATTERN_SIZE, DEFAULT_PITCH},
+    config::{MachineConfig, BIG_FONT_SIZE, FONT_SIZE, MAX_STACK_DEPTH},
+    error::{ExecError, ExecResult},
+    instruction::Instruction,
+    keypad::{Keypad, KEY_COUNT},
+    megachip::{self, MegaRegisters, Sample, ADDRESS_MASK, SAMPLE_HEADER_SIZE},
+    quirks::{Quirks, SpriteEdge},
+    rng::Rng,
+    screen::{Resolution, Screen, PLANE_COUNT},
+};
+
+pub const REGISTER_COUNT: usize = 16;
+/// Number of `Fx75`/`Fx85` flag registers. SUPER-CHIP has 8, XO-CHIP 16.
+pub const FLAG_COUNT: usize = 16;
+
+const FONT_SPRITES: [[u8; 5]; 16] = [
+    [0xF0, 0x90, 0x90, 0x90, 0xF0], // 0
+    [0x20, 0x60, 0x20, 0x20, 0x70], // 1
+    [0xF0, 0x10, 0xF0, 0x80, 0xF0], // 2
+    [0xF0, 0x10, 0xF0, 0x10, 0xF0], // 3
+    [0x90, 0x90, 0xF0, 0x10, 0x10], // 4
+    [0xF0, 0x80, 0xF0, 0x10, 0xF0], // 5
+    [0xF0, 0x80, 0xF0, 0x90, 0xF0], // 6
+    [0xF0, 0x10, 0x20, 0x40, 0x40], // 7
+    [0xF0, 0x90, 0xF0, 0x90, 0xF0], // 8
+    [0xF0, 0x90, 0xF0, 0x10, 0xF0], // 9
+    [0xF0, 0x90, 0xF0, 0x90, 0x90], // A
+    [0xE0, 0x90, 0xE0, 0x90, 0xE0], // B
+    [0xF0, 0x80, 0x80, 0x80, 0xF0], // C
+    [0xE0, 0x90, 0x90, 0x90, 0xE0], // D
+    [0xF0, 0x80, 0xF0, 0x80, 0xF0], // E
+    [0xF0, 0x80, 0xF0, 0x80, 0x80], // F
+];
+
+/// SUPER-CHIP 8×10 digits, used by `Fx30`. SUPER-CHIP 1.1 only has 0 to 9,
+/// A to F follow the same style for XO-CHIP programs.
+const BIG_FONT_SPRITES: [[u8; 10]; 16] = [
+    [0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C], // 0
+    [0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C], // 1
+    [0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF], // 2
+    [0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C], // 3
+    [0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06], // 4
+    [0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C], // 5
+    [0x3E, 0x7C, 0xC0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C], // 6
+    [0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60], // 7
+    [0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C], // 8
+    [0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C], // 9
+    [0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3], // A
+    [0xFC, 0xFE, 0xC3, 0xC3, 0xFE, 0xFE, 0xC3, 0xC3, 0xFE, 0xFC], // B
+    [0x3C, 0x7E, 0xE7, 0xC0, 0xC0, 0xC0, 0xC0, 0xE7, 0x7E, 0x3C], // C
+    [0xFC, 0xFE, 0xC7, 0xC3, 0xC3, 0xC3, 0xC3, 0xC7, 0xFE, 0xFC], // D
+    [0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFC, 0xC0, 0xC0, 0xFF, 0xFF], // E
+    [0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFC, 0xC0, 0xC0, 0xC0, 0xC0], // F
+];
+
+/// Pixels scrolled horizontally by `00FB` and `00FC`.
+const SCROLL_COLUMNS: u8 = 4;
+
+/// Writes both fonts to `memory` from `font_address`.
+fn load_fonts(memory: &mut [u8], font_address: usize) {
+    for (i, sprite) in FONT_SPRITES.iter().enumerate() {
+        let address = font_address + i * 5;
+        memory[address..address + 5].copy_from_slice(sprite);
+    }
+    for (i, sprite) in BIG_FONT_SPRITES.iter().enumerate() {
+        let address = font_address + FONT_SIZE + i * 10;
+        memory[address..address + 10].copy_from_slice(sprite);
+    }
+}
+
+/// Storage of [`Chip8::memory`], at least as large as the configured memory
+/// size: a boxed slice, an array or a `&mut [u8]` the caller provides.
+pub trait Memory: AsRef<[u8]> + AsMut<[u8]> {
+    /// Makes room for `size` bytes if it can, returning whether there is.
+    /// Mega-Chip machines grow their memory to fit the ROM.
+    fn grow(&mut self, size: usize) -> bool {
+        self.as_ref().len() >= size
+    }
+}
+
+impl<const N: usize> Memory for [u8; N] {}
+
+impl Memory for &mut [u8] {}
+
+#[cfg(feature = "std")]
+impl Memory for Box<[u8]> {
+    fn grow(&mut self, size: usize) -> bool {
+        if self.len() < size {
+            let mut memory = std::mem::take(self).into_vec();
+            memory.resize(size, 0);
+            *self = memory.into_boxed_slice();
+        }
+        true
+    }
+}
+
+/// Memory of machines created with [`Chip8::with_buses`]. With `std` it is
+/// allocated on the heap in the configured size. Without it is an array of
+/// [`DEFAULT_MEMORY_SIZE`](crate::core::config::DEFAULT_MEMORY_SIZE) bytes, so larger memories need
+/// [`Chip8::with_memory`] and a buffer of their size.
+#[cfg(feature = "std")]
+pub type DefaultMemory = Box<[u8]>;
+#[cfg(not(feature = "std"))]
+pub type DefaultMemory = [u8; crate::core::config::DEFAULT_MEMORY_SIZE];
+
+/// How memory accesses past the end of memory are handled.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "std",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum MemoryMode {
+    /// Addresses wrap around to the start of memory.
+    #[default]
+    Wrap,
+    /// Out of bounds accesses stop execution with [`ExecError::MemoryOutOfBounds`].
+    Strict,
+}
+
+/// CHIP-8 interpreter state and instruction execution.
+///
+/// The display, keypad and buzzer are reached through the [`DisplayBus`],
+/// [`KeypadBus`] and [`AudioBus`] traits, defaulting to the built-in [`Screen`]
+/// and [`Keypad`] with no audio.
+///
+/// The stack is backed by an array of the largest supported depth, of which
+/// only the part selected by the [`MachineConfig`] is used, and memory by a
+/// [`Memory`] of at least the configured size.
+pub struct Chip8<D = Screen, K = Keypad, A = NoAudio, M = DefaultMemory> {
+    pub memory: M,
+    pub registers: [u8; REGISTER_COUNT],
+    /// Address register, 16 bits wide except on Mega-Chip, where it has 24.
+    pub register_i: u32,
+    pub pc: u16,
+    pub stack: [u16; MAX_STACK_DEPTH],
+    /// Address each `CALL` on the stack entered, recorded as it executes.
+    pub call_entries: [u16; MAX_STACK_DEPTH],
+    pub sp: u8,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub display: D,
+    pub keypad: K,
+    pub audio: A,
+    /// Register waiting for a key press (`Fx0A`), if any.
+    pub waiting_for_key: Option<usize>,
+    /// Key pressed during an `Fx0A` wait, pending its release.
+    pub key_wait_pressed: Option<u8>,
+    /// Execution is halted until the next timer tick, after a `Dxyn` with
+    /// `Quirks::display_wait` enabled.
+    pub waiting_for_vblank: bool,
+    /// The program ended with `00FD`. Nothing is executed until a reset.
+    pub exited: bool,
+    /// Instructions executed since the machine was created, wrapping around.
+    /// Not part of the machine state, so resets and save states leave it be.
+    pub executed: u64,
+    /// XO-CHIP 1-bit sample pattern, loaded by `F002`.
+    pub audio_pattern: [u8; AUDIO_PATTERN_SIZE],
+    /// XO-CHIP playback pitch, set by `Fx3A`.
+    pub pitch: u8,
+    /// SUPER-CHIP flag registers, saved by `Fx75`. They model the HP-48 user
+    /// flags and are kept across resets.
+    pub flags: [u8; FLAG_COUNT],
+    /// Mega-Chip sprite, blending and sound registers.
+    pub mega: MegaRegisters,
+    pub quirks: Quirks,
+    config: MachineConfig,
+    /// Source of `Cxkk` random bytes. Replace or reseed it for reproducible runs.
+    pub rng: Rng,
+}
+
+impl Chip8 {
+    /// Creates a machine with a randomly seeded RNG.
+    #[cfg(feature = "std")]
+    pub fn new() -> Self {
+        Self::with_config(MachineConfig::new())
+    }
+
+    /// Creates a machine with a custom memory layout.
+    pub fn with_config(config: MachineConfig) -> Self {
+        Self::with_buses(config, Screen::new(), Keypad::new(), NoAudio)
+    }
+
+    /// Creates a machine whose `Cxkk` results are derived from `seed`.
+    ///
+    /// Without `std` there is no entropy source, so use this or reseed with
+    /// [`Chip8::seed_rng`].
+    pub fn with_seed(seed: u64) -> Self {
+        let mut chip = Self::with_config(MachineConfig::new());
+        chip.seed_rng(seed);
+        chip
+    }
+}
+
+impl<D: DisplayBus, K: KeypadBus, A: AudioBus> Chip8<D, K, A> {
+    /// Creates a machine attached to custom peripherals, with [`DefaultMemory`].
+    ///
+    /// Without `std`, panics if the configured memory is larger than
+    /// [`DEFAULT_MEMORY_SIZE`](crate::core::config::DEFAULT_MEMORY_SIZE).
+    pub fn with_buses(config: MachineConfig, display: D, keypad: K, audio: A) -> Self {
+        #[cfg(feature = "std")]
+        let memory = vec![0; config.memory_size].into_boxed_slice();
+        #[cfg(not(feature = "std"))]
+        let memory = [0; crate::core::config::DEFAULT_MEMORY_SIZE];
+        Self::with_memory(config, memory, display, keypad, audio)
+    }
+}
+
+impl<D: DisplayBus, K: KeypadBus, A: AudioBus, M: Memory> Chip8<D, K, A, M> {
+    /// Creates a machine attached to custom peripherals, running in `memory`,
+    /// which is cleared first.
+    ///
+    /// The RNG is seeded from entropy with `std`, and with a fixed seed otherwise.
+    ///
+    /// Panics if `memory` is smaller than the configured memory size.
+    pub fn with_memory(
+        config: MachineConfig,
+        mut memory: M,
+        mut display: D,
+        keypad: K,
+        audio: A,
+    ) -> Self {
+        #[cfg(feature = "std")]
+        let rng = Rng::from_entropy();
+        #[cfg(not(feature = "std"))]
+        let rng = Rng::from_seed(0);
+
+        assert!(
+            memory.as_ref().len() >= config.memory_size,
+            "memory is smaller than the configured size"
+        );
+        // Sprite data should be stored in the interpreter area of Chip-8 memory (0x000 to 0x1FF).
+        assert!(
+            config.font_address as usize + FONT_SIZE + BIG_FONT_SIZE <= config.memory_size,
+            "font does not fit in memory"
+        );
+        memory.as_mut().fill(0);
+        load_fonts(memory.as_mut(), config.font_address as usize);
+
+        display.set_resolution(config.resolution);
+        display.set_color_mode(config.chip8x);
+
+        Self {
+            memory,
+            registers: [0; REGISTER_COUNT],
+            register_i: 0,
+            pc: config.start_address(),
+            stack: [0; MAX_STACK_DEPTH],
+            call_entries: [0; MAX_STACK_DEPTH],
+            sp: 0,
+            delay_timer: 0,
+            sound_timer: 0,
+            display,
+            keypad,
+            audio,
+            waiting_for_key: None,
+            key_wait_pressed: None,
+            waiting_for_vblank: false,
+            exited: false,
+            executed: 0,
+            audio_pattern: [0; AUDIO_PATTERN_SIZE],
+            pitch: DEFAULT_PITCH,
+            flags: [0; FLAG_COUNT],
+            mega: MegaRegisters::new(),
+            quirks: Quirks::new(),
+            config,
+            rng,
+        }
+    }
+
+    pub fn config(&self) -> &MachineConfig {
+        &self.config
+    }
+
+    /// The part of memory addressable with the configured memory size.
+    pub fn memory(&self) -> &[u8] {
+        &self.memory.as_ref()[..self.config.memory_size]
+    }
+
+    /// Copies the ROM into memory at the program start address.
+    ///
+    /// Mega-Chip memory grows to fit the ROM first, as far as the [`Memory`]
+    /// allows, see [`megachip::memory_size`].
+    pub fn load_rom(&mut self, rom: &[u8]) -> ExecResult<()> {
+        let start = self.config.program_start as usize;
+        if self.config.megachip {
+            let size = megachip::memory_size(self.config.program_start, rom.len());
+            if size > self.config.memory_size && self.memory.grow(size) {
+                self.config.memory_size = size;
+            }
+        }
+        let capacity = self.config.memory_size.saturating_sub(start);
+        if rom.len() > capacity {
+            return Err(ExecError::RomTooLarge {
+                size: rom.len(),
+                capacity,
+            });
+        }
+        self.memory.as_mut()[start..start + rom.len()].copy_from_slice(rom);
+        Ok(())
+    }
+
+    /// Restarts the machine: clears memory but for the fonts, registers,
+    /// stack, timers and the display, which returns to its initial resolution.
+    /// The flag registers are kept.
+    ///
+    /// The machine keeps no copy of the ROM, so load it again with
+    /// [`Chip8::load_rom`] to run it from the start.
+    pub fn reset(&mut self) {
+        self.memory.as_mut().fill(0);
+        load_fonts(self.memory.as_mut(), self.config.font_address as usize);
+        self.registers = [0; REGISTER_COUNT];
+        self.register_i = 0;
+        self.pc = self.config.start_address();
+        self.stack = [0; MAX_STACK_DEPTH];
+        self.call_entries = [0; MAX_STACK_DEPTH];
+        self.sp = 0;
+        self.delay_timer = 0;
+        if self.sound_timer > 0 {
+            self.sound_timer = 0;
+            self.audio.set_tone(false);
+        }
+        self.waiting_for_key = None;
+        self.key_wait_pressed = None;
+        self.waiting_for_vblank = false;
+        self.exited = false;
+        self.audio_pattern = [0; AUDIO_PATTERN_SIZE];
+        self.pitch = DEFAULT_PITCH;
+        self.audio.set_pattern(&self.audio_pattern);
+        self.audio.set_pitch(self.pitch);
+        if self.mega.sample.is_some() {
+            self.audio.stop_sample();
+        }
+        self.mega = MegaRegisters::new();
+        self.display.select_planes(1);
+        self.display.clear();
+        self.display.set_resolution(self.config.resolution);
+        self.display.set_color_mode(self.config.chip8x);
+    }
+
+    /// Reseeds the RNG used by `Cxkk`.
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng = Rng::from_seed(seed);
+    }
+
+    /// Decrements the delay and sound timers. Should be called at 60 Hz.
+    ///
+    /// This also marks the vertical blank, ending a `Dxyn` display wait.
+    pub fn tick_timers(&mut self) {
+        self.waiting_for_vblank = false;
+        self.delay_timer = self.delay_timer.saturating_sub(1);
+        if self.sound_timer > 0 {
+            self.sound_timer -= 1;
+            if self.sound_timer == 0 {
+                self.audio.set_tone(false);
+            }
+        }
+    }
+
+    /// Executes `cycles` instructions without touching the timers.
+    pub fn run_for(&mut self, cycles: usize) -> ExecResult<()> {
+        for _ in 0..cycles {
+            self.step()?;
+        }
+        Ok(())
+    }
+
+    /// Executes one 60 Hz frame: `instructions` instructions followed by a timer tick.
+    pub fn run_frame(&mut self, instructions: usize) -> ExecResult<()> {
+        for _ in 0..instructions {
+            self.step()?;
+        }
+        self.tick_timers();
+        Ok(())
+    }
+
+    /// Reads the opcode at the program counter.
+    pub fn fetch(&self) -> ExecResult<u16> {
+        self.read_opcode(self.pc as usize)
+    }
+
+    fn read_opcode(&self, address: usize) -> ExecResult<u16> {
+        Ok((self.read_memory(address)? as u16) << 8 | self.read_memory(address + 1)? as u16)
+    }
+
+    /// Advances the program counter by the size of the next instruction, which
+    /// takes four bytes if it is the XO-CHIP `F000 nnnn`.
+    fn skip_next_instruction(&mut self) -> ExecResult<()> {
+        let size = self
+            .decode_at(self.pc as usize + 2)?
+            .map_or(2, |instruction| instruction.size());
+        self.pc = self.program_address(self.pc as usize + size as usize);
+        Ok(())
+    }
+
+    /// Wraps a new program counter into memory with [`MemoryMode::Wrap`]. With
+    /// [`MemoryMode::Strict`] it is kept, so fetching from it fails.
+    fn program_address(&self, address: usize) -> u16 {
+        match self.config.memory_mode {
+            MemoryMode::Wrap => (address % self.config.memory_size) as u16,
+            MemoryMode::Strict => address as u16,
+        }
+    }
+
+    /// Decodes the instruction at `address`, with the CHIP-8X or Mega-Chip
+    /// instructions enabled by the configuration.
+    fn decode_at(&self, address: usize) -> ExecResult<Option<Instruction>> {
+        let opcode = self.read_opcode(address)?;
+        let next = if Instruction::is_long(opcode, self.config.megachip) {
+            self.read_opcode(address + 2)?
+        } else {
+            0
+        };
+        Ok(if self.config.chip8x {
+            Instruction::decode_chip8x(opcode)
+        } else if self.config.megachip {
+            Instruction::decode_mega(opcode, next)
+        } else {
+            Instruction::decode_long(opcode, next)
+        })
+    }
+
+    /// Resolves an address according to the memory mode.
+    fn memory_address(&self, address: usize) -> ExecResult<usize> {
+        let memory_size = self.config.memory_size;
+        if address < memory_size {
+            return Ok(address);
+        }
+        match self.config.memory_mode {
+            MemoryMode::Wrap => Ok(address % memory_size),
+            MemoryMode::Strict => Err(ExecError::MemoryOutOfBounds {
+                pc: self.pc,
+                address,
+            }),
+        }
+    }
+
+    pub fn read_memory(&self, address: usize) -> ExecResult<u8> {
+        Ok(self.memory.as_ref()[self.memory_address(address)?])
+    }
+
+    pub fn write_memory(&mut self, address: usize, value: u8) -> ExecResult<()> {
+        let address = self.memory_address(address)?;
+        self.memory.as_mut()[address] = value;
+        Ok(())
+    }
+
+    /// Fetches, decodes and executes a single instruction.
+    ///
+    /// On error the machine is left untouched, with the program counter still
+    /// pointing at the offending instruction.
+    pub fn step(&mut self) -> ExecResult<()> {
+        self.execute_next().map(|_| ())
+    }
+
+    /// Like [`Chip8::step`], returning the instruction executed. Nothing is
+    /// executed while the machine waits for a key or the vertical blank, or
+    /// has exited.
+    pub fn execute_next(&mut self) -> ExecResult<Option<Instruction>> {
+        if self.exited || self.waiting_for_vblank || !self.poll_key_wait() {
+            return Ok(None);
+        }
+        let instruction = self.next_instruction()?;
+        self.execute(instruction)?;
+        Ok(Some(instruction))
+    }
+
+    /// Resolves a pending `Fx0A` key wait. Returns whether execution may continue.
+    pub fn poll_key_wait(&mut self) -> bool {
+        let Some(x) = self.waiting_for_key else {
+            return true;
+        };
+
+        let key = match self.key_wait_pressed {
+            Some(key) if self.keypad.is_pressed(key) => return false,
+            Some(key) => key,
+            None => {
+                let Some(key) = (0..KEY_COUNT as u8).find(|&key| self.keypad.is_pressed(key))
+                else {
+                    return false;
+                };
+                if self.quirks.key_wait_release {
+                    self.key_wait_pressed = Some(key);
+                    return false;
+                }
+                key
+            }
+        };
+
+        self.registers[x] = key;
+        self.waiting_for_key = None;
+        self.key_wait_pressed = None;
+        true
+    }
+
+    /// Fetches and decodes the instruction at the program counter.
+    pub fn next_instruction(&self) -> ExecResult<Instruction> {
+        match self.decode_at(self.pc as usize)? {
+            Some(instruction) => Ok(instruction),
+            None => Err(ExecError::UnknownOpcode {
+                opcode: self.fetch()?,
+                pc: self.pc,
+            }),
+        }
+    }
+
+    /// Draws a 1-bit sprite at I into each selected plane. Returns whether any
+    /// pixel was erased.
+    fn draw_planes(&mut self, x: u8, y: u8, n: u8) -> ExecResult<bool> {
+        let (width, height, row_len) = if n == 0 { (16u8, 16u8, 2) } else { (8, n, 1) };
+        let (screen_width, screen_height) = self.display.size();
+        let origin_x = self.registers[x as usize] as usize % screen_width;
+        let origin_y = self.registers[y as usize] as usize % screen_height;
+        let clip = self.quirks.sprite_edge == SpriteEdge::Clip;
+        let planes = self.display.selected_planes();
+        let sprite_len = height as usize * row_len;
+        let mut address = self.register_i as usize;
+        if planes != 0 {
+            self.memory_address(address + sprite_len * planes.count_ones() as usize - 1)?;
+        }
+
+        let mut collided = false;
+        for plane in (0..PLANE_COUNT as u8).filter(|plane| planes & (1 << plane) != 0) {
+            for y_pixel in 0..height {
+                let mut row = (self.read_memory(address)? as u16) << 8;
+                if row_len == 2 {
+                    row |= self.read_memory(address + 1)? as u16;
+                }
+                address += row_len;
+                let pixel_y = origin_y + y_pixel as usize;
+                if clip && pixel_y >= screen_height {
+                    continue;
+                }
+                for x_pixel in 0..width {
+                    let pixel_x = origin_x + x_pixel as usize;
+                    if clip && pixel_x >= screen_width {
+                        break;
+                    }
+                    if (row & 0x8000) > 0
+                        && self.display.toggle_plane(
+                            plane,
+                            (pixel_x % screen_width) as u8,
+                            (pixel_y % screen_height) as u8,
+                        )
+                    {
+                        collided = true;
+                    }
+                    row <<= 1;
+                }
+            }
+        }
+        Ok(collided)
+    }
+
+    /// Draws a Mega-Chip sprite at I, a byte per pixel naming its palette
+    /// entry, into the back buffer. Returns whether it covered a pixel of the
+    /// collision color.
+    fn draw_mega(&mut self, x: u8, y: u8) -> ExecResult<bool> {
+        let (width, height) = self.mega.sprite_size();
+        let (screen_width, screen_height) = self.display.size();
+        let origin_x = self.registers[x as usize] as usize % screen_width;
+        let origin_y = self.registers[y as usize] as usize % screen_height;
+        let clip = self.quirks.sprite_edge == SpriteEdge::Clip;
+        let address = self.register_i as usize;
+        self.memory_address(address + width * height - 1)?;
+
+        let mut collided = false;
+        for y_pixel in 0..height {
+            let pixel_y = origin_y + y_pixel;
+            if clip && pixel_y >= screen_height {
+                break;
+            }
+            for x_pixel in 0..width {
+                let pixel_x = origin_x + x_pixel;
+                if clip && pixel_x >= screen_width {
+                    break;
+                }
+                let index = self.read_memory(address + y_pixel * width + x_pixel)?;
+                if index == 0 {
+                    continue;
+                }
+                let covered = self.display.blend(
+                    (pixel_x % screen_width) as u8,
+                    (pixel_y % screen_height) as u8,
+                    index,
+                    self.mega.blend_mode,
+                );
+                if covered != 0 && covered == self.mega.collision_color {
+                    collided = true;
+                }
+            }
+        }
+        Ok(collided)
+    }
+
+    /// Bits of `I`, 24 on Mega-Chip and 16 otherwise.
+    fn address_mask(&self) -> u32 {
+        if self.config.megachip {
+            ADDRESS_MASK
+        } else {
+            0xFFFF
+        }
+    }
+
+    /// Clears `VF` after a logical operation, according to `Quirks::vf_reset`.
+    fn reset_vf(&mut self) {
+        if self.quirks.vf_reset {
+            self.registers[0xF] = 0;
+        }
+    }
+
+    /// Register shifted by `8xy6` and `8xyE`, according to `Quirks::shift_vy`.
+    fn shift_source(&self, x: u8, y: u8) -> usize {
+        if self.quirks.shift_vy {
+            y as usize
+        } else {
+            x as usize
+        }
+    }
+
+    /// Advances `I` after `Fx55` or `Fx65`, according to `Quirks::memory_increment`.
+    fn advance_i(&mut self, x: u8) {
+        let amount = self.quirks.memory_increment.amount(x);
+        self.register_i = self.register_i.wrapping_add(amount as u32) & self.address_mask();
+    }
+
+    /// Executes an already decoded instruction located at the program counter.
+    ///
+    /// On error the machine is left untouched, and the instruction is not
+    /// counted in [`Chip8::executed`].
+    pub fn execute(&mut self, instruction: Instruction) -> ExecResult<()> {
+        self.apply(instruction)?;
+        self.executed = self.executed.wrapping_add(1);
+        Ok(())
+    }
+
+    /// Carries out `instruction` for [`Chip8::execute`].
+    fn apply(&mut self, instruction: Instruction) -> ExecResult<()> {
+        match instruction {
+            Instruction::CycleBackground => {
+                // 02A0 - BGND
+                // Cycle the background color through blue, black, green and red.
+                self.display.cycle_background();
+            }
+            Instruction::AddNibbles { x, y } => {
+                // 5xy1 - ADD Vx, Vy, nibbles
+                // Add each nibble of Vy to the corresponding nibble of Vx, modulo 8.
+                let (vx, vy) = (self.registers[x as usize], self.registers[y as usize]);
+                self.registers[x as usize] =
+                    ((vx & 0x70) + (vy & 0x70)) & 0x70 | ((vx & 0x07) + (vy & 0x07)) & 0x07;
+            }
+            Instruction::SetForeground { x, y, n } => {
+                // Bxy0 - COL Vx, Vy, 0
+                // Set the foreground color of 8x4 pixel zones to Vy.
+                // The low nibble of Vx is the first column and the high nibble the number of further columns.
+                // V(x+1) gives the rows the same way, in units of 4 pixels.
+                // Bxyn - COL Vx, Vy, nibble
+                // As above, but V(x+1) is the first pixel row and n the number of rows.
+                let horizontal = self.registers[x as usize];
+                let vertical = self.registers[(x as usize + 1) & 0xF];
+                let color = self.registers[y as usize] & 0x7;
+                let (left, right) = (horizontal & 0xF, (horizontal & 0xF) + (horizontal >> 4));
+                let (top, bottom) = if n == 0 {
+                    let top = (vertical & 0xF) * 4;
+                    (top, top + (vertical >> 4) * 4 + 3)
+                } else {
+                    (vertical, vertical.saturating_add(n - 1))
+                };
+                self.display.set_foreground(left, right, top, bottom, color);
+            }
+            Instruction::MegaOff => {
+                // 0010 - MEGAOFF
+                // Return to the CHIP-8 display.
+                self.display.set_resolution(Resolution::Low);
+            }
+            Instruction::MegaOn => {
+                // 0011 - MEGAON
+                // Switch to the 256x192 Mega-Chip display.
+                self.display.set_resolution(Resolution::Mega);
+            }
+            Instruction::MegaScrollUp { n } => {
+                // 00Bn - SCRU nibble
+                // Scroll display n lines up.
+                self.display.scroll_up(n);
+            }
+            Instruction::LoadIHigh(nnnnnn) => {
+                // 01nn nnnn - LDHI I, nnnnnn
+                // Set I = nnnnnn, the high byte from the opcode and the low 16 bits following it.
+                self.register_i = nnnnnn;
+            }
+            Instruction::LoadPalette { kk } => {
+                // 02nn - LDPAL byte
+                // Load nn colors from memory starting at I into palette entries 1 to nn.
+                // Each color takes 4 bytes: alpha, red, green and blue.
+                let (i, count) = (self.register_i as usize, kk as usize);
+                if count > 0 {
+                    self.memory_address(i + count * 4 - 1)?;
+                }
+                for entry in 0..count {
+                    let mut color = 0;
+                    for offset in 0..4 {
+                        color = color << 8 | self.read_memory(i + entry * 4 + offset)? as u32;
+                    }
+                    self.display.set_palette(entry as u8 + 1, color);
+                }
+            }
+            Instruction::SpriteWidth { kk } => {
+                // 03nn - SPRW byte
+                // Set the sprite width to nn pixels, 256 if nn is 0.
+                self.mega.sprite_width = kk;
+            }
+            Instruction::SpriteHeight { kk } => {
+                // 04nn - SPRH byte
+                // Set the sprite height to nn pixels, 256 if nn is 0.
+                self.mega.sprite_height = kk;
+            }
+            Instruction::ScreenAlpha { kk } => {
+                // 05nn - ALPHA byte
+                // Set the opacity the next pictures are shown with.
+                self.mega.alpha = kk;
+            }
+            Instruction::PlaySample { n } => {
+                // 060n - DIGISND nibble
+                // Play the sound at I, in a loop if n is 0 and once otherwise.
+                // I points at its header: the sample rate in 2 bytes and the number of samples in 3, followed by an unused byte.
+                // The samples follow as 8-bit unsigned values.
+                let i = self.register_i as usize;
+                self.memory_address(i + SAMPLE_HEADER_SIZE - 1)?;
+                let rate = (self.read_memory(i)? as u16) << 8 | self.read_memory(i + 1)? as u16;
+                let mut len = 0;
+                for offset in 2..5 {
+                    len = len << 8 | self.read_memory(i + offset)? as usize;
+                }
+                let start = self.memory_address(i + SAMPLE_HEADER_SIZE)?;
+                if len > 0 {
+                    self.memory_address(start + len - 1)?;
+                }
+                // Sounds running past the end of memory are cut short rather than wrapped.
+                let end = (start + len).min(self.config.memory_size);
+                let sample = Sample {
+                    address: start as u32,
+                    len: (end - start) as u32,
+                    rate,
+                    looping: n == 0,
+                };
+                self.audio
+                    .play_sample(&self.memory.as_ref()[sample.range()], rate, sample.looping);
+                self.mega.sample = Some(sample);
+            }
+            Instruction::StopSample => {
+                // 0700 - STOPSND
+                // Stop the sound playing.
+                self.mega.sample = None;
+                self.audio.stop_sample();
+            }
+            Instruction::SetBlendMode(mode) => {
+                // 080n - BMODE nibble
+                // Set how sprites mix with the picture: 0 by the alpha of their colors, 1 by 25%, 2 by 50%, 3 adding and 4 multiplying.
+                self.mega.blend_mode = mode;
+            }
+            Instruction::CollisionColor { kk } => {
+                // 09nn - CCOL byte
+                // Set the palette entry that sprites collide with.
+                self.mega.collision_color = kk;
+            }
+            Instruction::ScrollDown { n } => {
+                // 00Cn - SCD nibble
+                // Scroll display n lines down.
+                self.display.scroll_down(n);
+            }
+            Instruction::ScrollUp { n } => {
+                // 00Dn - SCU nibble
+                // Scroll display n lines up.
+                self.display.scroll_up(n);
+            }
+            Instruction::ClearScreen => {
+                // 00E0 - CLS
+                // Clear the display.
+                // In Mega-Chip mode, show the back buffer with the opacity set by ALPHA instead, and clear it for the next picture.
+                if self.display.resolution() == Resolution::Mega {
+                    self.display.present(self.mega.alpha);
+                } else {
+                    self.display.clear();
+                }
+            }
+            Instruction::Return => {
+                // 00EE - RET
+                // Return from a subroutine.
+                // The interpreter sets the program counter to the address at the top of the stack, then subtracts 1 from the stack pointer.
+                if self.sp == 0 {
+                    return Err(ExecError::StackUnderflow { pc: self.pc });
+                }
+                self.sp -= 1;
+                self.pc = self.stack[self.sp as usize];
+            }
+            Instruction::ScrollRight => {
+                // 00FB - SCR
+                // Scroll display 4 pixels right.
+                self.display.scroll_right(SCROLL_COLUMNS);
+            }
+            Instruction::ScrollLeft => {
+                // 00FC - SCL
+                // Scroll display 4 pixels left.
+                self.display.scroll_left(SCROLL_COLUMNS);
+            }
+            Instruction::Exit => {
+                // 00FD - EXIT
+                // Exit the interpreter.
+                // The program counter stays on this instruction.
+                self.exited = true;
+                return Ok(());
+            }
+            Instruction::LowRes => {
+                // 00FE - LOW
+                // Disable extended screen mode.
+                self.display.set_resolution(Resolution::Low);
+            }
+            Instruction::HighRes => {
+                // 00FF - HIGH
+                // Enable extended screen mode for full-screen graphics.
+                self.display.set_resolution(Resolution::High);
+            }
+            Instruction::SysAddr(nnn) => {
+                // 0nnn - SYS addr
+                // Jump to a machine code routine at nnn.
+                // This instruction is only used on the old computers on which Chip-8 was originally implemented.
+                // It is ignored by modern interpreters.
+                // The exception is 0230, which clears the 64x64 display of the hi-res CHIP-8 interpreter.
+                if nnn == 0x230 && self.display.resolution() == Resolution::Tall {
+                    self.display.clear();
+                }
+            }
+            Instruction::JumpAddr(nnn) => {
+                // 1nnn - JP addr
+                // Jump to location nnn.
+                // The interpreter sets the program counter to nnn.
+                self.pc = self.program_address(nnn as usize);
+                return Ok(());
+            }
+            Instruction::CallAddr(nnn) => {
+                // 2nnn - CALL addr
+                // Call subroutine at nnn.
+                // The interpreter increments the stack pointer, then puts the current PC on the top of the stack. The PC is then set to nnn.
+                if self.sp as usize >= self.config.stack_depth {
+                    return Err(ExecError::StackOverflow {
+                        pc: self.pc,
+                        depth: self.sp,
+                    });
+                }
+                self.stack[self.sp as usize] = self.pc;
+                self.pc = self.program_address(nnn as usize);
+                self.call_entries[self.sp as usize] = self.pc;
+                self.sp += 1;
+                return Ok(());
+            }
+            Instruction::SkipEqRegByte { x, kk } => {
+                // 3xkk - SE Vx, byte
+                // Skip next instruction if Vx = kk.
+                // The interpreter compares register Vx to kk, and if they are equal, increments the program counter by 2.
+                if self.registers[x as usize] == kk {
+                    self.skip_next_instruction()?;
+                }
+            }
+            Instruction::SkipNeRegByte { x, kk } => {
+                // 4xkk - SNE Vx, byte
+                // Skip next instruction if Vx != kk.
+                // The interpreter compares register Vx to kk, and if they are not equal, increments the program counter by 2.
+                if self.registers[x as usize] != kk {
+                    self.skip_next_instruction()?;
+                }
+            }
+            Instruction::SkipEqRegReg { x, y } => {
+                // 5xy0 - SE Vx, Vy
+                // Skip next instruction if Vx = Vy.
+                // The interpreter compares register Vx to register Vy, and if they are equal, increments the program counter by 2.
+                if self.registers[x as usize] == self.registers[y as usize] {
+                    self.skip_next_instruction()?;
+                }
+            }
+            Instruction::StoreRange { x, y } => {
+                // 5xy2 - LD [I], Vx-Vy
+                // Store registers Vx through Vy in memory starting at location I, in either order.
+                // I is not modified.
+                let i = self.register_i as usize;
+                let (x, y) = (x as usize, y as usize);
+                self.memory_address(i + x.abs_diff(y))?;
+                for (offset, register) in register_range(x, y).enumerate() {
+                    self.write_memory(i + offset, self.registers[register])?;
+                }
+            }
+            Instruction::LoadRange { x, y } => {
+                // 5xy3 - LD Vx-Vy, [I]
+                // Read registers Vx through Vy from memory starting at location I, in either order.
+                // I is not modified.
+                let i = self.register_i as usize;
+                let (x, y) = (x as usize, y as usize);
+                self.memory_address(i + x.abs_diff(y))?;
+                for (offset, register) in register_range(x, y).enumerate() {
+                    self.registers[register] = self.read_memory(i + offset)?;
+                }
+            }
+            Instruction::LoadRegByte { x, kk } => {
+                // 6xkk - LD Vx, byte
+                // Set Vx = kk.
+                // The interpreter puts the value kk into register Vx.
+                self.registers[x as usize] = kk;
+            }
+            Instruction::AddRegByte { x, kk } => {
+                // 7xkk - ADD Vx, byte
+                // Set Vx = Vx + kk.
+                // Adds the value kk to the value of register Vx, then stores the result in Vx.
+                self.registers[x as usize] = self.registers[x as usize].wrapping_add(kk);
+            }
+            Instruction::LoadRegReg { x, y } => {
+                // 8xy0 - LD Vx, Vy
+                // Set Vx = Vy.
+                // Stores the value of register Vy in register Vx.
+                self.registers[x as usize] = self.registers[y as usize];
+            }
+            Instruction::OrRegReg { x, y } => {
+                // 8xy1 - OR Vx, Vy
+                // Set Vx = Vx OR Vy.
+                // Performs a bitwise OR on the values of Vx and Vy, then stores the result in Vx.
+                self.registers[x as usize] |= self.registers[y as usize];
+                self.reset_vf();
+            }
+            Instruction::AndRegReg { x, y } => {
+                // 8xy2 - AND Vx, Vy
+                // Set Vx = Vx AND Vy.
+                // Performs a bitwise AND on the values of Vx and Vy, then stores the result in Vx.
+                self.registers[x as usize] &= self.registers[y as usize];
+                self.reset_vf();
+            }
+            Instruction::XorRegReg { x, y } => {
+                // 8xy3 - XOR Vx, Vy
+                // Set Vx = Vx XOR Vy.
+                // Performs a bitwise exclusive OR on the values of Vx and Vy, then stores the result in Vx.
+                self.registers[x as usize] ^= self.registers[y as usize];
+                self.reset_vf();
+            }
+            Instruction::AddRegReg { x, y } => {
+                // 8xy4 - ADD Vx, Vy
+                // Set Vx = Vx + Vy, set VF = carry.
+                // The values of Vx and Vy are added together. If the result is greater than 8 bits (i.e., > 255,) VF is set to 1, otherwise 0.
+                // Only the lowest 8 bits of the result are kept, and stored in Vx.
+                let (result, overflow) =
+                    self.registers[x as usize].overflowing_add(self.registers[y as usize]);
+                self.registers[x as usize] = result;
+                self.registers[0xF] = overflow as u8;
+            }
+            Instruction::SubRegReg { x, y } => {
+                // 8xy5 - SUB Vx, Vy
+                // Set Vx = Vx - Vy, set VF = NOT borrow.
+                // If Vx > Vy, then VF is set to 1, otherwise 0. Then Vy is subtracted from Vx, and the results stored in Vx.
+                let (result, overflow) =
+                    self.registers[x as usize].overflowing_sub(self.registers[y as usize]);
+                self.registers[x as usize] = result;
+                self.registers[0xF] = !overflow as u8;
+            }
+            Instruction::ShiftRight { x, y } => {
+                // 8xy6 - SHR Vx {, Vy}
+                // Set Vx = Vx SHR 1.
+                // If the least-significant bit of Vx is 1, then VF is set to 1, otherwise 0. Then Vx is divided by 2.
+                // The VIP shifted Vy instead, see `Quirks::shift_vy`.
+                let value = self.registers[self.shift_source(x, y)];
+                self.registers[x as usize] = value >> 1;
+                self.registers[0xF] = value & 0x1;
+            }
+            Instruction::SubnRegReg { x, y } => {
+                // 8xy7 - SUBN Vx, Vy
+                // Set Vx = Vy - Vx, set VF = NOT borrow.
+                // If Vy > Vx, then VF is set to 1, otherwise 0. Then Vx is subtracted from Vy, and the results stored in Vx.
+                let (result, overflow) =
+                    self.registers[y as usize].overflowing_sub(self.registers[x as usize]);
+                self.registers[x as usize] = result;
+                self.registers[0xF] = !overflow as u8;
+            }
+            Instruction::ShiftLeft { x, y } => {
+                // 8xyE - SHL Vx {, Vy}
+                // Set Vx = Vx SHL 1.
+                // If the most-significant bit of Vx is 1, then VF is set to 1, otherwise to 0. Then Vx is multiplied by 2.
+                // The VIP shifted Vy instead, see `Quirks::shift_vy`.
+                let value = self.registers[self.shift_source(x, y)];
+                self.registers[x as usize] = value << 1;
+                self.registers[0xF] = (value & 0x80) >> 7;
+            }
+            Instruction::SkipNeRegReg { x, y } => {
+                // 9xy0 - SNE Vx, Vy
+                // Skip next instruction if Vx != Vy.
+                // The values of Vx and Vy are compared, and if they are not equal, the program counter is increased by 2.
+                if self.registers[x as usize] != self.registers[y as usize] {
+                    self.skip_next_instruction()?;
+                }
+            }
+            Instruction::LoadIAddr(nnn) => {
+                // Annn - LD I, addr
+                // Set I = nnn.
+                // The value of register I is set to nnn.
+                self.register_i = nnn as u32;
+            }
+            Instruction::JumpV0Addr(nnn) => {
+                // Bnnn - JP V0, addr
+                // Jump to location nnn + V0.
+                // The program counter is set to nnn plus the value of V0.
+                // CHIP-48 adds Vx instead, with x taken from the high nibble of nnn, see `Quirks::jump_vx`.
+                let register = if self.quirks.jump_vx {
+                    (nnn >> 8) as usize & 0xF
+                } else {
+                    0
+                };
+                self.pc = self.program_address(nnn as usize + self.registers[register] as usize);
+                return Ok(());
+            }
+            Instruction::RandomRegByte { x, kk } => {
+                // Cxkk - RND Vx, byte
+                // Set Vx = random byte AND kk.
+                // The interpreter generates a random number from 0 to 255, which is then ANDed with the value kk.
+                // The results are stored in Vx.
+                self.registers[x as usize] = self.rng.next_u8() & kk;
+            }
+            Instruction::Draw { x, y, n } => {
+                // Dxyn - DRW Vx, Vy, nibble
+                // Display n-byte sprite starting at memory location I at (Vx, Vy), set VF = collision.
+                // The interpreter reads n bytes from memory, starting at the address stored in I.
+                // These bytes are then displayed as sprites on screen at coordinates (Vx, Vy).
+                // Sprites are XORed onto the existing screen.
+                // If this causes any pixels to be erased, VF is set to 1, otherwise it is set to 0.
+                // If the sprite is positioned so part of it is outside the coordinates of the display, it wraps around to the opposite side of the screen.
+                // Most other interpreters clip it at the edges instead, see `Quirks::sprite_edge`.
+                // Dxy0 - DRW Vx, Vy, 0
+                // Show 16x16 sprite, stored as 2 bytes per row (SUPER-CHIP).
+                // With several XO-CHIP planes selected, the sprite for each plane follows the previous one in memory.
+                // In Mega-Chip mode the sprite is SPRW x SPRH bytes, each a palette entry mixed into the back buffer as BMODE says.
+                // Entry 0 is transparent, and VF is set if the sprite covers a pixel of the CCOL entry.
+                let collided = if self.display.resolution() == Resolution::Mega {
+                    self.draw_mega(x, y)?
+                } else {
+                    self.draw_planes(x, y, n)?
+                };
+                self.registers[0xF] = collided as u8;
+                self.waiting_for_vblank = self.quirks.display_wait;
+            }
+            Instruction::SkipKeyPressed { x } => {
+                // Ex9E - SKP Vx
+                // Skip next instruction if key with the value of Vx is pressed.
+                // Checks the keyboard, and if the key corresponding to the value of Vx is currently in the down position, PC is increased by 2.
+                if self.keypad.is_pressed(self.registers[x as usize]) {
+                    self.skip_next_instruction()?;
+                }
+            }
+            Instruction::SkipKeyNotPressed { x } => {
+                // ExA1 - SKNP Vx
+                // Skip next instruction if key with the value of Vx is not pressed.
+                // Checks the keyboard, and if the key corresponding to the value of Vx is currently in the up position, PC is increased by 2.
+                if !self.keypad.is_pressed(self.registers[x as usize]) {
+                    self.skip_next_instruction()?;
+                }
+            }
+            Instruction::LoadILong(nnnn) => {
+                // F000 nnnn - LD I, long nnnn
+                // Set I = nnnn, the 16-bit address following the opcode.
+                self.register_i = nnnn as u32;
+            }
+            Instruction::SelectPlanes { n } => {
+                // Fn01 - PLANE n
+                // Select the drawing planes given by the bit mask n.
+                self.display.select_planes(n);
+            }
+            Instruction::LoadAudio => {
+                // F002 - AUDIO
+                // Store 16 bytes starting at I in the audio pattern buffer.
+                let i = self.register_i as usize;
+                self.memory_address(i + AUDIO_PATTERN_SIZE - 1)?;
+                for offset in 0..AUDIO_PATTERN_SIZE {
+                    self.audio_pattern[offset] = self.read_memory(i + offset)?;
+                }
+                self.audio.set_pattern(&self.audio_pattern);
+            }
+            Instruction::LoadRegDelay { x } => {
+                // Fx07 - LD Vx, DT
+                // Set Vx = delay timer value.
+                // The value of DT is placed into Vx.
+                self.registers[x as usize] = self.delay_timer;
+            }
+            Instruction::WaitKey { x } => {
+                // Fx0A - LD Vx, K
+                // Wait for a key press, store the value of the key in Vx.
+                // All execution stops until a key is pressed, then the value of that key is stored in Vx.
+                // The original interpreter only registers the key once it is released, see `Quirks::key_wait_release`.
+                self.waiting_for_key = Some(x as usize);
+            }
+            Instruction::LoadDelayReg { x } => {
+                // Fx15 - LD DT, Vx
+                // Set delay timer = Vx.
+                // DT is set equal to the value of Vx.
+                self.delay_timer = self.registers[x as usize];
+            }
+            Instruction::LoadSoundReg { x } => {
+                // Fx18 - LD ST, Vx
+                // Set sound timer = Vx.
+                // ST is set equal to the value of Vx.
+                self.sound_timer = self.registers[x as usize];
+                self.audio.set_tone(self.sound_timer > 0);
+            }
+            Instruction::AddIReg { x } => {
+                // Fx1E - ADD I, Vx
+                // Set I = I + Vx.
+                // The values of I and Vx are added, and the results are stored in I.
+                self.register_i = self
+                    .register_i
+                    .wrapping_add(self.registers[x as usize] as u32)
+                    & self.address_mask();
+            }
+            Instruction::LoadFont { x } => {
+                // Fx29 - LD F, Vx
+                // Set I = location of sprite for digit Vx.
+                // The value of I is set to the location for the hexadecimal sprite corresponding to the value of Vx.
+                let address =
+                    self.config.font_address as usize + self.registers[x as usize] as usize * 5;
+                self.register_i = self.memory_address(address)? as u32;
+            }
+            Instruction::LoadBigFont { x } => {
+                // Fx30 - LD HF, Vx
+                // Set I = location of 10-byte font sprite for digit Vx.
+                // Point I to 10-byte font sprite for digit Vx (0..9).
+                // The XO-CHIP hexadecimal digits A..F are available as well.
+                let address = self.config.font_address as usize
+                    + FONT_SIZE
+                    + (self.registers[x as usize] & 0xF) as usize * 10;
+                self.register_i = self.memory_address(address)? as u32;
+            }
+            Instruction::LoadPitch { x } => {
+                // Fx3A - PITCH Vx
+                // Set the audio pattern playback rate to 4000*2^((Vx-64)/48) Hz.
+                self.pitch = self.registers[x as usize];
+                self.audio.set_pitch(self.pitch);
+            }
+            Instruction::StoreBcd { x } => {
+                // Fx33 - LD B, Vx
+                // Store BCD representation of Vx in memory locations I, I+1, and I+2.
+                // The interpreter takes the decimal value of Vx, and places the hundreds digit in memory at location in I, the tens digit at location I+1, and the ones digit at location I+2.
+                let i = self.register_i as usize;
+                let value = self.registers[x as usize];
+                self.memory_address(i + 2)?;
+                self.write_memory(i, value / 100)?;
+                self.write_memory(i + 1, (value / 10) % 10)?;
+                self.write_memory(i + 2, value % 10)?;
+            }
+            Instruction::StoreRegs { x } => {
+                // Fx55 - LD [I], Vx
+                // Store registers V0 through Vx in memory starting at location I.
+                // The interpreter copies the values of registers V0 through Vx into memory, starting at the address in I.
+                let (i, x) = (self.register_i as usize, x as usize);
+                self.memory_address(i + x)?;
+                for offset in 0..=x {
+                    self.write_memory(i + offset, self.registers[offset])?;
+                }
+                self.advance_i(x as u8);
+            }
+            Instruction::LoadRegs { x } => {
+                // Fx65 - LD Vx, [I]
+                // Read registers V0 through Vx from memory starting at location I.
+                // The interpreter reads values from memory starting at location I into registers V0 through Vx.
+                let (i, x) = (self.register_i as usize, x as usize);
+                self.memory_address(i + x)?;
+                for offset in 0..=x {
+                    self.registers[offset] = self.read_memory(i + offset)?;
+                }
+                self.advance_i(x as u8);
+            }
+            Instruction::StoreFlags { x } => {
+                // Fx75 - LD R, Vx
+                // Store V0 through Vx in the flag registers.
+                let x = x as usize;
+                self.flags[..=x].copy_from_slice(&self.registers[..=x]);
+            }
+            Instruction::LoadFlags { x } => {
+                // Fx85 - LD Vx, R
+                // Read V0 through Vx from the flag registers.
+                let x = x as usize;
+                self.registers[..=x].copy_from_slice(&self.flags[..=x]);
+            }
+        }
+
+        self.pc = self.program_address(self.pc as usize + instruction.size() as usize);
+        Ok(())
+    }
+}
+
+/// Registers `x` through `y`, counting down if `y` is below `x`.
+fn register_range(x: usize, y: usize) -> impl Iterator<Item = usize> {
+    let descending = y < x;
+    (x.min(y)..=x.max(y)).map(move |register| {
+        if descending {
+            x + y - register
+        } else {
+            register
+        }
+    })
+}
+
+#[cfg(feature = "std")]
+impl Default for Chip8 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
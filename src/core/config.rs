@@ -0,0 +1,122 @@
+use crate::core::{chip::MemoryMode, screen::Resolution};
+
+pub const DEFAULT_MEMORY_SIZE: usize = 0x1000;
+/// Memory of XO-CHIP, all a 16-bit address reaches.
+pub const XO_CHIP_MEMORY_SIZE: usize = 0x10000;
+/// Memory of Mega-Chip, all a 24-bit address reaches.
+pub const MEGA_MEMORY_SIZE: usize = 0x1000000;
+/// Largest supported memory, as large as Mega-Chip's.
+pub const MAX_MEMORY_SIZE: usize = MEGA_MEMORY_SIZE;
+pub const DEFAULT_STACK_DEPTH: usize = 16;
+pub const MAX_STACK_DEPTH: usize = 64;
+pub const DEFAULT_FONT_ADDRESS: u16 = 0x000;
+pub const DEFAULT_PROGRAM_START: u16 = 0x200;
+
+/// Size of the built-in hexadecimal font, 16 sprites of 5 bytes.
+pub const FONT_SIZE: usize = 16 * 5;
+/// Size of the large hexadecimal font, 16 sprites of 10 bytes, stored right
+/// after the small one.
+pub const BIG_FONT_SIZE: usize = 16 * 10;
+
+/// Layout of a machine, fixed for its lifetime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(serde::Serialize, serde::Deserialize))]
+pub struct MachineConfig {
+    pub memory_size: usize,
+    pub stack_depth: usize,
+    pub font_address: u16,
+    /// Where the ROM is loaded.
+    pub program_start: u16,
+    /// Where execution starts, if not at `program_start`.
+    pub entry_point: Option<u16>,
+    pub memory_mode: MemoryMode,
+    /// Resolution at power on and after a reset.
+    pub resolution: Resolution,
+    /// Enables the CHIP-8X color instructions, which replace the `BXYN` jump.
+    pub chip8x: bool,
+    /// Enables the Mega-Chip instructions, which take over some `0nnn` calls.
+    pub megachip: bool,
+}
+
+impl MachineConfig {
+    pub const fn new() -> Self {
+        Self {
+            memory_size: DEFAULT_MEMORY_SIZE,
+            stack_depth: DEFAULT_STACK_DEPTH,
+            font_address: DEFAULT_FONT_ADDRESS,
+            program_start: DEFAULT_PROGRAM_START,
+            entry_point: None,
+            memory_mode: MemoryMode::Wrap,
+            resolution: Resolution::Low,
+            chip8x: false,
+            megachip: false,
+        }
+    }
+
+    /// Address of the first instruction executed.
+    pub const fn start_address(&self) -> u16 {
+        match self.entry_point {
+            Some(entry_point) => entry_point,
+            None => self.program_start,
+        }
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `memory_size` is zero or larger than [`MAX_MEMORY_SIZE`].
+    pub const fn with_memory_size(mut self, memory_size: usize) -> Self {
+        assert!(memory_size > 0 && memory_size <= MAX_MEMORY_SIZE);
+        self.memory_size = memory_size;
+        self
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `stack_depth` is larger than [`MAX_STACK_DEPTH`].
+    pub const fn with_stack_depth(mut self, stack_depth: usize) -> Self {
+        assert!(stack_depth <= MAX_STACK_DEPTH);
+        self.stack_depth = stack_depth;
+        self
+    }
+
+    pub const fn with_font_address(mut self, font_address: u16) -> Self {
+        self.font_address = font_address;
+        self
+    }
+
+    pub const fn with_program_start(mut self, program_start: u16) -> Self {
+        self.program_start = program_start;
+        self
+    }
+
+    pub const fn with_entry_point(mut self, entry_point: u16) -> Self {
+        self.entry_point = Some(entry_point);
+        self
+    }
+
+    pub const fn with_resolution(mut self, resolution: Resolution) -> Self {
+        self.resolution = resolution;
+        self
+    }
+
+    pub const fn with_chip8x(mut self) -> Self {
+        self.chip8x = true;
+        self
+    }
+
+    pub const fn with_megachip(mut self) -> Self {
+        self.megachip = true;
+        self
+    }
+
+    pub const fn with_memory_mode(mut self, memory_mode: MemoryMode) -> Self {
+        self.memory_mode = memory_mode;
+        self
+    }
+}
+
+impl Default for MachineConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
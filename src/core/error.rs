@@ -0,0 +1,68 @@
+use core::fmt;
+
+/// Error raised by the interpreter while executing an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecError {
+    UnknownOpcode {
+        opcode: u16,
+        pc: u16,
+    },
+    /// `CALL` with every stack slot already in use.
+    StackOverflow {
+        pc: u16,
+        depth: u8,
+    },
+    /// `RET` with an empty stack.
+    StackUnderflow {
+        pc: u16,
+    },
+    /// Memory access past the end of memory, with [`MemoryMode::Strict`](crate::core::chip::MemoryMode::Strict).
+    MemoryOutOfBounds {
+        pc: u16,
+        address: usize,
+    },
+    /// ROM does not fit in memory after the program start address.
+    RomTooLarge {
+        size: usize,
+        capacity: usize,
+    },
+}
+
+pub type ExecResult<T> = Result<T, ExecError>;
+
+impl fmt::Display for ExecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownOpcode { opcode, pc } => {
+                write!(f, "unknown opcode {:#06X} at {:#05X}", opcode, pc)
+            }
+            Self::StackOverflow { pc, depth } => {
+                write!(f, "stack overflow at {:#05X} (call depth {})", pc, depth)
+            }
+            Self::StackUnderflow { pc } => {
+                write!(
+                    f,
+                    "stack underflow at {:#05X} (return with empty stack)",
+                    pc
+                )
+            }
+            Self::MemoryOutOfBounds { pc, address } => {
+                write!(
+                    f,
+                    "memory access out of bounds at {:#05X} (address {:#06X})",
+                    pc, address
+                )
+            }
+            Self::RomTooLarge { size, capacity } => {
+                write!(
+                    f,
+                    "ROM is {} bytes, but only {} bytes are available",
+                    size, capacity
+                )
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ExecError {}
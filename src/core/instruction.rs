@@ -0,0 +1,549 @@
+use core::fmt;
+
+use crate::core::megachip::BlendMode;
+
+/// A decoded CHIP-8 instruction.
+///
+/// Mnemonics follow [Cowgod's Chip-8 Technical Reference](http://devernay.free.fr/hacks/chip8/C8TECH10.HTM).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Instruction {
+    /// `02A0` - BGND (CHIP-8X)
+    CycleBackground,
+    /// `5xy1` - ADD Vx, Vy nibbles (CHIP-8X)
+    ///
+    /// Adds each nibble separately, modulo 8.
+    AddNibbles { x: u8, y: u8 },
+    /// `Bxyn` - COL Vx, Vy, nibble (CHIP-8X)
+    ///
+    /// With `n == 0` the zones are 8×4 pixels, otherwise `n` rows of 8×1 pixels.
+    SetForeground { x: u8, y: u8, n: u8 },
+    /// `0010` - MEGAOFF (Mega-Chip)
+    MegaOff,
+    /// `0011` - MEGAON (Mega-Chip)
+    MegaOn,
+    /// `00Bn` - SCRU nibble (Mega-Chip)
+    MegaScrollUp { n: u8 },
+    /// `01nn nnnn` - LDHI I, nnnnnn (Mega-Chip)
+    ///
+    /// Four bytes long, the low 16 bits of the address are the opcode following it.
+    LoadIHigh(u32),
+    /// `02nn` - LDPAL byte (Mega-Chip)
+    ///
+    /// Loads `nn` colors of 4 bytes each from I into palette entries 1 and up.
+    LoadPalette { kk: u8 },
+    /// `03nn` - SPRW byte (Mega-Chip)
+    SpriteWidth { kk: u8 },
+    /// `04nn` - SPRH byte (Mega-Chip)
+    SpriteHeight { kk: u8 },
+    /// `05nn` - ALPHA byte (Mega-Chip)
+    ScreenAlpha { kk: u8 },
+    /// `060n` - DIGISND nibble (Mega-Chip)
+    ///
+    /// Plays the sound at I, in a loop if `n == 0` and once otherwise.
+    PlaySample { n: u8 },
+    /// `0700` - STOPSND (Mega-Chip)
+    StopSample,
+    /// `080n` - BMODE nibble (Mega-Chip)
+    SetBlendMode(BlendMode),
+    /// `09nn` - CCOL byte (Mega-Chip)
+    CollisionColor { kk: u8 },
+    /// `00Cn` - SCD nibble (SUPER-CHIP)
+    ScrollDown { n: u8 },
+    /// `00Dn` - SCU nibble (XO-CHIP)
+    ScrollUp { n: u8 },
+    /// `00E0` - CLS
+    ClearScreen,
+    /// `00EE` - RET
+    Return,
+    /// `00FB` - SCR (SUPER-CHIP)
+    ScrollRight,
+    /// `00FC` - SCL (SUPER-CHIP)
+    ScrollLeft,
+    /// `00FD` - EXIT (SUPER-CHIP)
+    Exit,
+    /// `00FE` - LOW (SUPER-CHIP)
+    LowRes,
+    /// `00FF` - HIGH (SUPER-CHIP)
+    HighRes,
+    /// `0nnn` - SYS addr
+    SysAddr(u16),
+    /// `1nnn` - JP addr
+    JumpAddr(u16),
+    /// `2nnn` - CALL addr
+    CallAddr(u16),
+    /// `3xkk` - SE Vx, byte
+    SkipEqRegByte { x: u8, kk: u8 },
+    /// `4xkk` - SNE Vx, byte
+    SkipNeRegByte { x: u8, kk: u8 },
+    /// `5xy0` - SE Vx, Vy
+    SkipEqRegReg { x: u8, y: u8 },
+    /// `5xy2` - LD [I], Vx-Vy (XO-CHIP)
+    StoreRange { x: u8, y: u8 },
+    /// `5xy3` - LD Vx-Vy, [I] (XO-CHIP)
+    LoadRange { x: u8, y: u8 },
+    /// `6xkk` - LD Vx, byte
+    LoadRegByte { x: u8, kk: u8 },
+    /// `7xkk` - ADD Vx, byte
+    AddRegByte { x: u8, kk: u8 },
+    /// `8xy0` - LD Vx, Vy
+    LoadRegReg { x: u8, y: u8 },
+    /// `8xy1` - OR Vx, Vy
+    OrRegReg { x: u8, y: u8 },
+    /// `8xy2` - AND Vx, Vy
+    AndRegReg { x: u8, y: u8 },
+    /// `8xy3` - XOR Vx, Vy
+    XorRegReg { x: u8, y: u8 },
+    /// `8xy4` - ADD Vx, Vy
+    AddRegReg { x: u8, y: u8 },
+    /// `8xy5` - SUB Vx, Vy
+    SubRegReg { x: u8, y: u8 },
+    /// `8xy6` - SHR Vx {, Vy}
+    ShiftRight { x: u8, y: u8 },
+    /// `8xy7` - SUBN Vx, Vy
+    SubnRegReg { x: u8, y: u8 },
+    /// `8xyE` - SHL Vx {, Vy}
+    ShiftLeft { x: u8, y: u8 },
+    /// `9xy0` - SNE Vx, Vy
+    SkipNeRegReg { x: u8, y: u8 },
+    /// `Annn` - LD I, addr
+    LoadIAddr(u16),
+    /// `Bnnn` - JP V0, addr
+    JumpV0Addr(u16),
+    /// `Cxkk` - RND Vx, byte
+    RandomRegByte { x: u8, kk: u8 },
+    /// `Dxyn` - DRW Vx, Vy, nibble
+    ///
+    /// With `n == 0` a 16×16 sprite is drawn (SUPER-CHIP).
+    Draw { x: u8, y: u8, n: u8 },
+    /// `Ex9E` - SKP Vx
+    SkipKeyPressed { x: u8 },
+    /// `ExA1` - SKNP Vx
+    SkipKeyNotPressed { x: u8 },
+    /// `F000 nnnn` - LD I, long nnnn (XO-CHIP)
+    ///
+    /// The only four byte instruction, its address is the opcode following `F000`.
+    LoadILong(u16),
+    /// `Fn01` - PLANE n (XO-CHIP)
+    SelectPlanes { n: u8 },
+    /// `F002` - AUDIO (XO-CHIP)
+    LoadAudio,
+    /// `Fx07` - LD Vx, DT
+    LoadRegDelay { x: u8 },
+    /// `Fx0A` - LD Vx, K
+    WaitKey { x: u8 },
+    /// `Fx15` - LD DT, Vx
+    LoadDelayReg { x: u8 },
+    /// `Fx18` - LD ST, Vx
+    LoadSoundReg { x: u8 },
+    /// `Fx1E` - ADD I, Vx
+    AddIReg { x: u8 },
+    /// `Fx29` - LD F, Vx
+    LoadFont { x: u8 },
+    /// `Fx30` - LD HF, Vx (SUPER-CHIP)
+    LoadBigFont { x: u8 },
+    /// `Fx3A` - PITCH Vx (XO-CHIP)
+    LoadPitch { x: u8 },
+    /// `Fx33` - LD B, Vx
+    StoreBcd { x: u8 },
+    /// `Fx55` - LD [I], Vx
+    StoreRegs { x: u8 },
+    /// `Fx65` - LD Vx, [I]
+    LoadRegs { x: u8 },
+    /// `Fx75` - LD R, Vx (SUPER-CHIP)
+    StoreFlags { x: u8 },
+    /// `Fx85` - LD Vx, R (SUPER-CHIP)
+    LoadFlags { x: u8 },
+}
+
+impl Instruction {
+    /// Opcode of the four byte `F000 nnnn` instruction.
+    pub const LONG_PREFIX: u16 = 0xF000;
+
+    /// Decodes a raw opcode, returning `None` if it is not a known instruction.
+    ///
+    /// `F000` needs the following opcode as well, use [`Instruction::decode_long`] for it.
+    pub fn decode(opcode: u16) -> Option<Self> {
+        let nnn = opcode & 0x0FFF;
+        let n = (opcode & 0x000F) as u8;
+        let x = ((opcode & 0x0F00) >> 8) as u8;
+        let y = ((opcode & 0x00F0) >> 4) as u8;
+        let kk = (opcode & 0x00FF) as u8;
+
+        let instruction = match opcode & 0xF000 {
+            0x0000 => match opcode {
+                0x00C0..=0x00CF => Self::ScrollDown { n },
+                0x00D0..=0x00DF => Self::ScrollUp { n },
+                0x00E0 => Self::ClearScreen,
+                0x00EE => Self::Return,
+                0x00FB => Self::ScrollRight,
+                0x00FC => Self::ScrollLeft,
+                0x00FD => Self::Exit,
+                0x00FE => Self::LowRes,
+                0x00FF => Self::HighRes,
+                _ => Self::SysAddr(nnn),
+            },
+            0x1000 => Self::JumpAddr(nnn),
+            0x2000 => Self::CallAddr(nnn),
+            0x3000 => Self::SkipEqRegByte { x, kk },
+            0x4000 => Self::SkipNeRegByte { x, kk },
+            0x5000 => match n {
+                0x0 => Self::SkipEqRegReg { x, y },
+                0x2 => Self::StoreRange { x, y },
+                0x3 => Self::LoadRange { x, y },
+                _ => return None,
+            },
+            0x6000 => Self::LoadRegByte { x, kk },
+            0x7000 => Self::AddRegByte { x, kk },
+            0x8000 => match n {
+                0x0 => Self::LoadRegReg { x, y },
+                0x1 => Self::OrRegReg { x, y },
+                0x2 => Self::AndRegReg { x, y },
+                0x3 => Self::XorRegReg { x, y },
+                0x4 => Self::AddRegReg { x, y },
+                0x5 => Self::SubRegReg { x, y },
+                0x6 => Self::ShiftRight { x, y },
+                0x7 => Self::SubnRegReg { x, y },
+                0xE => Self::ShiftLeft { x, y },
+                _ => return None,
+            },
+            0x9000 if n == 0x0 => Self::SkipNeRegReg { x, y },
+            0xA000 => Self::LoadIAddr(nnn),
+            0xB000 => Self::JumpV0Addr(nnn),
+            0xC000 => Self::RandomRegByte { x, kk },
+            0xD000 => Self::Draw { x, y, n },
+            0xE000 => match kk {
+                0x9E => Self::SkipKeyPressed { x },
+                0xA1 => Self::SkipKeyNotPressed { x },
+                _ => return None,
+            },
+            0xF000 => match kk {
+                0x01 => Self::SelectPlanes { n: x },
+                0x02 if x == 0x0 => Self::LoadAudio,
+                0x07 => Self::LoadRegDelay { x },
+                0x0A => Self::WaitKey { x },
+                0x15 => Self::LoadDelayReg { x },
+                0x18 => Self::LoadSoundReg { x },
+                0x1E => Self::AddIReg { x },
+                0x29 => Self::LoadFont { x },
+                0x30 => Self::LoadBigFont { x },
+                0x3A => Self::LoadPitch { x },
+                0x33 => Self::StoreBcd { x },
+                0x55 => Self::StoreRegs { x },
+                0x65 => Self::LoadRegs { x },
+                0x75 => Self::StoreFlags { x },
+                0x85 => Self::LoadFlags { x },
+                _ => return None,
+            },
+            _ => return None,
+        };
+        Some(instruction)
+    }
+
+    /// Decodes `opcode`, taking the address of `F000 nnnn` from `next`, the
+    /// opcode that follows it in memory.
+    pub fn decode_long(opcode: u16, next: u16) -> Option<Self> {
+        if opcode == Self::LONG_PREFIX {
+            return Some(Self::LoadILong(next));
+        }
+        Self::decode(opcode)
+    }
+
+    /// Decodes `opcode` with the CHIP-8X color instructions enabled, which
+    /// replace `02A0`, `5xy1` and the `Bnnn` jump.
+    pub fn decode_chip8x(opcode: u16) -> Option<Self> {
+        let n = (opcode & 0x000F) as u8;
+        let x = ((opcode & 0x0F00) >> 8) as u8;
+        let y = ((opcode & 0x00F0) >> 4) as u8;
+
+        let instruction = match opcode & 0xF000 {
+            0x0000 if opcode == 0x02A0 => Self::CycleBackground,
+            0x5000 if n == 0x1 => Self::AddNibbles { x, y },
+            0xB000 => Self::SetForeground { x, y, n },
+            _ => return Self::decode(opcode),
+        };
+        Some(instruction)
+    }
+
+    /// Decodes `opcode` with the Mega-Chip instructions enabled, which replace
+    /// `0010`, `0011`, `00Bn` and the `0nnn` calls from `0100` to `09FF`.
+    /// `next` is the opcode following it, as for [`Instruction::decode_long`].
+    pub fn decode_mega(opcode: u16, next: u16) -> Option<Self> {
+        let n = (opcode & 0x000F) as u8;
+        let kk = (opcode & 0x00FF) as u8;
+
+        let instruction = match opcode & 0xFF00 {
+            0x0000 => match opcode {
+                0x0010 => Self::MegaOff,
+                0x0011 => Self::MegaOn,
+                0x00B0..=0x00BF => Self::MegaScrollUp { n },
+                _ => return Self::decode_long(opcode, next),
+            },
+            0x0100 => Self::LoadIHigh((kk as u32) << 16 | next as u32),
+            0x0200 => Self::LoadPalette { kk },
+            0x0300 => Self::SpriteWidth { kk },
+            0x0400 => Self::SpriteHeight { kk },
+            0x0500 => Self::ScreenAlpha { kk },
+            0x0600 if kk < 0x10 => Self::PlaySample { n },
+            0x0700 if kk == 0x00 => Self::StopSample,
+            0x0800 if kk < 0x10 => Self::SetBlendMode(BlendMode::from_nibble(n)?),
+            0x0900 => Self::CollisionColor { kk },
+            _ => return Self::decode_long(opcode, next),
+        };
+        Some(instruction)
+    }
+
+    /// Whether `opcode` starts a four byte instruction, `F000 nnnn` or with
+    /// `megachip` the Mega-Chip `01nn nnnn`.
+    pub const fn is_long(opcode: u16, megachip: bool) -> bool {
+        opcode == Self::LONG_PREFIX || (megachip && opcode & 0xFF00 == 0x0100)
+    }
+
+    /// Decodes the instruction at `address` in `memory`, returning `None` if it
+    /// is unknown or runs past the end of memory.
+    pub fn decode_memory(memory: &[u8], address: usize) -> Option<Self> {
+        let word = |address: usize| -> Option<u16> {
+            let bytes = memory.get(address..address + 2)?;
+            Some((bytes[0] as u16) << 8 | bytes[1] as u16)
+        };
+        let opcode = word(address)?;
+        if opcode == Self::LONG_PREFIX {
+            return Self::decode_long(opcode, word(address + 2)?);
+        }
+        Self::decode(opcode)
+    }
+
+    /// Opcode pattern of the instruction, such as `8xy4` for `ADD Vx, Vy`.
+    pub const fn pattern(&self) -> &'static str {
+        match self {
+            Self::CycleBackground => "02A0",
+            Self::AddNibbles { .. } => "5xy1",
+            Self::SetForeground { .. } => "Bxyn",
+            Self::MegaOff => "0010",
+            Self::MegaOn => "0011",
+            Self::MegaScrollUp { .. } => "00Bn",
+            Self::LoadIHigh(_) => "01nn nnnn",
+            Self::LoadPalette { .. } => "02nn",
+            Self::SpriteWidth { .. } => "03nn",
+            Self::SpriteHeight { .. } => "04nn",
+            Self::ScreenAlpha { .. } => "05nn",
+            Self::PlaySample { .. } => "060n",
+            Self::StopSample => "0700",
+            Self::SetBlendMode(_) => "080n",
+            Self::CollisionColor { .. } => "09nn",
+            Self::ScrollDown { .. } => "00Cn",
+            Self::ScrollUp { .. } => "00Dn",
+            Self::ClearScreen => "00E0",
+            Self::Return => "00EE",
+            Self::ScrollRight => "00FB",
+            Self::ScrollLeft => "00FC",
+            Self::Exit => "00FD",
+            Self::LowRes => "00FE",
+            Self::HighRes => "00FF",
+            Self::SysAddr(_) => "0nnn",
+            Self::JumpAddr(_) => "1nnn",
+            Self::CallAddr(_) => "2nnn",
+            Self::SkipEqRegByte { .. } => "3xkk",
+            Self::SkipNeRegByte { .. } => "4xkk",
+            Self::SkipEqRegReg { .. } => "5xy0",
+            Self::StoreRange { .. } => "5xy2",
+            Self::LoadRange { .. } => "5xy3",
+            Self::LoadRegByte { .. } => "6xkk",
+            Self::AddRegByte { .. } => "7xkk",
+            Self::LoadRegReg { .. } => "8xy0",
+            Self::OrRegReg { .. } => "8xy1",
+            Self::AndRegReg { .. } => "8xy2",
+            Self::XorRegReg { .. } => "8xy3",
+            Self::AddRegReg { .. } => "8xy4",
+            Self::SubRegReg { .. } => "8xy5",
+            Self::ShiftRight { .. } => "8xy6",
+            Self::SubnRegReg { .. } => "8xy7",
+            Self::ShiftLeft { .. } => "8xyE",
+            Self::SkipNeRegReg { .. } => "9xy0",
+            Self::LoadIAddr(_) => "Annn",
+            Self::JumpV0Addr(_) => "Bnnn",
+            Self::RandomRegByte { .. } => "Cxkk",
+            Self::Draw { .. } => "Dxyn",
+            Self::SkipKeyPressed { .. } => "Ex9E",
+            Self::SkipKeyNotPressed { .. } => "ExA1",
+            Self::LoadILong(_) => "F000 nnnn",
+            Self::SelectPlanes { .. } => "Fn01",
+            Self::LoadAudio => "F002",
+            Self::LoadRegDelay { .. } => "Fx07",
+            Self::WaitKey { .. } => "Fx0A",
+            Self::LoadDelayReg { .. } => "Fx15",
+            Self::LoadSoundReg { .. } => "Fx18",
+            Self::AddIReg { .. } => "Fx1E",
+            Self::LoadFont { .. } => "Fx29",
+            Self::LoadBigFont { .. } => "Fx30",
+            Self::LoadPitch { .. } => "Fx3A",
+            Self::StoreBcd { .. } => "Fx33",
+            Self::StoreRegs { .. } => "Fx55",
+            Self::LoadRegs { .. } => "Fx65",
+            Self::StoreFlags { .. } => "Fx75",
+            Self::LoadFlags { .. } => "Fx85",
+        }
+    }
+
+    /// Size of the instruction in bytes.
+    pub fn size(&self) -> u16 {
+        match self {
+            Self::LoadILong(_) | Self::LoadIHigh(_) => 4,
+            _ => 2,
+        }
+    }
+
+    /// Encodes the instruction back into its raw opcode.
+    ///
+    /// For `F000 nnnn` and `01nn nnnn` this is the first opcode only, followed
+    /// in memory by the rest of the address.
+    pub fn encode(&self) -> u16 {
+        fn xkk(prefix: u16, x: u8, kk: u8) -> u16 {
+            prefix | (x as u16 & 0xF) << 8 | kk as u16
+        }
+        fn xyn(prefix: u16, x: u8, y: u8, n: u8) -> u16 {
+            prefix | (x as u16 & 0xF) << 8 | (y as u16 & 0xF) << 4 | (n as u16 & 0xF)
+        }
+
+        match *self {
+            Self::CycleBackground => 0x02A0,
+            Self::AddNibbles { x, y } => xyn(0x5000, x, y, 0x1),
+            Self::SetForeground { x, y, n } => xyn(0xB000, x, y, n),
+            Self::MegaOff => 0x0010,
+            Self::MegaOn => 0x0011,
+            Self::MegaScrollUp { n } => 0x00B0 | (n as u16 & 0xF),
+            Self::LoadIHigh(nnnnnn) => 0x0100 | (nnnnnn >> 16) as u16 & 0xFF,
+            Self::LoadPalette { kk } => 0x0200 | kk as u16,
+            Self::SpriteWidth { kk } => 0x0300 | kk as u16,
+            Self::SpriteHeight { kk } => 0x0400 | kk as u16,
+            Self::ScreenAlpha { kk } => 0x0500 | kk as u16,
+            Self::PlaySample { n } => 0x0600 | (n as u16 & 0xF),
+            Self::StopSample => 0x0700,
+            Self::SetBlendMode(mode) => 0x0800 | mode.nibble() as u16,
+            Self::CollisionColor { kk } => 0x0900 | kk as u16,
+            Self::ScrollDown { n } => 0x00C0 | (n as u16 & 0xF),
+            Self::ScrollUp { n } => 0x00D0 | (n as u16 & 0xF),
+            Self::ClearScreen => 0x00E0,
+            Self::Return => 0x00EE,
+            Self::ScrollRight => 0x00FB,
+            Self::ScrollLeft => 0x00FC,
+            Self::Exit => 0x00FD,
+            Self::LowRes => 0x00FE,
+            Self::HighRes => 0x00FF,
+            Self::SysAddr(nnn) => nnn & 0x0FFF,
+            Self::JumpAddr(nnn) => 0x1000 | (nnn & 0x0FFF),
+            Self::CallAddr(nnn) => 0x2000 | (nnn & 0x0FFF),
+            Self::SkipEqRegByte { x, kk } => xkk(0x3000, x, kk),
+            Self::SkipNeRegByte { x, kk } => xkk(0x4000, x, kk),
+            Self::SkipEqRegReg { x, y } => xyn(0x5000, x, y, 0x0),
+            Self::StoreRange { x, y } => xyn(0x5000, x, y, 0x2),
+            Self::LoadRange { x, y } => xyn(0x5000, x, y, 0x3),
+            Self::LoadRegByte { x, kk } => xkk(0x6000, x, kk),
+            Self::AddRegByte { x, kk } => xkk(0x7000, x, kk),
+            Self::LoadRegReg { x, y } => xyn(0x8000, x, y, 0x0),
+            Self::OrRegReg { x, y } => xyn(0x8000, x, y, 0x1),
+            Self::AndRegReg { x, y } => xyn(0x8000, x, y, 0x2),
+            Self::XorRegReg { x, y } => xyn(0x8000, x, y, 0x3),
+            Self::AddRegReg { x, y } => xyn(0x8000, x, y, 0x4),
+            Self::SubRegReg { x, y } => xyn(0x8000, x, y, 0x5),
+            Self::ShiftRight { x, y } => xyn(0x8000, x, y, 0x6),
+            Self::SubnRegReg { x, y } => xyn(0x8000, x, y, 0x7),
+            Self::ShiftLeft { x, y } => xyn(0x8000, x, y, 0xE),
+            Self::SkipNeRegReg { x, y } => xyn(0x9000, x, y, 0x0),
+            Self::LoadIAddr(nnn) => 0xA000 | (nnn & 0x0FFF),
+            Self::JumpV0Addr(nnn) => 0xB000 | (nnn & 0x0FFF),
+            Self::RandomRegByte { x, kk } => xkk(0xC000, x, kk),
+            Self::Draw { x, y, n } => xyn(0xD000, x, y, n),
+            Self::SkipKeyPressed { x } => xkk(0xE000, x, 0x9E),
+            Self::SkipKeyNotPressed { x } => xkk(0xE000, x, 0xA1),
+            Self::LoadILong(_) => Self::LONG_PREFIX,
+            Self::SelectPlanes { n } => xkk(0xF000, n, 0x01),
+            Self::LoadAudio => 0xF002,
+            Self::LoadRegDelay { x } => xkk(0xF000, x, 0x07),
+            Self::WaitKey { x } => xkk(0xF000, x, 0x0A),
+            Self::LoadDelayReg { x } => xkk(0xF000, x, 0x15),
+            Self::LoadSoundReg { x } => xkk(0xF000, x, 0x18),
+            Self::AddIReg { x } => xkk(0xF000, x, 0x1E),
+            Self::LoadFont { x } => xkk(0xF000, x, 0x29),
+            Self::LoadBigFont { x } => xkk(0xF000, x, 0x30),
+            Self::LoadPitch { x } => xkk(0xF000, x, 0x3A),
+            Self::StoreBcd { x } => xkk(0xF000, x, 0x33),
+            Self::StoreRegs { x } => xkk(0xF000, x, 0x55),
+            Self::LoadRegs { x } => xkk(0xF000, x, 0x65),
+            Self::StoreFlags { x } => xkk(0xF000, x, 0x75),
+            Self::LoadFlags { x } => xkk(0xF000, x, 0x85),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::CycleBackground => write!(f, "BGND"),
+            Self::AddNibbles { x, y } => write!(f, "ADD V{:X}, V{:X}, nibbles", x, y),
+            Self::SetForeground { x, y, n } => write!(f, "COL V{:X}, V{:X}, {:#03X}", x, y, n),
+            Self::MegaOff => write!(f, "MEGAOFF"),
+            Self::MegaOn => write!(f, "MEGAON"),
+            Self::MegaScrollUp { n } => write!(f, "SCRU {:#03X}", n),
+            Self::LoadIHigh(nnnnnn) => write!(f, "LDHI I, {:#08X}", nnnnnn),
+            Self::LoadPalette { kk } => write!(f, "LDPAL {:#04X}", kk),
+            Self::SpriteWidth { kk } => write!(f, "SPRW {:#04X}", kk),
+            Self::SpriteHeight { kk } => write!(f, "SPRH {:#04X}", kk),
+            Self::ScreenAlpha { kk } => write!(f, "ALPHA {:#04X}", kk),
+            Self::PlaySample { n } => write!(f, "DIGISND {}", n),
+            Self::StopSample => write!(f, "STOPSND"),
+            Self::SetBlendMode(mode) => write!(f, "BMODE {}", mode.nibble()),
+            Self::CollisionColor { kk } => write!(f, "CCOL {:#04X}", kk),
+            Self::ScrollDown { n } => write!(f, "SCD {:#03X}", n),
+            Self::ScrollUp { n } => write!(f, "SCU {:#03X}", n),
+            Self::ClearScreen => write!(f, "CLS"),
+            Self::Return => write!(f, "RET"),
+            Self::ScrollRight => write!(f, "SCR"),
+            Self::ScrollLeft => write!(f, "SCL"),
+            Self::Exit => write!(f, "EXIT"),
+            Self::LowRes => write!(f, "LOW"),
+            Self::HighRes => write!(f, "HIGH"),
+            Self::SysAddr(nnn) => write!(f, "SYS {:#05X}", nnn),
+            Self::JumpAddr(nnn) => write!(f, "JP {:#05X}", nnn),
+            Self::CallAddr(nnn) => write!(f, "CALL {:#05X}", nnn),
+            Self::SkipEqRegByte { x, kk } => write!(f, "SE V{:X}, {:#04X}", x, kk),
+            Self::SkipNeRegByte { x, kk } => write!(f, "SNE V{:X}, {:#04X}", x, kk),
+            Self::SkipEqRegReg { x, y } => write!(f, "SE V{:X}, V{:X}", x, y),
+            Self::StoreRange { x, y } => write!(f, "LD [I], V{:X}-V{:X}", x, y),
+            Self::LoadRange { x, y } => write!(f, "LD V{:X}-V{:X}, [I]", x, y),
+            Self::LoadRegByte { x, kk } => write!(f, "LD V{:X}, {:#04X}", x, kk),
+            Self::AddRegByte { x, kk } => write!(f, "ADD V{:X}, {:#04X}", x, kk),
+            Self::LoadRegReg { x, y } => write!(f, "LD V{:X}, V{:X}", x, y),
+            Self::OrRegReg { x, y } => write!(f, "OR V{:X}, V{:X}", x, y),
+            Self::AndRegReg { x, y } => write!(f, "AND V{:X}, V{:X}", x, y),
+            Self::XorRegReg { x, y } => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Self::AddRegReg { x, y } => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Self::SubRegReg { x, y } => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Self::ShiftRight { x, y } => write!(f, "SHR V{:X}, V{:X}", x, y),
+            Self::SubnRegReg { x, y } => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Self::ShiftLeft { x, y } => write!(f, "SHL V{:X}, V{:X}", x, y),
+            Self::SkipNeRegReg { x, y } => write!(f, "SNE V{:X}, V{:X}", x, y),
+            Self::LoadIAddr(nnn) => write!(f, "LD I, {:#05X}", nnn),
+            Self::JumpV0Addr(nnn) => write!(f, "JP V0, {:#05X}", nnn),
+            Self::RandomRegByte { x, kk } => write!(f, "RND V{:X}, {:#04X}", x, kk),
+            Self::Draw { x, y, n } => write!(f, "DRW V{:X}, V{:X}, {:#03X}", x, y, n),
+            Self::SkipKeyPressed { x } => write!(f, "SKP V{:X}", x),
+            Self::SkipKeyNotPressed { x } => write!(f, "SKNP V{:X}", x),
+            Self::LoadILong(nnnn) => write!(f, "LD I, long {:#06X}", nnnn),
+            Self::SelectPlanes { n } => write!(f, "PLANE {}", n),
+            Self::LoadAudio => write!(f, "AUDIO"),
+            Self::LoadRegDelay { x } => write!(f, "LD V{:X}, DT", x),
+            Self::WaitKey { x } => write!(f, "LD V{:X}, K", x),
+            Self::LoadDelayReg { x } => write!(f, "LD DT, V{:X}", x),
+            Self::LoadSoundReg { x } => write!(f, "LD ST, V{:X}", x),
+            Self::AddIReg { x } => write!(f, "ADD I, V{:X}", x),
+            Self::LoadFont { x } => write!(f, "LD F, V{:X}", x),
+            Self::LoadBigFont { x } => write!(f, "LD HF, V{:X}", x),
+            Self::LoadPitch { x } => write!(f, "PITCH V{:X}", x),
+            Self::StoreBcd { x } => write!(f, "LD B, V{:X}", x),
+            Self::StoreRegs { x } => write!(f, "LD [I], V{:X}", x),
+            Self::LoadRegs { x } => write!(f, "LD V{:X}, [I]", x),
+            Self::StoreFlags { x } => write!(f, "LD R, V{:X}", x),
+            Self::LoadFlags { x } => write!(f, "LD V{:X}, R", x),
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use crate::core::bus::KeypadBus;
+
+pub const KEY_COUNT: usize = 16;
+
+#[derive(Debug, Default, Clone)]
+pub struct Keypad {
+    pub keys: [bool; KEY_COUNT],
+}
+
+impl Keypad {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn press(&mut self, key: usize) {
+        self.keys[key] = true;
+    }
+
+    pub fn release(&mut self, key: usize) {
+        self.keys[key] = false;
+    }
+
+    /// Pressed keys as a bit mask, with bit `n` set for key `n`.
+    pub fn mask(&self) -> u16 {
+        self.keys
+            .iter()
+            .enumerate()
+            .fold(0, |mask, (key, &pressed)| mask | (pressed as u16) << key)
+    }
+
+    /// Presses exactly the keys set in `mask`, see [`Keypad::mask`].
+    pub fn set_mask(&mut self, mask: u16) {
+        for (key, pressed) in self.keys.iter_mut().enumerate() {
+            *pressed = mask & (1 << key) != 0;
+        }
+    }
+}
+
+impl KeypadBus for Keypad {
+    fn is_pressed(&self, key: u8) -> bool {
+        self.keys[key as usize & 0xF]
+    }
+}
@@ -0,0 +1,244 @@
+//! Mega-Chip, the extension of SUPER-CHIP by Revival Studios with a 256×192
+//! display of 256 colors and digitized sound.
+//!
+//! `0011` switches to the Mega-Chip display. Sprites there are bytes indexing
+//! a palette of `0xAARRGGBB` colors, as wide and high as `03nn` and `04nn`
+//! set, and are mixed into a back buffer that `00E0` shows and then clears.
+//! Entry 0 of the palette is transparent.
+//!
+//! Its memory is addressed with a 24-bit `I`, and grows to fit the ROM when
+//! it is loaded. Without `std`, the buffer given to
+//! [`Chip8::with_memory`](crate::core::chip::Chip8::with_memory) must be large
+//! enough, and there is no Mega-Chip display.
+
+use crate::core::config::{MAX_MEMORY_SIZE, XO_CHIP_MEMORY_SIZE};
+
+pub const MEGA_SCREEN_WIDTH: usize = 256;
+pub const MEGA_SCREEN_HEIGHT: usize = 192;
+/// Number of palette entries, including the transparent entry 0.
+pub const PALETTE_SIZE: usize = 256;
+/// Bytes before the samples of a sound played by `060n`: the sample rate as a
+/// big-endian `u16`, the number of samples as a big-endian 24-bit number and
+/// one unused byte.
+pub const SAMPLE_HEADER_SIZE: usize = 6;
+
+/// Mask of the 24-bit `I` register.
+pub(crate) const ADDRESS_MASK: u32 = 0xFF_FFFF;
+#[cfg(feature = "std")]
+const BLACK: u32 = 0xFF00_0000;
+
+/// Memory for a Mega-Chip program of `rom_size` bytes loaded at
+/// `program_start`: what the ROM fills, rounded up to 64 KB, and another 64 KB
+/// for its variables, within [`MAX_MEMORY_SIZE`].
+///
+/// Snapshots for rewinding and stepping back copy all of memory every frame,
+/// which would be too slow with the full 16 MB.
+pub const fn memory_size(program_start: u16, rom_size: usize) -> usize {
+    let end = program_start as usize + rom_size;
+    let size = (end / XO_CHIP_MEMORY_SIZE + 2) * XO_CHIP_MEMORY_SIZE;
+    if size < MAX_MEMORY_SIZE {
+        size
+    } else {
+        MAX_MEMORY_SIZE
+    }
+}
+
+/// How the pixels of a sprite mix with the picture below, set by `080n`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "std",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum BlendMode {
+    /// Over the picture by the alpha of the palette color.
+    #[default]
+    Normal,
+    /// A quarter of the sprite color and three quarters of the picture.
+    Percent25,
+    /// Half of each.
+    Percent50,
+    /// Sum of both, saturating at white.
+    Add,
+    /// Product of both, darkening the picture.
+    Multiply,
+}
+
+impl BlendMode {
+    /// Mode selected by `080n`, or `None` if `n` is not one.
+    pub const fn from_nibble(n: u8) -> Option<Self> {
+        Some(match n {
+            0 => Self::Normal,
+            1 => Self::Percent25,
+            2 => Self::Percent50,
+            3 => Self::Add,
+            4 => Self::Multiply,
+            _ => return None,
+        })
+    }
+
+    /// The `n` of `080n` selecting this mode.
+    pub const fn nibble(self) -> u8 {
+        self as u8
+    }
+
+    /// Mixes `color` into `below`, both `0xAARRGGBB`. The result is opaque.
+    pub fn blend(self, color: u32, below: u32) -> u32 {
+        let alpha = color >> 24;
+        let channel = |shift: u32| {
+            let (top, bottom) = ((color >> shift) & 0xFF, (below >> shift) & 0xFF);
+            let mixed = match self {
+                Self::Normal => (top * alpha + bottom * (0xFF - alpha)) / 0xFF,
+                Self::Percent25 => (top + bottom * 3) / 4,
+                Self::Percent50 => (top + bottom) / 2,
+                Self::Add => (top + bottom).min(0xFF),
+                Self::Multiply => top * bottom / 0xFF,
+            };
+            mixed << shift
+        };
+        0xFF00_0000 | channel(16) | channel(8) | channel(0)
+    }
+}
+
+/// Sound started by `060n`, as found in memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(serde::Serialize, serde::Deserialize))]
+pub struct Sample {
+    /// Address of the first sample, after the header.
+    pub address: u32,
+    /// Number of samples, 8-bit unsigned each.
+    pub len: u32,
+    /// Samples per second.
+    pub rate: u16,
+    /// Repeats until stopped by `0700`, rather than playing once.
+    pub looping: bool,
+}
+
+impl Sample {
+    /// Where the samples are in memory.
+    pub const fn range(&self) -> core::ops::Range<usize> {
+        self.address as usize..self.address as usize + self.len as usize
+    }
+}
+
+/// Mega-Chip registers of the drawing and the sound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(serde::Serialize, serde::Deserialize))]
+pub struct MegaRegisters {
+    /// Width of sprites in pixels, set by `03nn`, 0 meaning 256.
+    pub sprite_width: u8,
+    /// Height of sprites in pixels, set by `04nn`, 0 meaning 256.
+    pub sprite_height: u8,
+    /// Opacity of the back buffer shown by `00E0` over the picture, set by `05nn`.
+    pub alpha: u8,
+    pub blend_mode: BlendMode,
+    /// Palette entry that sets `VF` when a sprite covers it, set by `09nn`.
+    pub collision_color: u8,
+    /// Sound playing, started by `060n` and stopped by `0700`. A sound played
+    /// once stays here after it ends.
+    pub sample: Option<Sample>,
+}
+
+impl MegaRegisters {
+    /// The registers at power on, with opaque 1×1 sprites.
+    pub const fn new() -> Self {
+        Self {
+            sprite_width: 1,
+            sprite_height: 1,
+            alpha: 0xFF,
+            blend_mode: BlendMode::Normal,
+            collision_color: 0,
+            sample: None,
+        }
+    }
+
+    /// Width and height of sprites in pixels.
+    pub fn sprite_size(&self) -> (usize, usize) {
+        let size = |value: u8| if value == 0 { 256 } else { value as usize };
+        (size(self.sprite_width), size(self.sprite_height))
+    }
+}
+
+impl Default for MegaRegisters {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Palette and buffers of the Mega-Chip display.
+///
+/// Sprites are drawn into the back buffer, keeping the palette entry drawn
+/// last at every pixel for collisions. `00E0` mixes it into the picture shown
+/// and starts the next one from black.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MegaScreen {
+    /// Colors loaded by `02nn`, as `0xAARRGGBB`.
+    pub palette: [u32; PALETTE_SIZE],
+    /// Palette entry drawn last at each pixel of the back buffer, row by row.
+    pub back_indices: Vec<u8>,
+    /// Colors of the back buffer.
+    pub back: Vec<u32>,
+    /// Colors of the picture shown.
+    pub front: Vec<u32>,
+}
+
+#[cfg(feature = "std")]
+impl MegaScreen {
+    /// Black buffers and a palette of transparent black.
+    pub fn new() -> Self {
+        let pixels = MEGA_SCREEN_WIDTH * MEGA_SCREEN_HEIGHT;
+        Self {
+            palette: [0; PALETTE_SIZE],
+            back_indices: vec![0; pixels],
+            back: vec![BLACK; pixels],
+            front: vec![BLACK; pixels],
+        }
+    }
+
+    /// Mixes palette entry `index` into the back buffer at (`x`, `y`) and
+    /// returns the entry drawn there before.
+    pub fn blend(&mut self, x: usize, y: usize, index: u8, mode: BlendMode) -> u8 {
+        let pixel = y * MEGA_SCREEN_WIDTH + x;
+        let previous = self.back_indices[pixel];
+        self.back[pixel] = mode.blend(self.palette[index as usize], self.back[pixel]);
+        self.back_indices[pixel] = index;
+        previous
+    }
+
+    /// Shows the back buffer over the picture with opacity `alpha` and clears it.
+    pub fn present(&mut self, alpha: u8) {
+        for (front, back) in self.front.iter_mut().zip(&self.back) {
+            *front = BlendMode::Normal.blend(back & 0xFF_FFFF | (alpha as u32) << 24, *front);
+        }
+        self.back.fill(BLACK);
+        self.back_indices.fill(0);
+    }
+
+    /// Moves the back buffer by (`dx`, `dy`), filling uncovered pixels with black.
+    pub fn scroll(&mut self, dx: isize, dy: isize) {
+        let (width, height) = (MEGA_SCREEN_WIDTH as isize, MEGA_SCREEN_HEIGHT as isize);
+        let (indices, colors) = (self.back_indices.clone(), self.back.clone());
+        for y in 0..height {
+            for x in 0..width {
+                let (source_x, source_y) = (x - dx, y - dy);
+                let index = (y * width + x) as usize;
+                if (0..width).contains(&source_x) && (0..height).contains(&source_y) {
+                    let source = (source_y * width + source_x) as usize;
+                    self.back_indices[index] = indices[source];
+                    self.back[index] = colors[source];
+                } else {
+                    self.back_indices[index] = 0;
+                    self.back[index] = BLACK;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for MegaScreen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
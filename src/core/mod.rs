@@ -0,0 +1,16 @@
+//! Platform independent emulation core.
+//!
+//! Everything in this module compiles without `std`, so it can be used on
+//! embedded targets that provide their own display and input.
+
+pub mod bus;
+pub mod chip;
+pub mod config;
+pub mod error;
+pub mod instruction;
+pub mod keypad;
+pub mod megachip;
+pub mod platform;
+pub mod quirks;
+pub mod rng;
+pub mod screen;
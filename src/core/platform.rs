@@ -0,0 +1,114 @@
+use core::{fmt, str::FromStr};
+
+use crate::core::{
+    config::{MachineConfig, DEFAULT_PROGRAM_START, XO_CHIP_MEMORY_SIZE},
+    megachip,
+    quirks::Quirks,
+    screen::Resolution,
+};
+
+/// Where the hi-res CHIP-8 interpreter hands over to the program, after the
+/// ROM's initial `1260` jump into its 64x64 display routine.
+const HIRES_ENTRY_POINT: u16 = 0x2C0;
+/// CHIP-8X programs load after the larger interpreter.
+const CHIP8X_PROGRAM_START: u16 = 0x300;
+/// ETI-660 programs load past the interpreter and its display memory.
+const ETI660_PROGRAM_START: u16 = 0x600;
+
+/// CHIP-8 variants, each bundling the quirks and memory layout its programs expect.
+///
+/// The quirks follow the platform definitions of the community
+/// [chip-8 database](https://github.com/chip-8/chip-8-database).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Platform {
+    /// The original COSMAC VIP interpreter.
+    Vip,
+    /// The two-page hi-res CHIP-8 variant for the VIP, with a 64×64 display.
+    Hires,
+    /// CHIP-8X for the VIP with the VP-590 color board.
+    Chip8x,
+    /// CHIP-8 on the ETI-660 learning computer.
+    Eti660,
+    /// CHIP-48 for the HP-48 calculators.
+    Chip48,
+    /// SUPER-CHIP 1.1.
+    Schip,
+    /// XO-CHIP, as implemented by Octo.
+    XoChip,
+    /// Mega-Chip, SUPER-CHIP with a 256-color display and digitized sound.
+    MegaChip,
+}
+
+impl Platform {
+    pub const ALL: [Platform; 8] = [
+        Self::Vip,
+        Self::Hires,
+        Self::Chip8x,
+        Self::Eti660,
+        Self::Chip48,
+        Self::Schip,
+        Self::XoChip,
+        Self::MegaChip,
+    ];
+
+    /// Name accepted by `--platform`.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Vip => "vip",
+            Self::Hires => "hires",
+            Self::Chip8x => "chip8x",
+            Self::Eti660 => "eti660",
+            Self::Chip48 => "chip48",
+            Self::Schip => "schip",
+            Self::XoChip => "xochip",
+            Self::MegaChip => "megachip",
+        }
+    }
+
+    pub const fn quirks(self) -> Quirks {
+        match self {
+            Self::Vip | Self::Hires | Self::Chip8x | Self::Eti660 => Quirks::vip(),
+            Self::Chip48 => Quirks::chip48(),
+            Self::Schip | Self::MegaChip => Quirks::schip(),
+            Self::XoChip => Quirks::xo_chip(),
+        }
+    }
+
+    pub const fn config(self) -> MachineConfig {
+        match self {
+            Self::Hires => MachineConfig::new()
+                .with_entry_point(HIRES_ENTRY_POINT)
+                .with_resolution(Resolution::Tall),
+            Self::Chip8x => MachineConfig::new()
+                .with_program_start(CHIP8X_PROGRAM_START)
+                .with_chip8x(),
+            Self::Eti660 => MachineConfig::new().with_program_start(ETI660_PROGRAM_START),
+            Self::XoChip => MachineConfig::new().with_memory_size(XO_CHIP_MEMORY_SIZE),
+            // Grows to fit the ROM when it is loaded.
+            Self::MegaChip => MachineConfig::new()
+                .with_memory_size(megachip::memory_size(DEFAULT_PROGRAM_START, 0))
+                .with_megachip(),
+            _ => MachineConfig::new(),
+        }
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Platform {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|platform| platform.name() == s)
+            .ok_or(
+            "expected one of `vip`, `hires`, `chip8x`, `eti660`, `chip48`, `schip`, `xochip` or \
+                 `megachip`",
+        )
+    }
+}
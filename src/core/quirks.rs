@@ -0,0 +1,160 @@
+use core::str::FromStr;
+
+/// How `Fx55` and `Fx65` advance `I` after transferring registers.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "std",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum MemoryIncrement {
+    /// `I` is left unchanged, as on SUPER-CHIP.
+    #[default]
+    None,
+    /// `I` is increased by `x`, as on the HP-48 CHIP-48 interpreter.
+    X,
+    /// `I` is increased by `x + 1`, pointing past the last register, as on the COSMAC VIP.
+    XPlusOne,
+}
+
+impl MemoryIncrement {
+    /// Amount added to `I` after transferring registers `V0` through `Vx`.
+    pub const fn amount(self, x: u8) -> u16 {
+        match self {
+            Self::None => 0,
+            Self::X => x as u16,
+            Self::XPlusOne => x as u16 + 1,
+        }
+    }
+}
+
+impl FromStr for MemoryIncrement {
+    type Err = &'static str;
+
+    /// Parses `none`, `x` or `x+1`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "x" => Ok(Self::X),
+            "x+1" => Ok(Self::XPlusOne),
+            _ => Err("expected one of `none`, `x` or `x+1`"),
+        }
+    }
+}
+
+/// What happens to the parts of a sprite drawn past the edge of the screen.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "std",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum SpriteEdge {
+    /// Pixels wrap around to the opposite side of the screen.
+    #[default]
+    Wrap,
+    /// Pixels past the edge are not drawn, as on the VIP and most later interpreters.
+    Clip,
+}
+
+impl FromStr for SpriteEdge {
+    type Err = &'static str;
+
+    /// Parses `wrap` or `clip`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "wrap" => Ok(Self::Wrap),
+            "clip" => Ok(Self::Clip),
+            _ => Err("expected `wrap` or `clip`"),
+        }
+    }
+}
+
+/// Behaviors that differ between CHIP-8 interpreters.
+///
+/// Defaults follow common modern interpreters rather than one platform, and
+/// each field notes its default. [`Quirks::vip`] and the other presets give
+/// the behavior of a specific interpreter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(serde::Serialize, serde::Deserialize))]
+pub struct Quirks {
+    /// `Fx0A` completes when the key is released, rather than when it is pressed.
+    /// Defaults to waiting for the release, as the VIP did.
+    pub key_wait_release: bool,
+    /// `8xy6` and `8xyE` shift `Vy` and store the result in `Vx`, as the VIP did.
+    /// Defaults to shifting `Vx` in place, like CHIP-48 and SUPER-CHIP.
+    pub shift_vy: bool,
+    /// `8xy1`, `8xy2` and `8xy3` reset `VF` to 0, as the VIP did. Defaults to leaving `VF` untouched.
+    pub vf_reset: bool,
+    /// `Dxyn` waits for the vertical blank, so at most one sprite is drawn per
+    /// 60 Hz frame, as on the VIP. Defaults to drawing immediately.
+    pub display_wait: bool,
+    /// Whether sprites wrap around or are clipped at the screen edges. The
+    /// starting position always wraps. Defaults to wrapping.
+    pub sprite_edge: SpriteEdge,
+    /// How `Fx55` and `Fx65` advance `I`. Defaults to leaving it unchanged.
+    pub memory_increment: MemoryIncrement,
+    /// `Bxnn` jumps to `xnn + Vx` instead of `nnn + V0`, as on CHIP-48 and SUPER-CHIP.
+    /// Defaults to `nnn + V0`.
+    pub jump_vx: bool,
+}
+
+impl Quirks {
+    pub const fn new() -> Self {
+        Self {
+            key_wait_release: true,
+            shift_vy: false,
+            vf_reset: false,
+            display_wait: false,
+            sprite_edge: SpriteEdge::Wrap,
+            memory_increment: MemoryIncrement::None,
+            jump_vx: false,
+        }
+    }
+
+    /// Behavior of the original COSMAC VIP interpreter.
+    pub const fn vip() -> Self {
+        Self {
+            shift_vy: true,
+            vf_reset: true,
+            display_wait: true,
+            sprite_edge: SpriteEdge::Clip,
+            memory_increment: MemoryIncrement::XPlusOne,
+            ..Self::new()
+        }
+    }
+
+    /// Behavior of the CHIP-48 interpreter for the HP-48 calculators.
+    pub const fn chip48() -> Self {
+        Self {
+            sprite_edge: SpriteEdge::Clip,
+            memory_increment: MemoryIncrement::X,
+            jump_vx: true,
+            ..Self::new()
+        }
+    }
+
+    /// Behavior of SUPER-CHIP 1.1.
+    pub const fn schip() -> Self {
+        Self {
+            sprite_edge: SpriteEdge::Clip,
+            jump_vx: true,
+            ..Self::new()
+        }
+    }
+
+    /// Behavior of XO-CHIP.
+    pub const fn xo_chip() -> Self {
+        Self {
+            shift_vy: true,
+            memory_increment: MemoryIncrement::XPlusOne,
+            ..Self::new()
+        }
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
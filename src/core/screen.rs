@@ -0,0 +1,388 @@
+#[cfg(feature = "std")]
+use crate::core::megachip::{BlendMode, MegaScreen};
+use crate::core::{
+    bus::DisplayBus,
+    megachip::{MEGA_SCREEN_HEIGHT, MEGA_SCREEN_WIDTH},
+};
+
+pub const SCREEN_WIDTH: usize = 64;
+pub const SCREEN_HEIGHT: usize = 32;
+/// SUPER-CHIP high resolution mode.
+pub const HIRES_SCREEN_WIDTH: usize = 128;
+pub const HIRES_SCREEN_HEIGHT: usize = 64;
+/// Number of XO-CHIP bit planes.
+pub const PLANE_COUNT: usize = 2;
+
+/// Pixels stored. Only builds with `std` have the Mega-Chip display, the others
+/// get by with the SUPER-CHIP one.
+#[cfg(feature = "std")]
+const MAX_PIXELS: usize = MEGA_SCREEN_WIDTH * MEGA_SCREEN_HEIGHT;
+#[cfg(not(feature = "std"))]
+const MAX_PIXELS: usize = HIRES_SCREEN_WIDTH * HIRES_SCREEN_HEIGHT;
+
+/// Width in pixels of a CHIP-8X color zone. Zones are one pixel high.
+pub const COLOR_ZONE_WIDTH: usize = 8;
+/// Number of CHIP-8X color zones, covering the 64×32 display.
+pub const COLOR_ZONE_COUNT: usize = (SCREEN_WIDTH / COLOR_ZONE_WIDTH) * SCREEN_HEIGHT;
+
+/// CHIP-8X foreground colors as `0xAARRGGBB`: black, red, blue, violet,
+/// green, yellow, aqua and white.
+pub const FOREGROUND_COLORS: [u32; 8] = [
+    0xFF000000, 0xFFFF0000, 0xFF0000FF, 0xFFFF00FF, 0xFF00FF00, 0xFFFFFF00, 0xFF00FFFF, 0xFFFFFFFF,
+];
+/// CHIP-8X background colors, in the order `02A0` cycles through them: blue,
+/// black, green and red.
+pub const BACKGROUND_COLORS: [u32; 4] = [0xFF000080, 0xFF000000, 0xFF008000, 0xFF800000];
+
+/// Colors of each combination of XO-CHIP planes as `0xAARRGGBB`, indexed by
+/// the plane bits of a pixel: the background, the first plane, the second
+/// plane and both, like Octo. White on black, with grays for the second plane.
+pub const DEFAULT_PLANE_COLORS: [u32; 4] = [0xFF000000, 0xFFFFFFFF, 0xFFAAAAAA, 0xFF555555];
+
+/// Colors of the VP-590 color board used by CHIP-8X.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColorZones {
+    /// Index into [`BACKGROUND_COLORS`].
+    pub background: u8,
+    /// Index into [`FOREGROUND_COLORS`] for each 8×1 zone, row by row.
+    pub foreground: [u8; COLOR_ZONE_COUNT],
+}
+
+impl ColorZones {
+    /// Red on blue, the colors at power on.
+    pub const fn new() -> Self {
+        Self {
+            background: 0,
+            foreground: [1; COLOR_ZONE_COUNT],
+        }
+    }
+
+    /// Color of the pixel at (`x`, `y`), either on or off.
+    pub fn color(&self, x: usize, y: usize, on: bool) -> u32 {
+        let zone = (y % SCREEN_HEIGHT) * (SCREEN_WIDTH / COLOR_ZONE_WIDTH)
+            + (x % SCREEN_WIDTH) / COLOR_ZONE_WIDTH;
+        if on {
+            FOREGROUND_COLORS[self.foreground[zone] as usize]
+        } else {
+            BACKGROUND_COLORS[self.background as usize]
+        }
+    }
+}
+
+impl Default for ColorZones {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Display modes of the supported CHIP-8 variants.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "std",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum Resolution {
+    /// 64×32, the original CHIP-8 display.
+    #[default]
+    Low,
+    /// 64×64, used by the two-page hi-res CHIP-8 variant.
+    Tall,
+    /// 128×64, the SUPER-CHIP and XO-CHIP high resolution mode.
+    High,
+    /// 256×192 in 256 colors, the Mega-Chip mode.
+    Mega,
+}
+
+impl Resolution {
+    /// Width and height in pixels.
+    pub const fn size(self) -> (usize, usize) {
+        match self {
+            Self::Low => (SCREEN_WIDTH, SCREEN_HEIGHT),
+            Self::Tall => (SCREEN_WIDTH, SCREEN_WIDTH),
+            Self::High => (HIRES_SCREEN_WIDTH, HIRES_SCREEN_HEIGHT),
+            Self::Mega => (MEGA_SCREEN_WIDTH, MEGA_SCREEN_HEIGHT),
+        }
+    }
+}
+
+/// Framebuffer, 64×32 unless switched to another [`Resolution`].
+///
+/// Pixels are stored row by row, using the first `width() * height()` entries.
+/// Each pixel holds one bit per plane, so plain CHIP-8 programs only ever set
+/// bit 0 while XO-CHIP programs can produce four colors. On the Mega-Chip
+/// display each holds the palette entry shown there instead, with the colors
+/// in [`Screen::mega`].
+#[derive(Clone)]
+pub struct Screen {
+    pub pixels: [u8; MAX_PIXELS],
+    /// CHIP-8X colors, or `None` for a monochrome display.
+    pub colors: Option<ColorZones>,
+    /// Mega-Chip palette and buffers, while in [`Resolution::Mega`].
+    #[cfg(feature = "std")]
+    pub mega: Option<Box<MegaScreen>>,
+    resolution: Resolution,
+    /// Bit mask of the planes affected by drawing, clearing and scrolling.
+    planes: u8,
+    /// Something visible changed since the last [`Screen::take_dirty`].
+    dirty: bool,
+}
+
+impl Screen {
+    pub fn new() -> Self {
+        Self {
+            pixels: [0; MAX_PIXELS],
+            colors: None,
+            #[cfg(feature = "std")]
+            mega: None,
+            resolution: Resolution::Low,
+            planes: 0b01,
+            dirty: true,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.resolution.size().0
+    }
+
+    pub fn height(&self) -> usize {
+        self.resolution.size().1
+    }
+
+    pub fn resolution(&self) -> Resolution {
+        self.resolution
+    }
+
+    /// Switches resolution, clearing every plane. Without `std` the Mega-Chip
+    /// display is not available and switching to it is ignored.
+    pub fn set_resolution(&mut self, resolution: Resolution) {
+        #[cfg(not(feature = "std"))]
+        if resolution == Resolution::Mega {
+            return;
+        }
+        self.resolution = resolution;
+        self.pixels = [0; MAX_PIXELS];
+        #[cfg(feature = "std")]
+        {
+            self.mega = (resolution == Resolution::Mega).then(Box::default);
+        }
+        self.dirty = true;
+    }
+
+    /// Whether anything visible changed since the last call, so a frontend
+    /// can skip redrawing an idle screen.
+    pub fn take_dirty(&mut self) -> bool {
+        core::mem::take(&mut self.dirty)
+    }
+
+    /// Flags the screen as changed, after writing to its public fields directly.
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    pub fn selected_planes(&self) -> u8 {
+        self.planes
+    }
+
+    pub fn select_planes(&mut self, planes: u8) {
+        self.planes = planes & ((1 << PLANE_COUNT) - 1);
+    }
+
+    /// Visible pixels, one slice per row.
+    pub fn rows(&self) -> impl Iterator<Item = &[u8]> {
+        let width = self.width();
+        self.pixels[..width * self.height()].chunks(width)
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> u8 {
+        self.pixels[y * self.width() + x]
+    }
+
+    /// Color of the pixel at (`x`, `y`) as `0xAARRGGBB`: the Mega-Chip or
+    /// CHIP-8X colors if the program set them, otherwise the one of
+    /// `plane_colors` for its planes, see [`DEFAULT_PLANE_COLORS`].
+    pub fn color(&self, x: usize, y: usize, plane_colors: &[u32; 4]) -> u32 {
+        #[cfg(feature = "std")]
+        if let Some(mega) = &self.mega {
+            return mega.front[y * MEGA_SCREEN_WIDTH + x];
+        }
+        let pixel = self.pixel(x, y);
+        if let Some(colors) = &self.colors {
+            colors.color(x, y, pixel != 0)
+        } else {
+            plane_colors[(pixel & 0b11) as usize]
+        }
+    }
+
+    /// Flips the pixel at (`x`, `y`) in the first plane.
+    pub fn toggle(&mut self, x: u8, y: u8) -> bool {
+        self.toggle_plane(0, x, y)
+    }
+
+    /// Flips the pixel at (`x`, `y`) in `plane` and returns whether it was previously on.
+    pub fn toggle_plane(&mut self, plane: u8, x: u8, y: u8) -> bool {
+        let (x, y) = self.clamp(x, y);
+        let index = y * self.width() + x;
+        let bit = 1 << plane;
+        let previous = self.pixels[index] & bit != 0;
+        self.pixels[index] ^= bit;
+        self.dirty = true;
+        previous
+    }
+
+    /// Sets the foreground color of the zones from column `left` to `right`
+    /// and row `top` to `bottom`, inclusive. Columns are 8 pixels wide.
+    pub fn set_foreground(&mut self, left: u8, right: u8, top: u8, bottom: u8, color: u8) {
+        let Some(colors) = &mut self.colors else {
+            return;
+        };
+        let columns = SCREEN_WIDTH / COLOR_ZONE_WIDTH;
+        for row in top as usize..=(bottom as usize).min(SCREEN_HEIGHT - 1) {
+            for column in left as usize..=(right as usize).min(columns - 1) {
+                colors.foreground[row * columns + column] = color % FOREGROUND_COLORS.len() as u8;
+            }
+        }
+        self.dirty = true;
+    }
+
+    /// Turns off every pixel in the selected planes.
+    pub fn clear(&mut self) {
+        for pixel in &mut self.pixels {
+            *pixel &= !self.planes;
+        }
+        self.dirty = true;
+    }
+
+    /// Turns on every pixel in the selected planes.
+    pub fn fill(&mut self) {
+        for pixel in &mut self.pixels {
+            *pixel |= self.planes;
+        }
+        self.dirty = true;
+    }
+
+    pub fn clamp(&self, x: u8, y: u8) -> (usize, usize) {
+        (x as usize % self.width(), y as usize % self.height())
+    }
+
+    /// Moves the selected planes by (`dx`, `dy`), filling uncovered pixels with
+    /// blanks. On the Mega-Chip display the back buffer moves instead.
+    pub fn scroll(&mut self, dx: isize, dy: isize) {
+        #[cfg(feature = "std")]
+        if let Some(mega) = &mut self.mega {
+            mega.scroll(dx, dy);
+            return;
+        }
+        let (width, height) = (self.width() as isize, self.height() as isize);
+        let source = self.pixels;
+        for y in 0..height {
+            for x in 0..width {
+                let (source_x, source_y) = (x - dx, y - dy);
+                let moved = if (0..width).contains(&source_x) && (0..height).contains(&source_y) {
+                    source[(source_y * width + source_x) as usize] & self.planes
+                } else {
+                    0
+                };
+                let index = (y * width + x) as usize;
+                self.pixels[index] = (source[index] & !self.planes) | moved;
+            }
+        }
+        self.dirty = true;
+    }
+}
+
+impl Default for Screen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DisplayBus for Screen {
+    fn clear(&mut self) {
+        Screen::clear(self);
+    }
+
+    fn toggle(&mut self, x: u8, y: u8) -> bool {
+        Screen::toggle(self, x, y)
+    }
+
+    fn toggle_plane(&mut self, plane: u8, x: u8, y: u8) -> bool {
+        Screen::toggle_plane(self, plane, x, y)
+    }
+
+    fn set_resolution(&mut self, resolution: Resolution) {
+        Screen::set_resolution(self, resolution);
+    }
+
+    fn resolution(&self) -> Resolution {
+        self.resolution
+    }
+
+    fn size(&self) -> (usize, usize) {
+        (self.width(), self.height())
+    }
+
+    fn select_planes(&mut self, planes: u8) {
+        Screen::select_planes(self, planes);
+    }
+
+    fn selected_planes(&self) -> u8 {
+        self.planes
+    }
+
+    fn set_color_mode(&mut self, enabled: bool) {
+        self.colors = enabled.then(ColorZones::new);
+        self.dirty = true;
+    }
+
+    fn cycle_background(&mut self) {
+        if let Some(colors) = &mut self.colors {
+            colors.background = (colors.background + 1) % BACKGROUND_COLORS.len() as u8;
+            self.dirty = true;
+        }
+    }
+
+    fn set_foreground(&mut self, left: u8, right: u8, top: u8, bottom: u8, color: u8) {
+        Screen::set_foreground(self, left, right, top, bottom, color);
+    }
+
+    fn scroll_down(&mut self, rows: u8) {
+        self.scroll(0, rows as isize);
+    }
+
+    fn scroll_up(&mut self, rows: u8) {
+        self.scroll(0, -(rows as isize));
+    }
+
+    fn scroll_right(&mut self, columns: u8) {
+        self.scroll(columns as isize, 0);
+    }
+
+    fn scroll_left(&mut self, columns: u8) {
+        self.scroll(-(columns as isize), 0);
+    }
+
+    #[cfg(feature = "std")]
+    fn set_palette(&mut self, index: u8, color: u32) {
+        if let Some(mega) = &mut self.mega {
+            mega.palette[index as usize] = color;
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn blend(&mut self, x: u8, y: u8, index: u8, mode: BlendMode) -> u8 {
+        match &mut self.mega {
+            Some(mega) => mega.blend(x as usize, y as usize % MEGA_SCREEN_HEIGHT, index, mode),
+            None => 0,
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn present(&mut self, alpha: u8) {
+        if let Some(mega) = &mut self.mega {
+            self.pixels[..mega.back_indices.len()].copy_from_slice(&mega.back_indices);
+            mega.present(alpha);
+            self.dirty = true;
+        }
+    }
+}
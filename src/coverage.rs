@@ -0,0 +1,91 @@
+//! Exports which instructions of a ROM executed, to find dead code or measure
+//! how much of a program a test run reaches.
+
+use std::{fmt::Write, ops::Range};
+
+use serde::Serialize;
+
+use crate::{instruction::Instruction, profiler::Profiler};
+
+/// Coverage of a memory range, as exported to JSON.
+#[derive(Debug, Clone, Serialize)]
+pub struct Coverage {
+    /// First address of the range.
+    pub start: usize,
+    /// Address after the range.
+    pub end: usize,
+    /// Executed addresses in ascending order.
+    pub executed: Vec<ExecutedAddress>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ExecutedAddress {
+    pub address: u16,
+    pub count: u64,
+}
+
+impl Coverage {
+    /// Collects the addresses in `range` that `profiler` saw executing.
+    pub fn new(profiler: &Profiler, range: Range<usize>) -> Self {
+        let executed = range
+            .clone()
+            .filter_map(|address| {
+                let count = profiler.executed(address as u16);
+                (count > 0).then_some(ExecutedAddress {
+                    address: address as u16,
+                    count,
+                })
+            })
+            .collect();
+        Self {
+            start: range.start,
+            end: range.end,
+            executed,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("coverage is always serializable")
+    }
+}
+
+/// Disassembly of `range` in `memory`, each line prefixed with how often it
+/// executed, or `-` if it never did.
+///
+/// Instructions are decoded one after another from the start of the range.
+/// Decoding skips ahead to an executed address whenever one falls inside the
+/// current instruction, so code following unaligned data is still listed right.
+pub fn annotate(profiler: &Profiler, memory: &[u8], range: Range<usize>) -> String {
+    let end = range.end.min(memory.len());
+    let mut text = String::new();
+    let mut address = range.start;
+    while address < end {
+        let instruction = Instruction::decode_memory(memory, address);
+        let mut size = instruction.map_or(2, |instruction| instruction.size() as usize);
+        if let Some(next) =
+            (address + 1..address + size).find(|&next| profiler.executed(next as u16) > 0)
+        {
+            size = next - address;
+        }
+        let size = size.min(end - address);
+        let count = match profiler.executed(address as u16) {
+            0 => "-".to_owned(),
+            count => count.to_string(),
+        };
+        let bytes = memory[address..address + size]
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect::<String>();
+        let mnemonic = match instruction {
+            Some(instruction) if size == instruction.size() as usize => instruction.to_string(),
+            _ => String::new(),
+        };
+        let _ = writeln!(
+            text,
+            "{:>10}  {:04X}  {:<8}  {}",
+            count, address, bytes, mnemonic
+        );
+        address += size;
+    }
+    text
+}
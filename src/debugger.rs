@@ -0,0 +1,344 @@
+//! Breakpoints and other debugging aids for developing ROMs.
+
+use std::{
+    collections::BTreeSet,
+    fmt,
+    fmt::Write,
+    ops::{Range, RangeInclusive},
+};
+
+use crate::{
+    bus::{AudioBus, DisplayBus, KeypadBus},
+    chip::Chip8,
+    instruction::Instruction,
+    symbols::Symbols,
+};
+
+/// Most instructions a debugger front end steps at once, as the window and
+/// the emulation thread wait for the emulator while they run.
+pub const MAX_STEP_COUNT: usize = 10_000;
+
+/// Kind of memory access caught by a watchpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Read => "read",
+            Self::Write => "written",
+        })
+    }
+}
+
+/// Where [`Debugger::run_to`] stops once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunTarget {
+    /// Stop when the program counter reaches the address.
+    Address(u16),
+    /// Stop before the next `Dxyn` or `00E0`.
+    Draw,
+}
+
+impl fmt::Display for RunTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Address(address) => write!(f, "{:#05X}", address),
+            Self::Draw => f.write_str("the next draw"),
+        }
+    }
+}
+
+/// Why execution stopped in the debugger.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakReason {
+    /// The program counter reached a breakpoint.
+    Breakpoint(u16),
+    /// A `Dxyn` at the address is next, with break on draw enabled.
+    Draw(u16),
+    /// A `00E0` at the address is next, with break on clear enabled.
+    Clear(u16),
+    /// Execution reached the target of [`Debugger::run_to`].
+    Reached(RunTarget),
+    /// The instruction at `pc` is about to access a watched `address`.
+    Watchpoint {
+        address: u16,
+        access: Access,
+        pc: u16,
+        instruction: Instruction,
+    },
+}
+
+impl fmt::Display for BreakReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Breakpoint(address) => write!(f, "breakpoint at {:#05X}", address),
+            Self::Draw(address) => write!(f, "draw at {:#05X}", address),
+            Self::Clear(address) => write!(f, "clear at {:#05X}", address),
+            Self::Reached(target) => write!(f, "reached {}", target),
+            Self::Watchpoint {
+                address,
+                access,
+                pc,
+                instruction,
+            } => write!(
+                f,
+                "watchpoint {:#05X} {} by `{}` at {:#05X}",
+                address, access, instruction, pc
+            ),
+        }
+    }
+}
+
+/// Conditions that pause the [`Emulator`](crate::emulator::Emulator) before an instruction executes.
+#[derive(Debug, Default, Clone)]
+pub struct Debugger {
+    breakpoints: BTreeSet<u16>,
+    watchpoints: Vec<RangeInclusive<u16>>,
+    run_target: Option<RunTarget>,
+    break_on_draw: bool,
+    break_on_clear: bool,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether any condition is set, requiring every instruction to be checked.
+    pub fn is_active(&self) -> bool {
+        !self.breakpoints.is_empty()
+            || !self.watchpoints.is_empty()
+            || self.run_target.is_some()
+            || self.break_on_draw
+            || self.break_on_clear
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    pub fn add_breakpoint(&mut self, address: u16) {
+        self.breakpoints.insert(address);
+    }
+
+    pub fn remove_breakpoint(&mut self, address: u16) {
+        self.breakpoints.remove(&address);
+    }
+
+    /// Adds a breakpoint at `address`, or removes it if there is one. Returns
+    /// whether the breakpoint is now set.
+    pub fn toggle_breakpoint(&mut self, address: u16) -> bool {
+        if self.breakpoints.remove(&address) {
+            false
+        } else {
+            self.breakpoints.insert(address);
+            true
+        }
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    /// Stops once `target` is reached, without setting a permanent breakpoint.
+    /// Replaces any previous target.
+    pub fn run_to(&mut self, target: RunTarget) {
+        self.run_target = Some(target);
+    }
+
+    pub fn run_target(&self) -> Option<RunTarget> {
+        self.run_target
+    }
+
+    pub fn cancel_run_to(&mut self) {
+        self.run_target = None;
+    }
+
+    pub fn break_on_draw(&self) -> bool {
+        self.break_on_draw
+    }
+
+    /// Stops before every `Dxyn`, to inspect the screen between draws.
+    pub fn set_break_on_draw(&mut self, enabled: bool) {
+        self.break_on_draw = enabled;
+    }
+
+    pub fn break_on_clear(&self) -> bool {
+        self.break_on_clear
+    }
+
+    /// Stops before every `00E0`.
+    pub fn set_break_on_clear(&mut self, enabled: bool) {
+        self.break_on_clear = enabled;
+    }
+
+    pub fn watchpoints(&self) -> &[RangeInclusive<u16>] {
+        &self.watchpoints
+    }
+
+    /// Stops before any instruction reading or writing memory in `range`.
+    pub fn add_watchpoint(&mut self, range: RangeInclusive<u16>) {
+        if !self.watchpoints.contains(&range) {
+            self.watchpoints.push(range);
+        }
+    }
+
+    pub fn remove_watchpoint(&mut self, range: &RangeInclusive<u16>) {
+        self.watchpoints.retain(|watchpoint| watchpoint != range);
+    }
+
+    pub fn clear_watchpoints(&mut self) {
+        self.watchpoints.clear();
+    }
+
+    /// Checks whether the machine should stop before executing its next instruction.
+    pub fn check<D: DisplayBus, K: KeypadBus, A: AudioBus>(
+        &mut self,
+        chip: &Chip8<D, K, A>,
+    ) -> Option<BreakReason> {
+        if self.breakpoints.contains(&chip.pc) {
+            return Some(BreakReason::Breakpoint(chip.pc));
+        }
+        if let Some(target) = self.run_target {
+            let reached = match target {
+                RunTarget::Address(address) => chip.pc == address,
+                RunTarget::Draw => matches!(
+                    chip.next_instruction(),
+                    Ok(Instruction::Draw { .. } | Instruction::ClearScreen)
+                ),
+            };
+            if reached {
+                self.run_target = None;
+                return Some(BreakReason::Reached(target));
+            }
+        }
+        if self.break_on_draw || self.break_on_clear {
+            match chip.next_instruction() {
+                Ok(Instruction::Draw { .. }) if self.break_on_draw => {
+                    return Some(BreakReason::Draw(chip.pc));
+                }
+                Ok(Instruction::ClearScreen) if self.break_on_clear => {
+                    return Some(BreakReason::Clear(chip.pc));
+                }
+                _ => {}
+            }
+        }
+        if self.watchpoints.is_empty() {
+            return None;
+        }
+        let instruction = chip.next_instruction().ok()?;
+        let (access, accessed) = memory_access(chip, instruction)?;
+        self.watchpoints.iter().find_map(|watchpoint| {
+            let start = (*watchpoint.start() as usize).max(accessed.start);
+            let end = (*watchpoint.end() as usize).min(accessed.end - 1);
+            (start <= end).then_some(BreakReason::Watchpoint {
+                address: start as u16,
+                access,
+                pc: chip.pc,
+                instruction,
+            })
+        })
+    }
+}
+
+/// Memory `instruction` would access if `chip` executed it now.
+fn memory_access<D: DisplayBus, K: KeypadBus, A: AudioBus>(
+    chip: &Chip8<D, K, A>,
+    instruction: Instruction,
+) -> Option<(Access, Range<usize>)> {
+    let (access, len) = match instruction {
+        Instruction::StoreRegs { x } => (Access::Write, x as usize + 1),
+        Instruction::LoadRegs { x } => (Access::Read, x as usize + 1),
+        Instruction::StoreBcd { .. } => (Access::Write, 3),
+        Instruction::StoreRange { x, y } => (Access::Write, x.abs_diff(y) as usize + 1),
+        Instruction::LoadRange { x, y } => (Access::Read, x.abs_diff(y) as usize + 1),
+        Instruction::LoadAudio => (Access::Read, 16),
+        Instruction::Draw { n: 0, .. } => (
+            Access::Read,
+            32 * chip.display.selected_planes().count_ones() as usize,
+        ),
+        Instruction::Draw { n, .. } => (
+            Access::Read,
+            n as usize * chip.display.selected_planes().count_ones() as usize,
+        ),
+        _ => return None,
+    };
+    let start = chip.register_i as usize;
+    (len > 0).then_some((access, start..start + len))
+}
+
+/// Subroutine the program is executing, one level of the call stack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CallFrame {
+    /// Address of the subroutine.
+    pub entry: u16,
+    /// Address of the `CALL` instruction that entered the subroutine.
+    pub caller: u16,
+    /// Where `RET` continues.
+    pub return_address: u16,
+}
+
+/// The subroutines being executed, outermost first, from the callers on
+/// `stack` and the addresses they entered in `call_entries`, as recorded by
+/// `2nnn` when it executed.
+///
+/// `2nnn` pushes its own address, which `RET` then skips.
+pub fn call_stack(stack: &[u16], call_entries: &[u16]) -> Vec<CallFrame> {
+    stack
+        .iter()
+        .zip(call_entries)
+        .map(|(&caller, &entry)| CallFrame {
+            entry,
+            caller,
+            return_address: caller.wrapping_add(2),
+        })
+        .collect()
+}
+
+/// Registers, timers, the call stack and the next instruction of `chip`, as
+/// printed when it stops. Addresses are named after the nearest label in `symbols`.
+pub fn describe<D: DisplayBus, K: KeypadBus, A: AudioBus>(
+    chip: &Chip8<D, K, A>,
+    symbols: &Symbols,
+) -> String {
+    let mut text = String::new();
+    let instruction = match chip.next_instruction() {
+        Ok(instruction) => instruction.to_string(),
+        Err(err) => err.to_string(),
+    };
+    let _ = write!(text, "PC {:#05X}  {}", chip.pc, instruction);
+    if !symbols.is_empty() {
+        let _ = write!(text, "  ({})", symbols.describe(chip.pc));
+    }
+    text.push('\n');
+    for (row, registers) in chip.registers.chunks(8).enumerate() {
+        for (column, value) in registers.iter().enumerate() {
+            let _ = write!(text, "V{:X} {:#04X}  ", row * 8 + column, value);
+        }
+        text.push('\n');
+    }
+    let _ = writeln!(
+        text,
+        "I {:#05X}  SP {}  DT {}  ST {}",
+        chip.register_i, chip.sp, chip.delay_timer, chip.sound_timer
+    );
+    let sp = chip.sp as usize;
+    let frames = call_stack(&chip.stack[..sp], &chip.call_entries[..sp]);
+    if !frames.is_empty() {
+        text.push_str("Call stack:");
+        for frame in frames.iter().rev() {
+            let _ = write!(
+                text,
+                " {} (from {})",
+                symbols.describe(frame.entry),
+                symbols.describe(frame.caller)
+            );
+        }
+        text.push('\n');
+    }
+    text
+}
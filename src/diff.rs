@@ -0,0 +1,168 @@
+//! Readable differences between two machine states, for bisecting where two
+//! runs or emulator versions diverge.
+
+use std::fmt::{self, Debug, Write};
+
+use crate::state::MachineState;
+
+/// Differing bytes closer than this are reported as one range.
+const MEMORY_GAP: usize = 4;
+/// Bytes per line of a memory difference.
+const MEMORY_ROW_LEN: usize = 16;
+
+/// Lists every difference between states `a` and `b`, or returns an empty
+/// string if they are the same.
+///
+/// The screen is drawn for every row that differs, with `#` for pixels on in
+/// both, `-` for pixels only on in `a`, `+` for pixels only on in `b` and `*`
+/// for pixels on in both but in different XO-CHIP colors.
+pub fn diff(a: &MachineState, b: &MachineState) -> String {
+    let mut text = String::new();
+    let mut field = |name: &str, a: &dyn Debug, b: &dyn Debug| {
+        let (a, b) = (format!("{:?}", a), format!("{:?}", b));
+        if a != b {
+            let _ = writeln!(text, "{}: {} -> {}", name, a, b);
+        }
+    };
+    field("PC", &Hex(a.pc), &Hex(b.pc));
+    field("I", &Hex(a.register_i), &Hex(b.register_i));
+    for (index, (a, b)) in a.registers.iter().zip(&b.registers).enumerate() {
+        field(&format!("V{:X}", index), &Hex(*a), &Hex(*b));
+    }
+    field("SP", &a.sp, &b.sp);
+    field(
+        "stack",
+        &a.stack[..a.sp as usize]
+            .iter()
+            .map(|&address| Hex(address))
+            .collect::<Vec<_>>(),
+        &b.stack[..b.sp as usize]
+            .iter()
+            .map(|&address| Hex(address))
+            .collect::<Vec<_>>(),
+    );
+    field(
+        "call entries",
+        &a.call_entries[..a.sp as usize]
+            .iter()
+            .map(|&address| Hex(address))
+            .collect::<Vec<_>>(),
+        &b.call_entries[..b.sp as usize]
+            .iter()
+            .map(|&address| Hex(address))
+            .collect::<Vec<_>>(),
+    );
+    field("DT", &a.delay_timer, &b.delay_timer);
+    field("ST", &a.sound_timer, &b.sound_timer);
+    field("waiting for key", &a.waiting_for_key, &b.waiting_for_key);
+    field("key wait pressed", &a.key_wait_pressed, &b.key_wait_pressed);
+    field(
+        "waiting for vblank",
+        &a.waiting_for_vblank,
+        &b.waiting_for_vblank,
+    );
+    field("exited", &a.exited, &b.exited);
+    field("resolution", &a.resolution, &b.resolution);
+    field("planes", &a.planes, &b.planes);
+    field("keypad", &a.keypad, &b.keypad);
+    field("RNG state", &Hex(a.rng_state), &Hex(b.rng_state));
+    field("flags", &a.flags, &b.flags);
+    field("pitch", &a.pitch, &b.pitch);
+    field("audio pattern", &a.audio_pattern, &b.audio_pattern);
+    field("background color", &a.background_color, &b.background_color);
+    field(
+        "foreground colors",
+        &a.foreground_colors,
+        &b.foreground_colors,
+    );
+    field("Mega-Chip registers", &a.mega, &b.mega);
+    if a.mega_screen != b.mega_screen {
+        text.push_str("Mega-Chip colors: differ\n");
+    }
+
+    diff_memory(&mut text, &a.memory, &b.memory);
+    if a.resolution == b.resolution {
+        diff_screen(&mut text, a, b);
+    } else if a.screen != b.screen {
+        text.push_str("screen: differs in size\n");
+    }
+    text
+}
+
+fn diff_memory(text: &mut String, a: &[u8], b: &[u8]) {
+    if a.len() != b.len() {
+        let _ = writeln!(text, "memory size: {} -> {}", a.len(), b.len());
+    }
+    let len = a.len().min(b.len());
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for address in (0..len).filter(|&address| a[address] != b[address]) {
+        match ranges.last_mut() {
+            Some((_, end)) if address - *end <= MEMORY_GAP => *end = address + 1,
+            _ => ranges.push((address, address + 1)),
+        }
+    }
+    if ranges.is_empty() {
+        return;
+    }
+    let changed = (0..len).filter(|&address| a[address] != b[address]).count();
+    let _ = writeln!(text, "memory: {} bytes differ", changed);
+    let hex = |bytes: &[u8]| {
+        bytes
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    for (start, end) in ranges {
+        for row in (start..end).step_by(MEMORY_ROW_LEN) {
+            let row_end = (row + MEMORY_ROW_LEN).min(end);
+            let _ = writeln!(text, "  {:04X}  - {}", row, hex(&a[row..row_end]));
+            let _ = writeln!(text, "        + {}", hex(&b[row..row_end]));
+        }
+    }
+}
+
+fn diff_screen(text: &mut String, a: &MachineState, b: &MachineState) {
+    let (width, _) = a.resolution.size();
+    let changed = a
+        .screen
+        .iter()
+        .zip(&b.screen)
+        .filter(|(a, b)| a != b)
+        .count();
+    if changed == 0 {
+        return;
+    }
+    let _ = writeln!(text, "screen: {} pixels differ", changed);
+    for (y, (row_a, row_b)) in a
+        .screen
+        .chunks(width)
+        .zip(b.screen.chunks(width))
+        .enumerate()
+    {
+        if row_a == row_b {
+            continue;
+        }
+        let row = row_a
+            .iter()
+            .zip(row_b)
+            .map(|(&a, &b)| match (a != 0, b != 0) {
+                _ if a != b && a != 0 && b != 0 => '*',
+                (true, true) => '#',
+                (true, false) => '-',
+                (false, true) => '+',
+                (false, false) => '.',
+            })
+            .collect::<String>();
+        let _ = writeln!(text, "  {:3}  {}", y, row);
+    }
+}
+
+/// Formats numbers in hexadecimal through [`Debug`].
+struct Hex<T>(T);
+
+impl<T: fmt::UpperHex> Debug for Hex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#X}", self.0)
+    }
+}
@@ -0,0 +1,150 @@
+//! Annotated disassembly of whole ROMs.
+
+use std::{
+    collections::{BTreeSet, HashSet},
+    fmt::Write,
+};
+
+use crate::{instruction::Instruction, symbols::Symbols};
+
+/// Disassembles `rom` loaded at `start`, separating code from data.
+///
+/// Code is found by following every path of execution from `start`: jumps,
+/// calls and both outcomes of skips. Targets of `Bnnn` depend on a register
+/// and can't be followed, so code only reached through it is listed as data.
+/// Data is listed a byte per line with its bit pattern, which makes sprites recognizable.
+/// Jump and call targets get labels, named after `symbols` where possible.
+pub fn disassemble(rom: &[u8], start: u16, symbols: &Symbols) -> String {
+    let code = trace(rom, start);
+    let mut labels = BTreeSet::new();
+    for &address in &code {
+        if let Some(Instruction::JumpAddr(target) | Instruction::CallAddr(target)) =
+            decode(rom, start, address)
+        {
+            labels.insert(target);
+        }
+    }
+
+    let mut text = String::new();
+    let end = start as usize + rom.len();
+    let mut address = start as usize;
+    while address < end {
+        if labels.contains(&(address as u16)) || symbols.name(address as u16).is_some() {
+            let _ = writeln!(text, "{}:", label(symbols, address as u16));
+        }
+        let instruction = code
+            .contains(&(address as u16))
+            .then(|| decode(rom, start, address as u16))
+            .flatten();
+        match instruction {
+            Some(instruction) => {
+                let size = instruction.size() as usize;
+                let bytes = rom
+                    [address - start as usize..(address + size).min(end) - start as usize]
+                    .iter()
+                    .map(|byte| format!("{:02X}", byte))
+                    .collect::<String>();
+                let mnemonic = instruction.to_string();
+                match comment(instruction, address as u16, symbols) {
+                    Some(comment) => {
+                        let _ = writeln!(
+                            text,
+                            "{:04X}  {:<8}  {:<20}  ; {}",
+                            address, bytes, mnemonic, comment
+                        );
+                    }
+                    None => {
+                        let _ = writeln!(text, "{:04X}  {:<8}  {}", address, bytes, mnemonic);
+                    }
+                }
+                address += size;
+            }
+            None => {
+                let byte = rom[address - start as usize];
+                let bits = (0..8)
+                    .map(|bit| if byte & (0x80 >> bit) != 0 { '#' } else { '.' })
+                    .collect::<String>();
+                let data = format!("DB {:#04X}", byte);
+                let _ = writeln!(
+                    text,
+                    "{:04X}  {:02X}        {:<20}  ; {}",
+                    address, byte, data, bits
+                );
+                address += 1;
+            }
+        }
+    }
+    text
+}
+
+fn decode(rom: &[u8], start: u16, address: u16) -> Option<Instruction> {
+    let offset = (address as usize).checked_sub(start as usize)?;
+    Instruction::decode_memory(rom, offset)
+}
+
+/// Addresses of every instruction reachable from `start` in `rom` loaded
+/// there, see [`disassemble`].
+pub fn trace(rom: &[u8], start: u16) -> HashSet<u16> {
+    let mut code = HashSet::new();
+    let mut pending = vec![start];
+    while let Some(address) = pending.pop() {
+        if code.contains(&address) {
+            continue;
+        }
+        let Some(instruction) = decode(rom, start, address) else {
+            continue;
+        };
+        code.insert(address);
+        let next = address.wrapping_add(instruction.size());
+        match instruction {
+            Instruction::JumpAddr(target) => pending.push(target),
+            Instruction::CallAddr(target) => pending.extend([target, next]),
+            Instruction::Return | Instruction::Exit | Instruction::JumpV0Addr(_) => {}
+            Instruction::SkipEqRegByte { .. }
+            | Instruction::SkipNeRegByte { .. }
+            | Instruction::SkipEqRegReg { .. }
+            | Instruction::SkipNeRegReg { .. }
+            | Instruction::SkipKeyPressed { .. }
+            | Instruction::SkipKeyNotPressed { .. } => {
+                pending.push(next);
+                // Skips jump over a whole instruction, which is four bytes for `F000 nnnn`.
+                if let Some(skipped) = decode(rom, start, next) {
+                    pending.push(next.wrapping_add(skipped.size()));
+                }
+            }
+            _ => pending.push(next),
+        }
+    }
+    code
+}
+
+fn label(symbols: &Symbols, address: u16) -> String {
+    match symbols.name(address) {
+        Some(name) => name.to_owned(),
+        None => format!("L{:04X}", address),
+    }
+}
+
+/// Explains common idioms, and names the targets of jumps and calls.
+fn comment(instruction: Instruction, address: u16, symbols: &Symbols) -> Option<String> {
+    let comment = match instruction {
+        Instruction::JumpAddr(target) if target == address => "halt".to_owned(),
+        Instruction::JumpAddr(target) | Instruction::CallAddr(target) => label(symbols, target),
+        Instruction::LoadIAddr(target) | Instruction::LoadILong(target) => {
+            symbols.name(target)?.to_owned()
+        }
+        Instruction::LoadFont { x } => format!("I = small font digit V{:X}", x),
+        Instruction::LoadBigFont { x } => format!("I = large font digit V{:X}", x),
+        Instruction::StoreBcd { x } => format!("store the decimal digits of V{:X} at I", x),
+        Instruction::StoreRegs { x } => format!("store V0-V{:X} at I", x),
+        Instruction::LoadRegs { x } => format!("load V0-V{:X} from I", x),
+        Instruction::WaitKey { x } => format!("wait for a key, into V{:X}", x),
+        Instruction::RandomRegByte { x, kk } => format!("V{:X} = random & {:#04X}", x, kk),
+        Instruction::Draw { n: 0, .. } => "16x16 sprite".to_owned(),
+        Instruction::Draw { n, .. } => format!("8x{} sprite", n),
+        Instruction::LoadDelayReg { x } => format!("delay timer = V{:X}", x),
+        Instruction::LoadSoundReg { x } => format!("beep for V{:X} frames", x),
+        _ => return None,
+    };
+    Some(comment)
+}
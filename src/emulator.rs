@@ -0,0 +1,432 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use log::{error, info};
+
+use crate::{
+    audio::Buzzer,
+    chip::{Chip8, FLAG_COUNT},
+    core::error::{ExecError, ExecResult},
+    debugger::{BreakReason, Debugger, RunTarget},
+    error::AppResult,
+    flags::FlagStore,
+    history::{History, DEFAULT_HISTORY_FRAMES},
+    keypad::Keypad,
+    movie::{Movie, Playback},
+    profiler::Profiler,
+    rewind::{Rewind, DEFAULT_REWIND_FRAMES},
+    screen::Screen,
+    state::MachineState,
+};
+
+pub const DEFAULT_INSTRUCTIONS_PER_FRAME: usize = 15;
+pub const MAX_INSTRUCTIONS_PER_FRAME: usize = 1000;
+pub const FRAME_RATE: u32 = 60;
+pub const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / FRAME_RATE as u64);
+/// Upper bound on frames caught up in one go after a stall, to avoid a spiral
+/// where catching up takes longer than the time it is catching up on.
+const MAX_CATCH_UP_FRAMES: u32 = 5;
+
+/// Called after every emulated frame with the resulting screen.
+pub type FrameHook = Box<dyn FnMut(&Screen) + Send>;
+
+/// Machine of the frontend, which sounds through a [`Buzzer`].
+pub type Machine = Chip8<Screen, Keypad, Buzzer>;
+
+/// Machine together with the execution controls of the frontend.
+pub struct Emulator {
+    pub chip: Machine,
+    paused: bool,
+    pub instructions_per_frame: usize,
+    /// Error that stopped execution, until the frontend takes it.
+    pub error: Option<ExecError>,
+    pub debugger: Debugger,
+    /// Why the debugger paused execution, until the frontend takes it.
+    pub break_reason: Option<BreakReason>,
+    /// Recently executed instructions, for [`Emulator::step_back`].
+    pub history: History,
+    /// Recent frames, for rewinding with [`Emulator::set_rewinding`].
+    pub rewind: Rewind,
+    /// Frames go backwards through `rewind` instead of running.
+    rewinding: bool,
+    /// Counts executed instructions while set, at some cost in speed.
+    pub profiler: Option<Profiler>,
+    /// Input movie being recorded, which every frame adds its keys to.
+    pub recording: Option<Movie>,
+    /// Input movie being played back, which holds the keys of every frame until it ends.
+    pub playback: Option<Playback>,
+    /// Lets the instruction a breakpoint stopped at run when resuming.
+    skip_break: bool,
+    frame_hook: Option<FrameHook>,
+    /// Where the flag registers are persisted, if anywhere.
+    flag_store: Option<FlagStore>,
+    /// Flag registers as last saved to `flag_store`.
+    saved_flags: [u8; FLAG_COUNT],
+    /// Keeps the buzzer silent, without affecting the sound timer.
+    muted: bool,
+    /// ROM loaded by [`Emulator::load_rom`], loaded again by [`Emulator::reset`].
+    rom: Vec<u8>,
+}
+
+impl Emulator {
+    pub fn new(chip: Machine) -> Self {
+        Self {
+            chip,
+            paused: false,
+            instructions_per_frame: DEFAULT_INSTRUCTIONS_PER_FRAME,
+            error: None,
+            debugger: Debugger::new(),
+            break_reason: None,
+            history: History::new(DEFAULT_HISTORY_FRAMES),
+            rewind: Rewind::new(DEFAULT_REWIND_FRAMES),
+            rewinding: false,
+            profiler: None,
+            recording: None,
+            playback: None,
+            skip_break: false,
+            frame_hook: None,
+            flag_store: None,
+            saved_flags: [0; FLAG_COUNT],
+            muted: false,
+            rom: Vec::new(),
+        }
+    }
+
+    /// Loads the flag registers from `store` and saves them back there
+    /// whenever a frame changes them.
+    pub fn persist_flags(&mut self, store: FlagStore) -> AppResult<()> {
+        if let Some(flags) = store.load()? {
+            self.chip.flags = flags;
+        }
+        self.saved_flags = self.chip.flags;
+        self.flag_store = Some(store);
+        Ok(())
+    }
+
+    /// Writes the flag registers to the flag store, if they changed since the last save.
+    pub fn save_flags(&mut self) -> AppResult<()> {
+        if let Some(store) = &self.flag_store {
+            if self.chip.flags != self.saved_flags {
+                store.save(&self.chip.flags)?;
+                self.saved_flags = self.chip.flags;
+            }
+        }
+        Ok(())
+    }
+
+    /// Registers a callback invoked after every frame, replacing any previous
+    /// one.
+    pub fn on_frame(&mut self, hook: impl FnMut(&Screen) + Send + 'static) {
+        self.frame_hook = Some(Box::new(hook));
+    }
+
+    /// Silences the buzzer, or lets it sound again, from the next frame.
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+    }
+
+    pub fn clear_hooks(&mut self) {
+        self.frame_hook = None;
+    }
+
+    /// Sets the number of instructions executed per frame, clamped to `1..=MAX_INSTRUCTIONS_PER_FRAME`.
+    pub fn set_instructions_per_frame(&mut self, instructions_per_frame: usize) {
+        self.instructions_per_frame = instructions_per_frame.clamp(1, MAX_INSTRUCTIONS_PER_FRAME);
+    }
+
+    /// Loads `rom` into the machine, keeping a copy to restart it from.
+    pub fn load_rom(&mut self, rom: &[u8]) -> ExecResult<()> {
+        self.chip.load_rom(rom)?;
+        self.rom = rom.to_vec();
+        Ok(())
+    }
+
+    /// Restarts the loaded ROM from the beginning. Clears the history and
+    /// the rewind buffer, which lead back into the run before.
+    pub fn reset(&mut self) {
+        self.chip.reset();
+        self.chip
+            .load_rom(&self.rom)
+            .expect("the ROM fit in memory when first loaded");
+        self.error = None;
+        self.history.clear();
+        self.rewind.clear();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Stops [`Emulator::run_frame`] from advancing the machine.
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.skip_break = true;
+    }
+
+    /// Resumes until `target` is reached, see [`Debugger::run_to`].
+    pub fn run_to(&mut self, target: RunTarget) {
+        self.debugger.run_to(target);
+        self.resume();
+    }
+
+    pub fn toggle_pause(&mut self) {
+        if self.paused {
+            self.resume();
+        } else {
+            self.pause();
+        }
+    }
+
+    /// While set, [`Emulator::run_frame`] goes back a frame instead of running
+    /// one, even while paused.
+    pub fn set_rewinding(&mut self, rewinding: bool) {
+        self.rewinding = rewinding;
+    }
+
+    pub fn is_rewinding(&self) -> bool {
+        self.rewinding
+    }
+
+    /// Runs a single 60 Hz frame, unless paused, or rewinds one.
+    pub fn run_frame(&mut self) -> ExecResult<()> {
+        let result = if self.rewinding {
+            self.rewind_frame();
+            Ok(())
+        } else if self.paused {
+            Ok(())
+        } else {
+            self.step_frame()
+        };
+        self.update_buzzer();
+        result
+    }
+
+    /// Silences the buzzer while the machine stands still or is muted. The
+    /// machine itself turns it on and off with the sound timer.
+    fn update_buzzer(&mut self) {
+        let audible = !self.paused && !self.rewinding && !self.muted;
+        self.chip.audio.set_muted(!audible);
+    }
+
+    /// Executes exactly one instruction, even while paused. Timers are not ticked.
+    pub fn step_instruction(&mut self) -> ExecResult<()> {
+        self.skip_break = true;
+        self.history.checkpoint(&self.chip);
+        self.step().map(|_| ())
+    }
+
+    /// Undoes the last executed instruction, if it is still in the history.
+    /// Returns whether there was one.
+    pub fn step_back(&mut self) -> AppResult<bool> {
+        self.error = None;
+        self.break_reason = None;
+        self.history.step_back(&mut self.chip)
+    }
+
+    /// Restores the machine to a save state, which must be of the same
+    /// variant, and switches to its quirks. Clears the history and the rewind
+    /// buffer, like a reset.
+    pub fn load_state(&mut self, state: &MachineState) -> AppResult<()> {
+        let quirks = self.chip.quirks;
+        self.chip.restore(state)?;
+        if self.chip.quirks != quirks {
+            info!("Switched to the quirks of the save state");
+        }
+        self.error = None;
+        self.break_reason = None;
+        self.history.clear();
+        self.rewind.clear();
+        Ok(())
+    }
+
+    /// Goes back to the start of the last frame in the rewind buffer. Clears
+    /// the history, which only covers the frames being undone.
+    fn rewind_frame(&mut self) {
+        match self.rewind.rewind(&mut self.chip) {
+            Ok(true) => {
+                self.error = None;
+                self.break_reason = None;
+                self.history.clear();
+            }
+            Ok(false) => {}
+            Err(err) => error!("Could not rewind: {}", err),
+        }
+    }
+
+    /// Writes `bytes` to memory starting at `address`. Clears the history,
+    /// since stepping back through it would undo the change.
+    pub fn poke(&mut self, address: usize, bytes: &[u8]) -> ExecResult<()> {
+        for (offset, &byte) in bytes.iter().enumerate() {
+            self.chip.write_memory(address + offset, byte)?;
+        }
+        self.history.clear();
+        Ok(())
+    }
+
+    /// Executes exactly one 60 Hz frame, even while paused.
+    ///
+    /// A debugger break ends the frame early, without ticking the timers.
+    pub fn step_frame(&mut self) -> ExecResult<()> {
+        if self.paused {
+            self.skip_break = true;
+        }
+        if let Some(playback) = &mut self.playback {
+            match playback.next_frame() {
+                Some(keys) => self.chip.keypad.set_mask(keys),
+                None => {
+                    info!("Input movie ended");
+                    self.playback = None;
+                    self.chip.keypad.set_mask(0);
+                }
+            }
+        }
+        if let Some(movie) = &mut self.recording {
+            movie.frames.push(self.chip.keypad.mask());
+        }
+        self.history.checkpoint(&self.chip);
+        self.rewind.push(&self.chip);
+        if self.debugger.is_active() || self.history.is_enabled() || self.profiler.is_some() {
+            for _ in 0..self.instructions_per_frame {
+                if self.step()? {
+                    return Ok(());
+                }
+            }
+            self.chip.tick_timers();
+        } else {
+            self.chip.run_frame(self.instructions_per_frame)?;
+        }
+        if let Some(hook) = &mut self.frame_hook {
+            hook(&self.chip.display);
+        }
+
+        if let Err(err) = self.save_flags() {
+            error!("Could not save flags: {}", err);
+        }
+        Ok(())
+    }
+
+    /// Executes the next instruction, unless the debugger stops before it.
+    /// Returns whether the debugger stopped before it.
+    fn step(&mut self) -> ExecResult<bool> {
+        if self.chip.exited || self.chip.waiting_for_vblank || !self.chip.poll_key_wait() {
+            return Ok(false);
+        }
+        if !std::mem::take(&mut self.skip_break) {
+            if let Some(reason) = self.debugger.check(&self.chip) {
+                self.pause();
+                self.break_reason = Some(reason);
+                return Ok(true);
+            }
+        }
+        let pc = self.chip.pc;
+        let executed = self.chip.execute_next()?;
+        self.history.record();
+        if let (Some(profiler), Some(instruction)) = (&mut self.profiler, executed) {
+            profiler.record(pc, instruction);
+        }
+        Ok(false)
+    }
+}
+
+/// Fixed-timestep clock producing exactly [`FRAME_RATE`] frames per second of
+/// wall-clock time, regardless of display refresh rate or scheduling jitter.
+pub struct FrameClock {
+    previous: Instant,
+    accumulator: Duration,
+}
+
+impl FrameClock {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            previous: now,
+            accumulator: Duration::ZERO,
+        }
+    }
+
+    /// Advances the clock to `now` and returns how many frames are due.
+    pub fn advance(&mut self, now: Instant) -> u32 {
+        self.accumulator += now.saturating_duration_since(self.previous);
+        self.previous = now;
+
+        let mut frames = 0;
+        while self.accumulator >= FRAME_DURATION {
+            self.accumulator -= FRAME_DURATION;
+            frames += 1;
+        }
+        if frames > MAX_CATCH_UP_FRAMES {
+            frames = MAX_CATCH_UP_FRAMES;
+            self.accumulator = Duration::ZERO;
+        }
+        frames
+    }
+
+    /// Time remaining until the next frame is due.
+    pub fn until_next_frame(&self) -> Duration {
+        FRAME_DURATION.saturating_sub(self.accumulator)
+    }
+}
+
+/// Background thread driving an [`Emulator`] at 60 frames per second,
+/// independently of how often the window is redrawn.
+pub struct EmulationThread {
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl EmulationThread {
+    pub fn spawn(emulator: Arc<Mutex<Emulator>>) -> Self {
+        let running = Arc::new(AtomicBool::new(true));
+        let handle = thread::Builder::new()
+            .name("emulation".into())
+            .spawn({
+                let running = Arc::clone(&running);
+                move || {
+                    let mut clock = FrameClock::new(Instant::now());
+                    while running.load(Ordering::Relaxed) {
+                        let frames = clock.advance(Instant::now());
+                        if frames > 0 {
+                            let mut emulator = emulator.lock().unwrap();
+                            for _ in 0..frames {
+                                if let Err(err) = emulator.run_frame() {
+                                    error!("Execution stopped: {}", err);
+                                    emulator.pause();
+                                    emulator.error = Some(err);
+                                    break;
+                                }
+                            }
+                        }
+                        thread::sleep(clock.until_next_frame());
+                    }
+                }
+            })
+            .expect("Failed to spawn emulation thread");
+
+        Self {
+            running,
+            handle: Some(handle),
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for EmulationThread {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
@@ -1,11 +1,25 @@
-use std::error::Error;
 use thiserror::Error;
 use winit::error::EventLoopError;
 
+use crate::core::error::ExecError;
+
 #[derive(Error, Debug)]
 pub enum AppError {
     #[error("internal error: {0}")]
     Internal(#[from] Box<dyn std::error::Error + Send + Sync>),
+    #[error("invalid machine state: {0}")]
+    InvalidState(&'static str),
+    /// A save state of a different variant than the machine it is loaded into.
+    #[error("incompatible save state: {0}")]
+    IncompatibleState(String),
+    #[error("invalid argument: {0}")]
+    InvalidArgument(String),
+    #[error("unknown opcode {opcode:#06X} at {pc:#05X}")]
+    UnknownOpcode { opcode: u16, pc: u16 },
+    #[error("assembly error on line {line}: {message}")]
+    Assembly { line: usize, message: String },
+    #[error("execution error: {0}")]
+    Execution(ExecError),
 }
 
 pub type AppResult<T> = Result<T, AppError>;
@@ -22,4 +36,21 @@ macro_rules! impl_internal_errors {
     };
 }
 
-impl_internal_errors!(EventLoopError, std::io::Error);
+impl From<ExecError> for AppError {
+    fn from(err: ExecError) -> Self {
+        match err {
+            ExecError::UnknownOpcode { opcode, pc } => AppError::UnknownOpcode { opcode, pc },
+            err => AppError::Execution(err),
+        }
+    }
+}
+
+impl_internal_errors!(
+    EventLoopError,
+    std::io::Error,
+    png::EncodingError,
+    png::DecodingError,
+    rodio::cpal::DefaultStreamConfigError,
+    rodio::cpal::BuildStreamError,
+    rodio::cpal::PlayStreamError,
+);
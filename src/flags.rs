@@ -0,0 +1,83 @@
+//! Persistence of the SUPER-CHIP flag registers (`Fx75`/`Fx85`).
+//!
+//! On the HP-48 these were the calculator's RPL user flags, which survive
+//! between runs, so games use them to keep high scores. They are stored per
+//! ROM: in a file next to it natively, and in `localStorage` on the web.
+
+use std::path::{Path, PathBuf};
+
+use crate::{chip::FLAG_COUNT, error::AppResult};
+
+/// Where the flag registers of one ROM are kept.
+pub struct FlagStore {
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    path: PathBuf,
+    #[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+    key: String,
+}
+
+impl FlagStore {
+    /// Store for the ROM at `rom_path` with contents `rom`.
+    ///
+    /// Natively the flags go to `rom_path` with a `.flags` extension. The web
+    /// build has no file system and keys them by the ROM's SHA-1 instead.
+    pub fn for_rom(rom_path: &Path, rom: &[u8]) -> Self {
+        Self {
+            path: rom_path.with_extension("flags"),
+            key: format!("rusty-chip8.flags.{}", sha1_smol::Sha1::from(rom).digest()),
+        }
+    }
+
+    /// Reads the stored flags, or `None` if nothing has been saved yet.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(&self) -> AppResult<Option<[u8; FLAG_COUNT]>> {
+        match std::fs::read(&self.path) {
+            Ok(bytes) => Ok(Some(from_bytes(&bytes))),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save(&self, flags: &[u8; FLAG_COUNT]) -> AppResult<()> {
+        std::fs::write(&self.path, flags)?;
+        Ok(())
+    }
+
+    /// Reads the stored flags, or `None` if nothing has been saved yet.
+    #[cfg(target_arch = "wasm32")]
+    pub fn load(&self) -> AppResult<Option<[u8; FLAG_COUNT]>> {
+        let value = local_storage().and_then(|storage| storage.get_item(&self.key).ok().flatten());
+        Ok(value.map(|hex| {
+            let bytes = (0..hex.len() / 2)
+                .filter_map(|i| u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok())
+                .collect::<Vec<u8>>();
+            from_bytes(&bytes)
+        }))
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn save(&self, flags: &[u8; FLAG_COUNT]) -> AppResult<()> {
+        let hex = flags
+            .iter()
+            .map(|flag| format!("{:02x}", flag))
+            .collect::<String>();
+        if let Some(storage) = local_storage() {
+            let _ = storage.set_item(&self.key, &hex);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok().flatten()
+}
+
+/// Flags from stored bytes, tolerating files of another length.
+fn from_bytes(bytes: &[u8]) -> [u8; FLAG_COUNT] {
+    let mut flags = [0; FLAG_COUNT];
+    let len = bytes.len().min(FLAG_COUNT);
+    flags[..len].copy_from_slice(&bytes[..len]);
+    flags
+}
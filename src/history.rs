@@ -0,0 +1,121 @@
+//! Recently executed instructions, for stepping backwards while paused.
+
+use std::collections::VecDeque;
+
+use crate::{
+    bus::AudioBus, chip::Chip8, error::AppResult, keypad::Keypad, rewind::Delta, screen::Screen,
+    state::MachineState,
+};
+
+/// Frames kept by default, ten seconds of execution.
+pub const DEFAULT_HISTORY_FRAMES: usize = 600;
+
+/// Machine state at the start of a frame, and the number of instructions
+/// executed from it since.
+#[derive(Debug, Clone)]
+struct Checkpoint {
+    state: MachineState,
+    executed: usize,
+}
+
+/// Ring buffer of the last frames executed.
+///
+/// Only one snapshot is kept per frame. Any instruction within it is
+/// reconstructed by restoring the snapshot and executing the instructions
+/// before it again, which is deterministic since the keypad and random number
+/// generator are part of the snapshot and input only changes between frames.
+/// Instructions stepped one at a time while paused take up a frame each.
+///
+/// Like [`Rewind`](crate::rewind::Rewind), only the newest snapshot is kept
+/// whole and the older ones as the [`Delta`] from the frame after them.
+#[derive(Debug, Clone)]
+pub struct History {
+    latest: Option<Checkpoint>,
+    /// Older frames, oldest first, with the instructions executed in each.
+    older: VecDeque<(Delta, usize)>,
+    capacity: usize,
+}
+
+impl History {
+    /// Keeps up to `frames` frames, or nothing if it is 0.
+    pub fn new(frames: usize) -> Self {
+        Self {
+            latest: None,
+            older: VecDeque::new(),
+            capacity: frames,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    /// Number of instructions that can be stepped back.
+    pub fn len(&self) -> usize {
+        self.latest.as_ref().map_or(0, |latest| latest.executed)
+            + self
+                .older
+                .iter()
+                .map(|(_, executed)| executed)
+                .sum::<usize>()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.latest = None;
+        self.older.clear();
+    }
+
+    /// Starts a new frame from the current state of `chip`, dropping the
+    /// oldest one if the buffer is full. Does nothing if disabled.
+    pub fn checkpoint<A: AudioBus>(&mut self, chip: &Chip8<Screen, Keypad, A>) {
+        if !self.is_enabled() {
+            return;
+        }
+        let state = chip.snapshot();
+        if let Some(previous) = self.latest.take() {
+            self.older
+                .push_back((Delta::new(previous.state, &state), previous.executed));
+            if self.older.len() == self.capacity {
+                self.older.pop_front();
+            }
+        }
+        self.latest = Some(Checkpoint { state, executed: 0 });
+    }
+
+    /// Counts an instruction executed successfully in the current frame.
+    pub fn record(&mut self) {
+        if let Some(checkpoint) = &mut self.latest {
+            checkpoint.executed += 1;
+        }
+    }
+
+    /// Returns `chip` to the state before the last recorded instruction.
+    /// Returns `false` if there is none.
+    ///
+    /// Timers are only ticked between frames, so stepping back past the start
+    /// of a frame also undoes its tick.
+    pub fn step_back<A: AudioBus>(
+        &mut self,
+        chip: &mut Chip8<Screen, Keypad, A>,
+    ) -> AppResult<bool> {
+        while let Some(mut checkpoint) = self.latest.take() {
+            if checkpoint.executed == 0 {
+                self.latest = self.older.pop_back().map(|(delta, executed)| Checkpoint {
+                    state: delta.undo(checkpoint.state),
+                    executed,
+                });
+                continue;
+            }
+            checkpoint.executed -= 1;
+            let checkpoint = self.latest.insert(checkpoint);
+            chip.restore(&checkpoint.state)?;
+            chip.run_for(checkpoint.executed)?;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+}
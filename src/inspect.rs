@@ -0,0 +1,207 @@
+//! Checks of ROM files for problems that keep them from running.
+
+use std::fmt;
+
+use crate::{config::DEFAULT_MEMORY_SIZE, disasm, instruction::Instruction, platform::Platform};
+
+/// How bad a problem found by [`inspect`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// Likely a bug, or the ROM targets an unusual interpreter.
+    Warning,
+    /// The ROM can't run at all.
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct Issue {
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// What [`inspect`] found out about a ROM.
+#[derive(Debug, Clone)]
+pub struct RomReport {
+    pub start: u16,
+    pub size: usize,
+    /// Lowercase hex SHA-1 of the ROM file.
+    pub sha1: String,
+    /// Platform guessed from the instructions.
+    pub platform: Option<Platform>,
+    /// Reachable instructions only some variants have, as opcode patterns
+    /// grouped by the variant that introduced them.
+    pub variant_instructions: Vec<(Platform, Vec<&'static str>)>,
+    pub issues: Vec<Issue>,
+}
+
+impl RomReport {
+    /// Whether any issue keeps the ROM from running.
+    pub fn is_fatal(&self) -> bool {
+        self.issues
+            .iter()
+            .any(|issue| issue.severity == Severity::Error)
+    }
+}
+
+impl fmt::Display for RomReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Size:      {} bytes", self.size)?;
+        writeln!(f, "SHA-1:     {}", self.sha1)?;
+        writeln!(
+            f,
+            "Platform:  {}",
+            self.platform.map_or("chip-8", Platform::name)
+        )?;
+        writeln!(
+            f,
+            "Memory:    {:#06X}-{:#06X}",
+            self.start,
+            self.start as usize + self.size
+        )?;
+        for (platform, patterns) in &self.variant_instructions {
+            writeln!(f, "{:<10} {}", format!("{}:", platform), patterns.join(" "))?;
+        }
+        for issue in &self.issues {
+            let label = match issue.severity {
+                Severity::Warning => "warning",
+                Severity::Error => "error",
+            };
+            writeln!(f, "{}: {}", label, issue.message)?;
+        }
+        Ok(())
+    }
+}
+
+/// Variant that introduced `instruction`, or `None` for plain CHIP-8.
+fn variant(instruction: Instruction) -> Option<Platform> {
+    match instruction {
+        Instruction::ScrollDown { .. }
+        | Instruction::ScrollRight
+        | Instruction::ScrollLeft
+        | Instruction::Exit
+        | Instruction::LowRes
+        | Instruction::HighRes
+        | Instruction::Draw { n: 0, .. }
+        | Instruction::LoadBigFont { .. }
+        | Instruction::StoreFlags { .. }
+        | Instruction::LoadFlags { .. } => Some(Platform::Schip),
+        Instruction::ScrollUp { .. }
+        | Instruction::StoreRange { .. }
+        | Instruction::LoadRange { .. }
+        | Instruction::LoadILong(_)
+        | Instruction::SelectPlanes { .. }
+        | Instruction::LoadAudio
+        | Instruction::LoadPitch { .. } => Some(Platform::XoChip),
+        _ => None,
+    }
+}
+
+/// Instructions reachable from `start` in `rom` loaded there, with their
+/// addresses in order, as found by [`disasm::trace`].
+fn code(rom: &[u8], start: u16) -> Vec<(u16, Instruction)> {
+    let mut addresses = disasm::trace(rom, start).into_iter().collect::<Vec<_>>();
+    addresses.sort_unstable();
+    addresses
+        .into_iter()
+        .filter_map(|address| {
+            Instruction::decode_memory(rom, (address - start) as usize)
+                .map(|instruction| (address, instruction))
+        })
+        .collect()
+}
+
+/// Patterns of the instructions of each variant that `code` uses, ordered
+/// from the oldest variant to the newest.
+fn variant_instructions(code: &[(u16, Instruction)]) -> Vec<(Platform, Vec<&'static str>)> {
+    let mut variant_instructions: Vec<(Platform, Vec<&'static str>)> = Vec::new();
+    for &(_, instruction) in code {
+        let Some(platform) = variant(instruction) else {
+            continue;
+        };
+        match variant_instructions
+            .iter_mut()
+            .find(|(p, _)| *p == platform)
+        {
+            Some((_, patterns)) if patterns.contains(&instruction.pattern()) => {}
+            Some((_, patterns)) => patterns.push(instruction.pattern()),
+            None => variant_instructions.push((platform, vec![instruction.pattern()])),
+        }
+    }
+    variant_instructions.sort_by_key(|(platform, _)| *platform as u8);
+    variant_instructions
+}
+
+/// Guesses the platform of `rom` loaded at `start` from the SUPER-CHIP or
+/// XO-CHIP instructions its code uses, returning `None` if it looks like plain
+/// CHIP-8. Only code reachable from `start` counts, so sprite data that
+/// happens to look like such instructions doesn't, as `info` reports it.
+pub fn detect_platform(rom: &[u8], start: u16) -> Option<Platform> {
+    variant_instructions(&code(rom, start))
+        .last()
+        .map(|(platform, _)| *platform)
+}
+
+/// Inspects `rom` loaded at `start`, following its code like the
+/// disassembler does to find the instructions it uses.
+pub fn inspect(rom: &[u8], start: u16) -> RomReport {
+    let mut issues = Vec::new();
+    let mut issue = |severity, message: String| issues.push(Issue { severity, message });
+
+    let code = code(rom, start);
+    let variant_instructions = variant_instructions(&code);
+    let end = start as usize + rom.len();
+    for &(address, instruction) in &code {
+        match instruction {
+            Instruction::JumpAddr(target) | Instruction::CallAddr(target)
+                if target < start || target as usize >= end =>
+            {
+                issue(
+                    Severity::Warning,
+                    format!("{} at {:#05X} leaves the program", instruction, address),
+                );
+            }
+            Instruction::SysAddr(target) => issue(
+                Severity::Warning,
+                format!(
+                    "machine code call to {:#05X} at {:#05X} is ignored",
+                    target, address
+                ),
+            ),
+            _ => {}
+        }
+    }
+    let platform = variant_instructions.last().map(|(platform, _)| *platform);
+
+    if rom.is_empty() {
+        issue(Severity::Error, "the ROM is empty".to_owned());
+    }
+    if !rom.len().is_multiple_of(2) {
+        issue(
+            Severity::Warning,
+            "odd length, the ROM may be truncated".to_owned(),
+        );
+    }
+    let memory_size = platform.map_or(DEFAULT_MEMORY_SIZE, |platform| {
+        platform.config().memory_size
+    });
+    if end > memory_size {
+        issue(
+            Severity::Error,
+            format!(
+                "doesn't fit in {} KB of memory from {:#05X}, {} bytes too long",
+                memory_size / 1024,
+                start,
+                end - memory_size
+            ),
+        );
+    }
+
+    RomReport {
+        start,
+        size: rom.len(),
+        sha1: sha1_smol::Sha1::from(rom).digest().to_string(),
+        platform,
+        variant_instructions,
+        issues,
+    }
+}
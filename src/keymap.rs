@@ -0,0 +1,362 @@
+//! Which physical keys press the 16 CHIP-8 keys.
+
+use std::{collections::HashMap, str::FromStr};
+
+use winit::keyboard::KeyCode;
+
+use crate::keypad::KEY_COUNT;
+
+/// Keys that can be bound, named as in the W3C `KeyboardEvent.code` values,
+/// like their variants.
+const BINDABLE_KEYS: [KeyCode; 72] = [
+    KeyCode::KeyA,
+    KeyCode::KeyB,
+    KeyCode::KeyC,
+    KeyCode::KeyD,
+    KeyCode::KeyE,
+    KeyCode::KeyF,
+    KeyCode::KeyG,
+    KeyCode::KeyH,
+    KeyCode::KeyI,
+    KeyCode::KeyJ,
+    KeyCode::KeyK,
+    KeyCode::KeyL,
+    KeyCode::KeyM,
+    KeyCode::KeyN,
+    KeyCode::KeyO,
+    KeyCode::KeyP,
+    KeyCode::KeyQ,
+    KeyCode::KeyR,
+    KeyCode::KeyS,
+    KeyCode::KeyT,
+    KeyCode::KeyU,
+    KeyCode::KeyV,
+    KeyCode::KeyW,
+    KeyCode::KeyX,
+    KeyCode::KeyY,
+    KeyCode::KeyZ,
+    KeyCode::Digit0,
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+    KeyCode::Numpad0,
+    KeyCode::Numpad1,
+    KeyCode::Numpad2,
+    KeyCode::Numpad3,
+    KeyCode::Numpad4,
+    KeyCode::Numpad5,
+    KeyCode::Numpad6,
+    KeyCode::Numpad7,
+    KeyCode::Numpad8,
+    KeyCode::Numpad9,
+    KeyCode::NumpadDecimal,
+    KeyCode::NumpadDivide,
+    KeyCode::NumpadMultiply,
+    KeyCode::NumpadEnter,
+    KeyCode::ArrowUp,
+    KeyCode::ArrowDown,
+    KeyCode::ArrowLeft,
+    KeyCode::ArrowRight,
+    KeyCode::Backquote,
+    KeyCode::Backslash,
+    KeyCode::BracketLeft,
+    KeyCode::BracketRight,
+    KeyCode::Comma,
+    KeyCode::Period,
+    KeyCode::Quote,
+    KeyCode::Semicolon,
+    KeyCode::Slash,
+    KeyCode::IntlBackslash,
+    KeyCode::Tab,
+    KeyCode::ShiftLeft,
+    KeyCode::ShiftRight,
+    KeyCode::ControlLeft,
+    KeyCode::ControlRight,
+    KeyCode::Insert,
+    KeyCode::Delete,
+    KeyCode::Home,
+];
+
+/// Physical keys of the letter rows, from the top.
+const LETTER_ROWS: [&[KeyCode]; 3] = [
+    &[
+        KeyCode::KeyQ,
+        KeyCode::KeyW,
+        KeyCode::KeyE,
+        KeyCode::KeyR,
+        KeyCode::KeyT,
+        KeyCode::KeyY,
+        KeyCode::KeyU,
+        KeyCode::KeyI,
+        KeyCode::KeyO,
+        KeyCode::KeyP,
+        KeyCode::BracketLeft,
+        KeyCode::BracketRight,
+    ],
+    &[
+        KeyCode::KeyA,
+        KeyCode::KeyS,
+        KeyCode::KeyD,
+        KeyCode::KeyF,
+        KeyCode::KeyG,
+        KeyCode::KeyH,
+        KeyCode::KeyJ,
+        KeyCode::KeyK,
+        KeyCode::KeyL,
+        KeyCode::Semicolon,
+        KeyCode::Quote,
+    ],
+    &[
+        KeyCode::KeyZ,
+        KeyCode::KeyX,
+        KeyCode::KeyC,
+        KeyCode::KeyV,
+        KeyCode::KeyB,
+        KeyCode::KeyN,
+        KeyCode::KeyM,
+        KeyCode::Comma,
+        KeyCode::Period,
+        KeyCode::Slash,
+    ],
+];
+
+const DIGITS: [KeyCode; 10] = [
+    KeyCode::Digit0,
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+];
+
+/// Keyboard layout typed on, which decides the keys named by a letter.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    #[default]
+    Qwerty,
+    /// French.
+    Azerty,
+    /// German.
+    Qwertz,
+    Dvorak,
+    Colemak,
+}
+
+impl FromStr for Layout {
+    type Err = &'static str;
+
+    /// Parses `qwerty`, `azerty`, `qwertz`, `dvorak` or `colemak`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "qwerty" => Ok(Self::Qwerty),
+            "azerty" => Ok(Self::Azerty),
+            "qwertz" => Ok(Self::Qwertz),
+            "dvorak" => Ok(Self::Dvorak),
+            "colemak" => Ok(Self::Colemak),
+            _ => Err("expected one of `qwerty`, `azerty`, `qwertz`, `dvorak` or `colemak`"),
+        }
+    }
+}
+
+impl Layout {
+    /// What the keys of [`LETTER_ROWS`] are labelled with, unshifted.
+    fn labels(self) -> [&'static str; 3] {
+        match self {
+            Self::Qwerty => ["qwertyuiop[]", "asdfghjkl;'", "zxcvbnm,./"],
+            Self::Azerty => ["azertyuiop^$", "qsdfghjklmù", "wxcvbn,;:!"],
+            Self::Qwertz => ["qwertzuiopü+", "asdfghjklöä", "yxcvbnm,.-"],
+            Self::Dvorak => ["',.pyfgcrl/=", "aoeuidhtns-", ";qjkxbmwvz"],
+            Self::Colemak => ["qwfpgjluy;[]", "arstdhneio'", "zxcvbkm,./"],
+        }
+    }
+
+    /// Physical key labelled `label`, a lowercase letter, a digit or another
+    /// character of the letter rows.
+    pub fn key_code(self, label: char) -> Option<KeyCode> {
+        if let Some(digit) = label.to_digit(10) {
+            return Some(DIGITS[digit as usize]);
+        }
+        LETTER_ROWS
+            .into_iter()
+            .zip(self.labels())
+            .find_map(|(keys, labels)| {
+                let column = labels.chars().position(|c| c == label)?;
+                keys.get(column).copied()
+            })
+    }
+
+    /// Label of a physical key, its letter in uppercase, or its name if it is
+    /// neither in the letter rows nor a digit.
+    pub fn label(self, key_code: KeyCode) -> String {
+        if let Some(digit) = DIGITS.iter().position(|&code| code == key_code) {
+            return digit.to_string();
+        }
+        LETTER_ROWS
+            .into_iter()
+            .zip(self.labels())
+            .find_map(|(keys, labels)| {
+                let column = keys.iter().position(|&code| code == key_code)?;
+                labels.chars().nth(column)
+            })
+            .map_or_else(
+                || format!("{:?}", key_code),
+                |c| c.to_uppercase().to_string(),
+            )
+    }
+}
+
+/// Physical key to bind, by its name or its label.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyName {
+    Code(KeyCode),
+    /// A lowercase ASCII letter or a digit, naming the key with this label on
+    /// the layout typed on.
+    Label(char),
+}
+
+impl KeyName {
+    pub fn key_code(self, layout: Layout) -> KeyCode {
+        match self {
+            Self::Code(code) => code,
+            Self::Label(label) => layout
+                .key_code(label)
+                .expect("every layout has all letters and digits"),
+        }
+    }
+}
+
+/// Physical keys bound to the CHIP-8 keys.
+///
+/// By default the keys `0`-`F` are the left four columns of the digit and
+/// letter rows, row by row. Keys are bound by position, so these are the
+/// same keys on every layout, labelled on QWERTY:
+///
+/// ```text
+/// 1 2 3 4
+/// Q W E R
+/// A S D F
+/// Z X C V
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keymap {
+    keys: HashMap<KeyCode, u8>,
+}
+
+impl Keymap {
+    /// CHIP-8 key pressed by `key_code`, if any.
+    pub fn key(&self, key_code: KeyCode) -> Option<usize> {
+        self.keys.get(&key_code).map(|&key| key as usize)
+    }
+
+    /// Binds the physical key of every binding to its CHIP-8 key, replacing
+    /// what the CHIP-8 keys and physical keys were bound to. Labels name the
+    /// keys of `layout`.
+    pub fn remap(&mut self, bindings: &[(u8, KeyName)], layout: Layout) {
+        self.keys
+            .retain(|_, key| !bindings.iter().any(|(rebound, _)| rebound == key));
+        for &(key, name) in bindings {
+            self.keys.insert(name.key_code(layout), key);
+        }
+    }
+
+    /// Labels on `layout` of the keys bound to `0`-`F`, in rows of four like
+    /// `1 2 3 4 / A Z E R / Q S D F / W X C V`. CHIP-8 keys bound to several
+    /// keys list them joined by `|`, and unbound ones show `-`.
+    pub fn describe(&self, layout: Layout) -> String {
+        let keys: Vec<String> = (0..KEY_COUNT)
+            .map(|key| {
+                let mut labels: Vec<String> = self
+                    .keys
+                    .iter()
+                    .filter(|&(_, &bound)| bound as usize == key)
+                    .map(|(&code, _)| layout.label(code))
+                    .collect();
+                labels.sort();
+                if labels.is_empty() {
+                    "-".to_owned()
+                } else {
+                    labels.join("|")
+                }
+            })
+            .collect();
+        keys.chunks(4)
+            .map(|row| row.join(" "))
+            .collect::<Vec<_>>()
+            .join(" / ")
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        const LAYOUT: [KeyCode; KEY_COUNT] = [
+            KeyCode::Digit1,
+            KeyCode::Digit2,
+            KeyCode::Digit3,
+            KeyCode::Digit4,
+            KeyCode::KeyQ,
+            KeyCode::KeyW,
+            KeyCode::KeyE,
+            KeyCode::KeyR,
+            KeyCode::KeyA,
+            KeyCode::KeyS,
+            KeyCode::KeyD,
+            KeyCode::KeyF,
+            KeyCode::KeyZ,
+            KeyCode::KeyX,
+            KeyCode::KeyC,
+            KeyCode::KeyV,
+        ];
+        Self {
+            keys: (0..).zip(LAYOUT).map(|(key, code)| (code, key)).collect(),
+        }
+    }
+}
+
+/// Parses a physical key, named like `KeyQ`, `Digit1`, `Numpad5` or
+/// `ArrowUp`, in any case, or labelled with a single letter or digit.
+pub fn parse_key_name(s: &str) -> Result<KeyName, String> {
+    if let [c] = s.as_bytes() {
+        if c.is_ascii_alphanumeric() {
+            return Ok(KeyName::Label(c.to_ascii_lowercase() as char));
+        }
+    }
+    BINDABLE_KEYS
+        .into_iter()
+        .find(|code| format!("{:?}", code).eq_ignore_ascii_case(s))
+        .map(KeyName::Code)
+        .ok_or_else(|| {
+            format!(
+                "unknown key `{}`, expected a name like `KeyQ`, `Digit1` or `ArrowUp`",
+                s
+            )
+        })
+}
+
+/// Parses a binding like `5=KeyI`, of a CHIP-8 key `0`-`F` to a physical key.
+pub fn parse_binding(s: &str) -> Result<(u8, KeyName), String> {
+    let (key, key_code) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected `KEY=PHYSICAL_KEY`, got `{}`", s))?;
+    Ok((parse_key(key.trim())?, parse_key_name(key_code.trim())?))
+}
+
+/// Parses a CHIP-8 key, a hexadecimal digit.
+pub fn parse_key(s: &str) -> Result<u8, String> {
+    match u8::from_str_radix(s, 16) {
+        Ok(key) if s.len() == 1 => Ok(key),
+        _ => Err(format!(
+            "expected a CHIP-8 key from `0` to `F`, got `{}`",
+            s
+        )),
+    }
+}
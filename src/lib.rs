@@ -1,6 +1,84 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+pub mod core;
+
+pub use crate::core::{
+    bus, chip, config, instruction, keypad, megachip, platform, quirks, rng, screen,
+};
+
+pub use crate::core::error::{ExecError, ExecResult};
+
+#[cfg(feature = "std")]
+pub mod ascii;
+#[cfg(feature = "std")]
+pub mod assembler;
+#[cfg(feature = "std")]
+pub mod audio;
+#[cfg(feature = "std")]
+pub mod bench;
+#[cfg(feature = "std")]
+pub mod bezel;
+#[cfg(feature = "std")]
 pub mod camera;
+#[cfg(feature = "std")]
+pub mod check;
+#[cfg(feature = "std")]
+pub mod coverage;
+#[cfg(feature = "std")]
+pub mod debugger;
+#[cfg(feature = "std")]
+pub mod diff;
+#[cfg(feature = "std")]
+pub mod disasm;
+#[cfg(feature = "std")]
+pub mod emulator;
+#[cfg(feature = "std")]
 pub mod error;
+#[cfg(feature = "std")]
+pub mod flags;
+#[cfg(feature = "std")]
+pub mod history;
+#[cfg(feature = "std")]
+pub mod inspect;
+#[cfg(feature = "std")]
+pub mod keymap;
+#[cfg(feature = "std")]
 pub mod mesh;
+#[cfg(feature = "std")]
+pub mod movie;
+#[cfg(feature = "std")]
+pub mod offscreen;
+#[cfg(feature = "std")]
+pub mod overlay;
+#[cfg(feature = "std")]
+pub mod palette;
+#[cfg(feature = "std")]
+pub mod pbm;
+#[cfg(feature = "std")]
+pub mod postprocess;
+#[cfg(feature = "std")]
+pub mod profiler;
+#[cfg(all(feature = "remote", not(target_arch = "wasm32")))]
+pub mod remote;
+#[cfg(feature = "std")]
 pub mod renderer;
-pub mod screen;
+#[cfg(feature = "std")]
+pub mod repl;
+#[cfg(feature = "std")]
+pub mod rewind;
+#[cfg(feature = "std")]
+pub mod romtools;
+#[cfg(feature = "std")]
+pub mod scene;
+#[cfg(feature = "std")]
+pub mod screenshot;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub mod settings;
+#[cfg(all(feature = "softbuffer", not(target_arch = "wasm32")))]
+pub mod soft;
+#[cfg(feature = "std")]
+pub mod state;
+#[cfg(feature = "std")]
+pub mod symbols;
+#[cfg(feature = "std")]
 pub mod world;
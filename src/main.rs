@@ -2,68 +2,481 @@
 //!
 //! Reference: [Cowgod's Chip-8 Technical Reference](http://devernay.free.fr/hacks/chip8/C8TECH10.HTM)
 
-use bytemuck::{Pod, Zeroable};
-use cgmath::{prelude::*, Vector2, Vector3};
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use log::{error, info, warn};
-use rodio::{source::SineWave, Source};
+#[cfg(feature = "remote")]
+use rusty_chip8::remote::RemoteServer;
 use rusty_chip8::{
-    camera::{Camera, CameraUniform},
+    ascii, assembler,
+    audio::{
+        AudioOutput, Beep, BeepSample, Buzzer, Waveform, DEFAULT_BEEP_ENVELOPE,
+        DEFAULT_BEEP_FREQUENCY,
+    },
+    bench,
+    bezel::Bezel,
+    camera::Rotation,
+    check::{self, CHECKED_PLATFORMS},
+    chip::{Chip8, MemoryMode},
+    config::{MachineConfig, DEFAULT_PROGRAM_START},
+    coverage::{self, Coverage},
+    debugger::{self, RunTarget},
+    diff, disasm,
+    emulator::{EmulationThread, Emulator, DEFAULT_INSTRUCTIONS_PER_FRAME},
     error::{AppError, AppResult},
-    renderer::Renderer,
-    screen::Screen,
+    flags::FlagStore,
+    history::{History, DEFAULT_HISTORY_FRAMES},
+    inspect,
+    keymap::{self, KeyName, Keymap, Layout},
+    keypad::Keypad,
+    movie::{Movie, Playback},
+    offscreen::OffscreenRenderer,
+    overlay::{Panel, SlotPreview},
+    palette::{self, Palette},
+    pbm,
+    platform::Platform,
+    profiler::Profiler,
+    quirks::{MemoryIncrement, Quirks, SpriteEdge},
+    renderer::{PresentMode, Renderer},
+    repl,
+    rewind::{Rewind, DEFAULT_REWIND_FRAMES},
+    romtools,
+    scene::ColorCorrection,
+    screen::{Screen, SCREEN_HEIGHT, SCREEN_WIDTH},
+    screenshot::{self, DEFAULT_SCREENSHOT_SCALE},
+    settings::Settings,
+    state::{self, MachineState, SLOT_COUNT},
+    symbols::Symbols,
     world::World,
 };
 use std::{
-    borrow::Cow,
     cell::RefCell,
     fs::File,
     io::{BufReader, Read},
+    ops::RangeInclusive,
     path::{Path, PathBuf},
     rc::Rc,
-    time::{Duration, Instant},
+    sync::{Arc, Mutex},
+    time::Duration,
 };
-use wgpu::util::DeviceExt;
 use winit::{
-    dpi::{LogicalSize, Size},
+    dpi::{LogicalSize, PhysicalSize},
     event::{Event, WindowEvent},
     event_loop::EventLoop,
-    keyboard::{KeyCode, PhysicalKey},
+    keyboard::{KeyCode, ModifiersState, PhysicalKey},
     window::Window,
 };
 
-/// Simple program to greet a person
+/// CHIP-8 emulator
 #[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
+#[command(version, about, long_about = None, subcommand_negates_reqs = true)]
 struct Args {
-    #[arg(short, long)]
-    rom_path: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[arg(short, long, required = true)]
+    rom_path: Option<String>,
+    /// Seed for the random number generator, making runs reproducible
+    #[arg(long)]
+    rng_seed: Option<u64>,
+    /// Instructions executed per 60 Hz frame, adjustable at runtime with `+` and `-` [default: 15]
+    #[arg(long, visible_alias = "speed")]
+    ipf: Option<usize>,
+    /// Stop with an error on out of bounds memory access, instead of wrapping around
+    #[arg(long)]
+    strict_memory: bool,
+    /// Emulate a CHIP-8 variant: `vip`, `hires`, `chip8x`, `eti660`, `chip48`, `schip`, `xochip` or `megachip`.
+    /// Quirk options below override it. Without it, the platform is guessed from the instructions the ROM uses
+    #[arg(long)]
+    platform: Option<Platform>,
+    /// Don't guess the platform from the SUPER-CHIP and XO-CHIP instructions the ROM uses
+    #[arg(long)]
+    no_detect: bool,
+    /// Address the ROM is loaded and started at, e.g. `0x600` for ETI-660 programs [default: 0x200]
+    #[arg(long, value_parser = parse_address)]
+    start_address: Option<u16>,
+    /// Pause before executing the instruction at this address or label, can be given several
+    /// times. F9 toggles a breakpoint at the current instruction while running
+    #[arg(long = "break", value_name = "ADDR|LABEL")]
+    breakpoints: Vec<String>,
+    /// Pause before `Fx55`, `Fx65`, `Fx33`, `Dxyn` or another instruction reads or writes
+    /// this address or inclusive range like `0x300-0x30F`, can be given several times
+    #[arg(long = "watch", value_name = "ADDR[-ADDR]", value_parser = parse_address_range)]
+    watchpoints: Vec<RangeInclusive<u16>>,
+    /// Pause before every `Dxyn`, to inspect the screen and VF between draws
+    #[arg(long)]
+    break_on_draw: bool,
+    /// Pause before every `00E0`
+    #[arg(long)]
+    break_on_clear: bool,
+    /// Pause the first time execution reaches this address or label, to skip startup code.
+    /// D runs until the next `Dxyn` or `00E0` while paused
+    #[arg(long, value_name = "ADDR|LABEL")]
+    run_to: Option<String>,
+    /// Symbol file naming the addresses of the ROM, as written by Octo, for the debugger
+    /// [default: the ROM path with a `.sym` extension, if it exists]
+    #[arg(long, value_name = "PATH")]
+    symbols: Option<PathBuf>,
+    /// Number of recent frames kept for stepping back with B while paused, 0 to disable
+    #[arg(long, value_name = "FRAMES", default_value_t = DEFAULT_HISTORY_FRAMES)]
+    history: usize,
+    /// Number of recent frames kept for rewinding with Backspace, 0 to disable
+    #[arg(long, value_name = "FRAMES", default_value_t = DEFAULT_REWIND_FRAMES)]
+    rewind: usize,
+    /// Count how often each address and instruction executes, and print the hot spots on exit
+    #[arg(long)]
+    profile: bool,
+    /// Write the ROM addresses that executed to this file on exit: JSON if it ends in
+    /// `.json`, otherwise a disassembly annotated with execution counts
+    #[arg(long, value_name = "PATH")]
+    coverage: Option<PathBuf>,
+    /// Serve a WebSocket for remote debugging tools on this address, e.g. `127.0.0.1:8765`
+    #[cfg(feature = "remote")]
+    #[arg(long, value_name = "ADDR")]
+    remote: Option<String>,
+    /// Make `8xy6`/`8xyE` shift Vy into Vx, like the original COSMAC VIP interpreter
+    #[arg(long)]
+    shift_vy: bool,
+    /// How much `Fx55`/`Fx65` advance I: `none`, `x` (CHIP-48) or `x+1` (COSMAC VIP)
+    #[arg(long)]
+    memory_increment: Option<MemoryIncrement>,
+    /// Reset VF to 0 after `8xy1`/`8xy2`/`8xy3`, like the original COSMAC VIP interpreter
+    #[arg(long)]
+    vf_reset: bool,
+    /// Let `Dxyn` wait for the next 60 Hz frame, like the original COSMAC VIP interpreter
+    #[arg(long)]
+    display_wait: bool,
+    /// What happens to sprites drawn past the screen edge: `wrap` around or `clip`
+    #[arg(long)]
+    sprite_edge: Option<SpriteEdge>,
+    /// Make `Bxnn` jump to xnn + Vx, like CHIP-48 and SUPER-CHIP, instead of nnn + V0
+    #[arg(long)]
+    jump_vx: bool,
+    /// Complete `Fx0A` when a key is pressed, instead of when it is released
+    #[arg(long)]
+    key_wait_press: bool,
+    /// Record the keys held in every frame, with the machine, RNG seed, quirks and speed, to this
+    /// input movie on exit
+    #[arg(long, value_name = "PATH", conflicts_with = "play_input")]
+    record_input: Option<PathBuf>,
+    /// Replay an input movie written by `--record-input`, with its machine, RNG seed, quirks and
+    /// speed
+    #[arg(long, value_name = "PATH")]
+    play_input: Option<PathBuf>,
+    /// Run without a window or GPU, then dump the final framebuffer
+    #[arg(long)]
+    headless: bool,
+    /// Number of instructions to execute in headless mode or with `test`
+    #[arg(long, default_value_t = 1000, global = true)]
+    cycles: usize,
+    /// Run exactly this many frames of `--ipf` instructions in headless mode, instead of
+    /// `--cycles` instructions
+    #[arg(long, value_name = "N")]
+    frames: Option<usize>,
+    /// Write the final framebuffer as a PNG image to this path instead of printing it as
+    /// PBM, running headless without `--headless`
+    #[arg(long, value_name = "PATH")]
+    screenshot: Option<PathBuf>,
+    /// Size of a CHIP-8 pixel in `--screenshot` images and those taken with F12
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_SCREENSHOT_SCALE)]
+    screenshot_scale: u32,
+    /// Draw the `--screenshot` image the way the window shows the screen, with the CRT effect
+    /// or `--shader`, `--bezel`, `--rotate`, gamma and brightness. Needs a graphics adapter
+    #[arg(long, requires = "screenshot")]
+    render_screenshot: bool,
+    /// Directory F12 saves screenshots in, instead of next to the ROM
+    #[arg(long, value_name = "DIR")]
+    screenshot_dir: Option<PathBuf>,
+    /// Dump the headless framebuffer as text art with `█` for lit pixels instead of PBM
+    #[arg(long)]
+    ascii: bool,
+    /// Write the headless framebuffer as a PBM image to this path instead of stdout
+    #[arg(long)]
+    dump: Option<PathBuf>,
+    /// Start from a save state, such as one written to a slot with `Shift+F1`-`Shift+F4`
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["record_input", "play_input"])]
+    load_state: Option<PathBuf>,
+    /// Continue where the ROM was left off when the window was last closed
+    #[arg(
+        long,
+        conflicts_with_all = ["load_state", "record_input", "play_input"]
+    )]
+    resume: bool,
+    /// Also save the final machine state to this path in headless mode, for `diff`
+    #[arg(long, value_name = "PATH")]
+    dump_state: Option<PathBuf>,
+    /// Color the screen with a built-in palette: `octo`, `gameboy`, `amber`, `paperwhite` or
+    /// `inverted`. F6 cycles through them and remembers the choice for later runs
+    #[arg(long)]
+    palette: Option<Palette>,
+    /// Color the screen with a `custom` palette of the background, XO-CHIP plane 1, plane 2 and
+    /// both planes, such as `#996600,#FFCC00,#FF6600,#662200`
+    #[arg(long, value_name = "COLORS", value_parser = palette::parse_plane_colors, conflicts_with = "palette")]
+    plane_colors: Option<[u32; 4]>,
+    /// Draw the screen with CRT scanlines, curvature and vignette. F7 toggles it
+    #[arg(long)]
+    crt: bool,
+    /// Post-process the screen with this WGSL shader instead of the CRT effect, defining
+    /// `fs_main` over the bindings described in the README. F7 toggles it
+    #[arg(long, value_name = "PATH", conflicts_with = "crt")]
+    shader: Option<PathBuf>,
+    /// How frames are presented: `fifo` waits for vsync, `mailbox` and `immediate` have less
+    /// latency, `immediate` with tearing. Falls back to `fifo` if unsupported
+    #[arg(long, default_value = "fifo")]
+    present_mode: PresentMode,
+    /// Draw the screen on the CPU instead of the GPU, for broken graphics drivers and X
+    /// forwarding. The CRT effect, shaders, bezels and overlay panels are not available
+    #[cfg(all(feature = "softbuffer", not(target_arch = "wasm32")))]
+    #[arg(long, conflicts_with_all = ["crt", "shader", "bezel"])]
+    no_gpu: bool,
+    /// Gamma applied to the screen colors: above 1 brightens the midtones, below 1 darkens them
+    #[arg(long, value_name = "GAMMA", value_parser = parse_positive, default_value_t = 1.0)]
+    gamma: f32,
+    /// Factor the screen colors are multiplied by, after the gamma
+    #[arg(long, value_name = "FACTOR", value_parser = parse_positive, default_value_t = 1.0)]
+    brightness: f32,
+    /// Turn the displayed screen clockwise by `0`, `90`, `180` or `270` degrees, for ROMs meant
+    /// to be played with the display on its side
+    #[arg(long, value_name = "DEGREES", default_value = "0")]
+    rotate: Rotation,
+    /// Open the window with every CHIP-8 pixel this many logical pixels wide, kept when it moves
+    /// to a monitor with a different scale factor
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    scale: Option<u32>,
+    /// Start in borderless fullscreen. F11 or Alt+Enter switches back to the window
+    #[arg(long)]
+    fullscreen: bool,
+    /// Draw this PNG image around the screen, such as a cabinet bezel, fitting the screen into
+    /// the transparent hole in its middle
+    #[arg(long, value_name = "PATH")]
+    bezel: Option<PathBuf>,
+    /// Fade pixels out over this many frames after they turn off, like CRT phosphor, to reduce
+    /// the flicker of sprites erased and redrawn every frame
+    #[arg(long, value_name = "FRAMES", default_value_t = 0)]
+    decay: u32,
+    /// Limit how fast the screen as a whole changes brightness, fading pixels in and out when
+    /// most of it flips, for people sensitive to flashing
+    #[arg(long)]
+    anti_flash: bool,
+    /// Frequency of the buzzer [default: 440, or `beep_freq` in the settings]
+    #[arg(long, value_name = "HZ", value_parser = parse_positive)]
+    beep_freq: Option<f32>,
+    /// Waveform of the buzzer: `sine`, `square` (the authentic buzzer sound), `triangle` or
+    /// `noise` [default: square, or `beep_wave` in the settings]
+    #[arg(long, value_name = "WAVE")]
+    beep_wave: Option<Waveform>,
+    /// Milliseconds the buzzer takes to fade in and out, avoiding pops, 0 for raw edges
+    /// [default: 5, or `beep_envelope` in the settings]
+    #[arg(long, value_name = "MS")]
+    beep_envelope: Option<u64>,
+    /// Loop this WAV, FLAC, Ogg Vorbis or MP3 file while the sound timer runs, instead of a
+    /// wave [default: `beep_sample` in the settings, if any]
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["beep_freq", "beep_wave"])]
+    beep_sample: Option<PathBuf>,
+    /// Milliseconds of sound buffered ahead: lower starts beeps sooner, higher avoids crackling
+    /// on slow machines [default: the audio device's, or `audio_latency` in the settings]
+    #[arg(long, value_name = "MS", value_parser = clap::value_parser!(u64).range(1..))]
+    audio_latency: Option<u64>,
+    /// Bind CHIP-8 keys to other physical keys, like `5=KeyI,8=KeyK`, replacing their default
+    /// keys and the `[keymap]` table in the settings. Keys are named like `KeyQ`, `Digit1`,
+    /// `Numpad5` or `ArrowUp`, or by a letter or digit on the `--layout`
+    #[arg(
+        long,
+        value_name = "KEY=PHYSICAL_KEY",
+        value_delimiter = ',',
+        value_parser = keymap::parse_binding
+    )]
+    keymap: Vec<(u8, KeyName)>,
+    /// Keyboard layout typed on, `qwerty`, `azerty`, `qwertz`, `dvorak` or `colemak`, naming
+    /// the keys that letters stand for in `--keymap` [default: qwerty, or `layout` in the
+    /// settings]
+    #[arg(long, value_name = "LAYOUT")]
+    layout: Option<Layout>,
+    /// Show the on-screen keypad from the start, for touchscreens. It also shows when the window
+    /// is first touched, and `Ctrl+K` shows or hides it
+    #[arg(long)]
+    keypad: bool,
 }
 
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run the built-in test ROMs on each platform and compare their final screens
+    /// with the expected ones, printing a pass/fail matrix
+    Check {
+        /// Check a directory of other `.ch8` ROMs, such as the Timendus test suite, each with
+        /// a `<name>.<platform>.pbm` or `<name>.pbm` image next to it
+        suite_dir: Option<PathBuf>,
+        /// Frames to run each ROM for
+        #[arg(long, default_value_t = 600)]
+        frames: usize,
+        /// Instructions per frame
+        #[arg(long, default_value_t = 1000)]
+        ipf: usize,
+    },
+    /// Print the differences in registers, memory and screen between two save states
+    Diff { state_a: PathBuf, state_b: PathBuf },
+    /// Run a ROM in the window with an interactive debugger reading commands such as
+    /// `b`, `s`, `c`, `x/16 0x200`, `reg` and `poke` from the terminal
+    Debug {
+        /// ROM to debug, other options apply as without a subcommand
+        rom_path: String,
+    },
+    /// Report the size, SHA-1 and variant instructions of a ROM and check it for problems,
+    /// exiting with an error if it can't run. `--start-address` applies as without a subcommand
+    Info {
+        /// ROM to inspect
+        rom_path: String,
+    },
+    /// Run a ROM headlessly for `--cycles` instructions and print the SHA-1 of its final
+    /// screen, the one of the PBM image `--headless` prints. Exits with an error if it doesn't
+    /// match `--expect-hash` or the ROM fails, for regression tests in CI
+    Test {
+        /// ROM to run, other options apply as without a subcommand
+        rom_path: String,
+        /// SHA-1 the final screen must have
+        #[arg(long, value_name = "SHA1")]
+        expect_hash: Option<String>,
+    },
+    /// Run a ROM as fast as possible without rendering and report the instructions per
+    /// second, the time each kind of instruction takes and the allocations made. Other
+    /// options apply as without a subcommand
+    Bench {
+        /// ROM to run
+        rom_path: String,
+        /// Seconds to run for, then as long again to time each instruction
+        #[arg(long, default_value_t = 5.0)]
+        seconds: f64,
+    },
+    /// Assemble a program written in a subset of Octo's assembly language into a ROM
+    Asm {
+        /// Octo source, usually with a `.8o` extension
+        source: PathBuf,
+        /// Where to write the ROM
+        #[arg(short, long, value_name = "PATH")]
+        output: PathBuf,
+        /// Address the ROM will be loaded at
+        #[arg(long, value_parser = parse_address, default_value = "0x200")]
+        start_address: u16,
+    },
+    /// Trim, pad or splice ROM images, for preparing them for real hardware
+    Rom {
+        #[command(subcommand)]
+        tool: RomTool,
+    },
+    /// Print the annotated assembly of a ROM, following its jumps, calls and skips to tell
+    /// code from data. `--start-address` and `--symbols` apply as without a subcommand
+    Disasm {
+        /// ROM to disassemble
+        rom_path: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum RomTool {
+    /// Remove the padding at the end of a ROM
+    Trim {
+        rom_path: PathBuf,
+        /// Where to write the trimmed ROM
+        #[arg(short, long, value_name = "PATH")]
+        output: PathBuf,
+        /// Padding byte to remove
+        #[arg(long, value_parser = parse_byte, default_value = "0x00")]
+        fill: u8,
+    },
+    /// Pad a ROM to a size, such as that of an EPROM
+    Pad {
+        rom_path: PathBuf,
+        /// Size in bytes, in hexadecimal with a `0x` prefix or in decimal
+        #[arg(value_parser = parse_size)]
+        size: usize,
+        /// Where to write the padded ROM
+        #[arg(short, long, value_name = "PATH")]
+        output: PathBuf,
+        /// Byte to pad with
+        #[arg(long, value_parser = parse_byte, default_value = "0x00")]
+        fill: u8,
+    },
+    /// Write a file into a ROM at an offset, or append it to the ROM without one
+    Splice {
+        rom_path: PathBuf,
+        /// File to write into the ROM
+        data_path: PathBuf,
+        /// Where to write the resulting ROM
+        #[arg(short, long, value_name = "PATH")]
+        output: PathBuf,
+        /// Offset from the start of the ROM file, not a memory address. Defaults to its end
+        #[arg(long, value_parser = parse_size)]
+        offset: Option<usize>,
+    },
+}
+
+/// Counts allocations for `bench`.
+#[cfg(not(target_arch = "wasm32"))]
+#[global_allocator]
+static ALLOCATOR: bench::CountingAllocator = bench::CountingAllocator;
+
 fn main() -> Result<(), AppError> {
     #[cfg(not(target_arch = "wasm32"))]
-    let rom = {
-        println!("Hello, CHIP-8!");
+    let (args, rom) = {
+        env_logger::init();
+        info!("Hello, CHIP-8!");
 
         let args = Args::parse();
+        if let Some(
+            command @ (Command::Check { .. }
+            | Command::Diff { .. }
+            | Command::Asm { .. }
+            | Command::Rom { .. }),
+        ) = &args.command
+        {
+            return run_command(command);
+        }
 
         // Load ROM
-        let file = File::open(&args.rom_path)?;
+        let file = File::open(rom_path(&args))?;
         let rom = BufReader::new(file);
-        rom.bytes().map(|b| b.unwrap()).collect::<Vec<u8>>()
+        let rom = rom.bytes().map(|b| b.unwrap()).collect::<Vec<u8>>();
+        if let Some(Command::Bench { seconds, .. }) = &args.command {
+            let mut emulator = create_emulator(&args, &rom, &Symbols::new())?;
+            let duration = Duration::try_from_secs_f64(*seconds).map_err(|err| {
+                AppError::InvalidArgument(format!("--seconds {}: {}", seconds, err))
+            })?;
+            let ipf = emulator.instructions_per_frame;
+            print!("{}", bench::run(&mut emulator.chip, &rom, duration, ipf)?);
+            return Ok(());
+        }
+        if let Some(Command::Test { expect_hash, .. }) = &args.command {
+            return run_test(&args, &rom, expect_hash.as_deref());
+        }
+        if matches!(args.command, Some(Command::Info { .. })) {
+            let report =
+                inspect::inspect(&rom, args.start_address.unwrap_or(DEFAULT_PROGRAM_START));
+            print!("{}", report);
+            if report.is_fatal() {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        if matches!(args.command, Some(Command::Disasm { .. })) {
+            let start = args.start_address.unwrap_or(DEFAULT_PROGRAM_START);
+            print!(
+                "{}",
+                disasm::disassemble(&rom, start, &load_symbols(&args)?)
+            );
+            return Ok(());
+        }
+        if args.headless || args.screenshot.is_some() {
+            return run_headless(&args, &rom);
+        }
+        (args, rom)
     };
 
-    // let (_stream, stream_handle) = rodio::OutputStream::try_default().unwrap();
-    // let beep = SineWave::new(560.0f32)
-    //     .take_duration(Duration::from_millis(200))
-    //     .fade_in(Duration::from_millis(100));
-    // let beep1 = stream_handle.play_raw(beep).unwrap();
-    // beep1.set_volume(1.0);
-
     let event_loop = EventLoop::new().unwrap();
 
     let mut builder = winit::window::WindowBuilder::new();
-    builder = builder.with_inner_size(LogicalSize::new(640 * 2, 320 * 2));
+    builder = builder.with_inner_size(if args.rotate.is_sideways() {
+        LogicalSize::new(400, 800)
+    } else {
+        LogicalSize::new(640 * 2, 320 * 2)
+    });
 
     #[cfg(target_arch = "wasm32")]
     {
@@ -80,11 +493,13 @@ fn main() -> Result<(), AppError> {
         builder = builder.with_canvas(Some(canvas));
     }
     let window = builder.build(&event_loop).unwrap();
+    if let Some(scale) = args.scale {
+        let _ = window.request_inner_size(window_size(scale, window.scale_factor(), args.rotate));
+    }
 
     #[cfg(not(target_arch = "wasm32"))]
     {
-        env_logger::init();
-        pollster::block_on(run(event_loop, window, rom));
+        pollster::block_on(run(event_loop, window, args, rom))?;
     }
     #[cfg(target_arch = "wasm32")]
     {
@@ -96,84 +511,131 @@ fn main() -> Result<(), AppError> {
     Ok(())
 }
 
-async fn run(event_loop: EventLoop<()>, window: Window, rom: Vec<u8>) -> AppResult<()> {
+async fn run(event_loop: EventLoop<()>, window: Window, args: Args, rom: Vec<u8>) -> AppResult<()> {
     let mut surface_size = window.inner_size();
     surface_size.width = surface_size.width.max(1);
     surface_size.height = surface_size.height.max(1);
 
     let world = Rc::new(RefCell::new(World::new(surface_size)));
-    let mut renderer = Renderer::create(&window, Rc::clone(&world), surface_size).await;
+    let mut settings = Settings::load().unwrap_or_else(|err| {
+        warn!("Could not load the settings: {}", err);
+        Settings::default()
+    });
+    let custom_palette = args
+        .plane_colors
+        .or_else(|| saved_plane_colors(&settings))
+        .map(Palette::custom);
+    world
+        .borrow_mut()
+        .set_palette(if args.plane_colors.is_some() {
+            custom_palette
+        } else {
+            args.palette
+                .or_else(|| saved_palette(&settings, custom_palette))
+        });
+    world.borrow_mut().set_decay(args.decay);
+    world.borrow_mut().set_anti_flash(args.anti_flash);
+    world.borrow_mut().camera.rotation = args.rotate;
+    let mut renderer = create_renderer(&window, Rc::clone(&world), surface_size, &args).await?;
+    match &args.shader {
+        Some(path) => renderer.load_shader(path)?,
+        None => renderer.set_post_processing(args.crt),
+    }
+    if let Some(path) = &args.bezel {
+        renderer.set_bezel(&Bezel::load(path)?)?;
+    }
+    renderer.set_color_correction(ColorCorrection {
+        gamma: args.gamma,
+        brightness: args.brightness,
+    });
+    renderer.set_fullscreen(args.fullscreen);
+    if args.keypad {
+        renderer.overlay.toggle(Panel::Keypad);
+    }
 
     let mut surface_configured = false;
+    // Whether the title already tells that the program exited with `00FD`.
+    let mut exit_shown = false;
+    let mut modifiers = ModifiersState::empty();
+    // Whether the emulator was paused by `pause_on_unfocus`, to resume when focused again.
+    let mut paused_unfocused = false;
+    // Keys held on the on-screen keypad when last checked.
+    let mut touched_keys = 0;
     let window = &window;
 
-    // Render timings
-    const FRAME_TIME: i64 = 16_666;
-    let start_time = Instant::now();
-    let mut previous_time = 0i64;
-    let mut elapsed_time = 0i64;
-    let mut lag = 0i64;
-    // let mut last_fps_update = 0i64;
-    // let mut fps = 0u64;
-
-    // Control
-    let mut pressed_keys: [bool; 16] = [false; 16];
-    let mut waiting_for_key: Option<usize> = None;
-    let mut paused = false;
-    let mut speed = 15;
-
-    // Chip
-    const INSTRUCTION_LEN: u16 = 2;
-    let mut memory: [u8; 4096] = [0; 4096];
-    let mut registers: [u8; 16] = [0; 16];
-    let mut register_i: u16 = 0;
-
-    let mut pc: u16 = 0x200;
-    let mut stack: [u16; 16] = [0; 16];
-    let mut sp: u8 = 0;
-    let mut delay_timer: u8 = 0;
-    let mut sound_timer: u8 = 0;
-
-    const SPRITES: [[u8; 5]; 16] = [
-        [0xF0, 0x90, 0x90, 0x90, 0xF0], // 0
-        [0x20, 0x60, 0x20, 0x20, 0x70], // 1
-        [0xF0, 0x10, 0xF0, 0x80, 0xF0], // 2
-        [0xF0, 0x10, 0xF0, 0x10, 0xF0], // 3
-        [0x90, 0x90, 0xF0, 0x10, 0x10], // 4
-        [0xF0, 0x80, 0xF0, 0x10, 0xF0], // 5
-        [0xF0, 0x80, 0xF0, 0x90, 0xF0], // 6
-        [0xF0, 0x10, 0x20, 0x40, 0x40], // 7
-        [0xF0, 0x90, 0xF0, 0x90, 0xF0], // 8
-        [0xF0, 0x90, 0xF0, 0x10, 0xF0], // 9
-        [0xF0, 0x90, 0xF0, 0x90, 0x90], // A
-        [0xE0, 0x90, 0xE0, 0x90, 0xE0], // B
-        [0xF0, 0x80, 0x80, 0x80, 0xF0], // C
-        [0xE0, 0x90, 0x90, 0x90, 0xE0], // D
-        [0xF0, 0x80, 0xF0, 0x80, 0xF0], // E
-        [0xF0, 0x80, 0xF0, 0x80, 0x80], // F
-    ];
-    // Sprite data should be stored in the interpreter area of Chip-8 memory (0x000 to 0x1FF).
-    for (i, sprite) in SPRITES.iter().enumerate() {
-        for (j, &value) in sprite.iter().enumerate() {
-            memory[i * 5 + j] = value;
-        }
+    let symbols = Arc::new(load_symbols(&args)?);
+    renderer.overlay.set_symbols(Arc::clone(&symbols));
+    let emulator = create_emulator(&args, &rom, &symbols)?;
+    // Kept for as long as the window, or the buzzer goes quiet.
+    let beep = Beep {
+        frequency: args
+            .beep_freq
+            .or_else(|| saved_beep_freq(&settings))
+            .unwrap_or(DEFAULT_BEEP_FREQUENCY),
+        waveform: args
+            .beep_wave
+            .or_else(|| saved_beep_wave(&settings))
+            .unwrap_or_default(),
+        envelope: args
+            .beep_envelope
+            .or(settings.beep_envelope)
+            .map_or(DEFAULT_BEEP_ENVELOPE, Duration::from_millis),
+        sample: match &args.beep_sample {
+            Some(path) => Some(BeepSample::load(path)?),
+            // A wave asked for on the command line replaces the saved sample.
+            None if args.beep_freq.is_none() && args.beep_wave.is_none() => {
+                saved_beep_sample(&settings)
+            }
+            None => None,
+        },
+    };
+    let layout = args
+        .layout
+        .or_else(|| saved_layout(&settings))
+        .unwrap_or_default();
+    let mut keymap = Keymap::default();
+    keymap.remap(&saved_keymap(&settings), layout);
+    keymap.remap(&args.keymap, layout);
+    info!("Keypad keys 0-F: {}", keymap.describe(layout));
+    let latency = args
+        .audio_latency
+        .or(settings.audio_latency)
+        .map(Duration::from_millis);
+    let audio = open_audio(&emulator, beep, latency)
+        .map_err(|err| {
+            warn!(
+                "Could not open the audio output, running without sound: {}",
+                err
+            )
+        })
+        .ok();
+    let rom_len = rom.len();
+    window.set_title(&window_title(&emulator));
+    let emulator = Arc::new(Mutex::new(emulator));
+    let mut emulation_thread = EmulationThread::spawn(Arc::clone(&emulator));
+    if matches!(args.command, Some(Command::Debug { .. })) {
+        repl::spawn(Arc::clone(&emulator), Arc::clone(&symbols))?;
     }
-    for (i, value) in rom.iter().enumerate() {
-        memory[0x200 + i] = *value;
+    #[cfg(feature = "remote")]
+    if let Some(addr) = &args.remote {
+        let server = RemoteServer::spawn(addr, Arc::clone(&emulator))?;
+        info!("Remote debugging on ws://{}", server.local_addr());
     }
 
     event_loop.run(move |event, target| {
         // Have the closure take ownership of the resources.
         // `event_loop.run` never returns, therefore we must do this to ensure
         // the resources are properly cleaned up.
-        // let _ = (&instance, &adapter, &shader, &pipeline_layout);
-        let _ = (&renderer);
+        let _ = (&renderer, &emulation_thread, &audio);
 
         if let Event::WindowEvent {
             window_id: _,
             event,
         } = event
         {
+            if renderer.overlay.handle_event(window, &event) {
+                return;
+            }
             match event {
                 WindowEvent::RedrawRequested => {
                     window.request_redraw();
@@ -182,29 +644,66 @@ async fn run(event_loop: EventLoop<()>, window: Window, rom: Vec<u8>) -> AppResu
                         return;
                     }
 
-                    let current_time = Instant::now().duration_since(start_time).as_micros() as i64;
-                    elapsed_time = current_time - previous_time;
-
-                    previous_time = current_time;
-
-                    if !paused {
-                        lag += elapsed_time;
-                        while lag >= FRAME_TIME {
-                            renderer.update();
-
-                            if delay_timer > 0 {
-                                delay_timer -= 1;
-                            }
-                            if sound_timer > 0 {
-                                sound_timer -= 1;
-                            }
-
-                            lag -= FRAME_TIME;
+                    {
+                        let mut emulator = emulator.lock().unwrap();
+                        let mut world = world.borrow_mut();
+                        world.set_screen(&mut emulator.chip.display);
+                        renderer.overlay.set_paused(emulator.is_paused());
+                        renderer.overlay.count_frame(emulator.chip.executed);
+                        let touched = renderer.overlay.touched_keys();
+                        if touched != touched_keys {
+                            // Keys held on the keyboard stay pressed.
+                            let keypad = &mut emulator.chip.keypad;
+                            keypad.set_mask((keypad.mask() & !touched_keys) | touched);
+                            touched_keys = touched;
+                        }
+                        world.machine = renderer
+                            .overlay
+                            .shows_machine()
+                            .then(|| emulator.chip.snapshot());
+                        if let Some(err) = emulator.error.take() {
+                            window.set_title(&format!("CHIP-8 - {}", err));
+                        }
+                        if emulator.chip.exited && !exit_shown {
+                            window.set_title("CHIP-8 - program exited, press F5 to restart");
+                        }
+                        exit_shown = emulator.chip.exited;
+                        if let Some(slot) = renderer.overlay.take_picked_slot() {
+                            let path = state::slot_path(Path::new(rom_path(&args)), slot);
+                            load_slot(&mut emulator, slot, &path);
+                            window.set_title(&window_title(&emulator));
+                        }
+                        if let Some(reason) = emulator.break_reason.take() {
+                            window.set_title(&format!("CHIP-8 - {} (paused)", reason));
+                            print!(
+                                "Stopped at {}\n{}",
+                                reason,
+                                debugger::describe(&emulator.chip, &symbols)
+                            );
+                        }
+                    }
+                    #[cfg(not(target_arch = "wasm32"))]
+                    match renderer.reload_shader() {
+                        Some(Ok(())) => {
+                            info!("Reloaded the shader");
+                            renderer.overlay.set_error(None);
+                            renderer.overlay.show_status("Shader reloaded");
                         }
+                        Some(Err(err)) => {
+                            error!("Could not reload the shader: {}", err);
+                            renderer.overlay.set_error(Some(err.to_string()));
+                        }
+                        None => {}
                     }
+                    renderer.update();
 
                     match renderer.render() {
-                        Ok(_) => {}
+                        Ok(true) => {}
+                        Ok(false) => {
+                            // Nothing was presented to wait for vsync on, so pace the loop here.
+                            #[cfg(not(target_arch = "wasm32"))]
+                            std::thread::sleep(renderer.idle_wait());
+                        }
                         Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
                             renderer.resize(renderer.surface_size());
                         }
@@ -216,356 +715,239 @@ async fn run(event_loop: EventLoop<()>, window: Window, rom: Vec<u8>) -> AppResu
                             warn!("Surface timeout")
                         }
                     }
+                }
+                WindowEvent::KeyboardInput {
+                    device_id: _,
+                    event,
+                    is_synthetic,
+                } => {
+                    if is_synthetic {
+                        return;
+                    }
 
-                    // fps += 1;
-                    // if (current_time - last_fps_update) >= 1_000_000 {
-                    //     println!("FPS: {}", fps);
-                    //     fps = 0;
-                    //     last_fps_update = current_time;
-                    // }
-
-                    // renderer.update();
-
-                    for i in 0..speed {
-                        if paused {
-                            break;
-                        }
-
-                        // Execute instruction
-                        let opcode =
-                            (memory[pc as usize] as u16) << 8 | memory[pc as usize + 1] as u16;
-
-                        // Variables
-                        let nnn = opcode & 0x0FFF;
-                        let nibble = opcode & 0x000F;
-                        let x = ((opcode & 0x0F00) >> 8) as usize;
-                        let y = ((opcode & 0x00F0) >> 4) as usize;
-                        let kk = (opcode & 0x00FF) as u8;
-
-                        // Decode opcode
-                        match opcode & 0xF000 {
-                            0x0000 => match opcode {
-                                0x00E0 => {
-                                    // 00E0 - CLS
-                                    // Clear the display.
-                                    world.borrow_mut().screen.clear();
-                                }
-                                0x00EE => {
-                                    // 00EE - RET
-                                    // Return from a subroutine.
-                                    // The interpreter sets the program counter to the address at the top of the stack, then subtracts 1 from the stack pointer.
-                                    sp -= 1;
-                                    pc = stack[sp as usize];
-                                }
-                                _ => {
-                                    // 0nnn - SYS addr
-                                    // Jump to a machine code routine at nnn.
-                                    // This instruction is only used on the old computers on which Chip-8 was originally implemented.
-                                    // It is ignored by modern interpreters.
-                                }
-                            },
-                            0x1000 => {
-                                // 1nnn - JP addr
-                                // Jump to location nnn.
-                                // The interpreter sets the program counter to nnn.
-                                pc = nnn;
-                                continue;
-                            }
-                            0x2000 => {
-                                // 2nnn - CALL addr
-                                // Call subroutine at nnn.
-                                // The interpreter increments the stack pointer, then puts the current PC on the top of the stack. The PC is then set to nnn.
-                                stack[sp as usize] = pc;
-                                sp += 1;
-                                pc = nnn;
-                                continue;
-                            }
-                            0x3000 => {
-                                // 3xkk - SE Vx, byte
-                                // Skip next instruction if Vx = kk.
-                                // The interpreter compares register Vx to kk, and if they are equal, increments the program counter by 2.
-                                if registers[x] == kk {
-                                    pc += INSTRUCTION_LEN;
-                                }
-                            }
-                            0x4000 => {
-                                // 4xkk - SNE Vx, byte
-                                // Skip next instruction if Vx != kk.
-                                // The interpreter compares register Vx to kk, and if they are not equal, increments the program counter by 2.
-                                if registers[x] != kk {
-                                    pc += INSTRUCTION_LEN;
-                                }
-                            }
-                            0x5000 => {
-                                // 5xy0 - SE Vx, Vy
-                                // Skip next instruction if Vx = Vy.
-                                // The interpreter compares register Vx to register Vy, and if they are equal, increments the program counter by 2.
-                                if registers[x] == registers[y] {
-                                    pc += INSTRUCTION_LEN;
-                                }
-                            }
-                            0x6000 => {
-                                // 6xkk - LD Vx, byte
-                                // Set Vx = kk.
-                                // The interpreter puts the value kk into register Vx.
-                                registers[x] = kk as u8;
-                            }
-                            0x7000 => {
-                                // 7xkk - ADD Vx, byte
-                                // Set Vx = Vx + kk.
-                                // Adds the value kk to the value of register Vx, then stores the result in Vx.
-                                registers[x] = registers[x].wrapping_add(kk as u8);
-                            }
-                            0x8000 => match nibble {
-                                0x0000 => {
-                                    // 8xy0 - LD Vx, Vy
-                                    // Set Vx = Vy.
-                                    // Stores the value of register Vy in register Vx.
-                                    registers[x] = registers[y];
-                                }
-                                0x0001 => {
-                                    // 8xy1 - OR Vx, Vy
-                                    // Set Vx = Vx OR Vy.
-                                    // Performs a bitwise OR on the values of Vx and Vy, then stores the result in Vx.
-                                    registers[x] |= registers[y];
-                                }
-                                0x0002 => {
-                                    // 8xy2 - AND Vx, Vy
-                                    // Set Vx = Vx AND Vy.
-                                    // Performs a bitwise AND on the values of Vx and Vy, then stores the result in Vx.
-                                    registers[x] &= registers[y];
-                                }
-                                0x0003 => {
-                                    // 8xy3 - XOR Vx, Vy
-                                    // Set Vx = Vx XOR Vy.
-                                    // Performs a bitwise exclusive OR on the values of Vx and Vy, then stores the result in Vx.
-                                    registers[x] ^= registers[y];
-                                }
-                                0x0004 => {
-                                    // 8xy4 - ADD Vx, Vy
-                                    // Set Vx = Vx + Vy, set VF = carry.
-                                    // The values of Vx and Vy are added together. If the result is greater than 8 bits (i.e., > 255,) VF is set to 1, otherwise 0.
-                                    // Only the lowest 8 bits of the result are kept, and stored in Vx.
-                                    let (result, overflow) =
-                                        registers[x].overflowing_add(registers[y]);
-                                    registers[x] = result;
-                                    registers[0xF] = overflow as u8;
-                                }
-                                0x0005 => {
-                                    // 8xy5 - SUB Vx, Vy
-                                    // Set Vx = Vx - Vy, set VF = NOT borrow.
-                                    // If Vx > Vy, then VF is set to 1, otherwise 0. Then Vy is subtracted from Vx, and the results stored in Vx.
-                                    let (result, overflow) =
-                                        registers[x].overflowing_sub(registers[y]);
-                                    registers[x] = result;
-                                    registers[0xF] = !overflow as u8;
-                                }
-                                0x0006 => {
-                                    // 8xy6 - SHR Vx {, Vy}
-                                    // Set Vx = Vx SHR 1.
-                                    // If the least-significant bit of Vx is 1, then VF is set to 1, otherwise 0. Then Vx is divided by 2.
-                                    registers[0xF] = registers[x] & 0x1;
-                                    registers[x] >>= 1;
-                                }
-                                0x0007 => {
-                                    // 8xy7 - SUBN Vx, Vy
-                                    // Set Vx = Vy - Vx, set VF = NOT borrow.
-                                    // If Vy > Vx, then VF is set to 1, otherwise 0. Then Vx is subtracted from Vy, and the results stored in Vx.
-                                    let (result, overflow) =
-                                        registers[y].overflowing_sub(registers[x]);
-                                    registers[x] = result;
-                                    registers[0xF] = !overflow as u8;
+                    if let PhysicalKey::Code(key_code) = event.physical_key {
+                        let mut emulator = emulator.lock().unwrap();
+                        if event.state.is_pressed() {
+                            match key_code {
+                                KeyCode::Space => {
+                                    emulator.toggle_pause();
+                                    window.set_title(&window_title(&emulator));
                                 }
-                                0x000E => {
-                                    // 8xyE - SHL Vx {, Vy}
-                                    // Set Vx = Vx SHL 1.
-                                    // If the most-significant bit of Vx is 1, then VF is set to 1, otherwise to 0. Then Vx is multiplied by 2.
-                                    registers[0xF] = (registers[x] & 0x80) >> 7;
-                                    registers[x] <<= 1;
+                                KeyCode::KeyN | KeyCode::KeyM if emulator.is_paused() => {
+                                    let result = if key_code == KeyCode::KeyN {
+                                        emulator.step_instruction()
+                                    } else {
+                                        emulator.step_frame()
+                                    };
+                                    match result {
+                                        Ok(()) => window.set_title(&window_title(&emulator)),
+                                        Err(err) => emulator.error = Some(err),
+                                    }
+                                    // `N` and `M` aren't bound to CHIP-8 keys by default, but a
+                                    // `--keymap` or `[keymap]` may bind them. Don't press those too.
+                                    return;
                                 }
-                                _ => unreachable!("Unknown opcode: {:#06X}", opcode),
-                            },
-                            0x9000 => {
-                                // 9xy0 - SNE Vx, Vy
-                                // Skip next instruction if Vx != Vy.
-                                // The values of Vx and Vy are compared, and if they are not equal, the program counter is increased by 2.
-                                if registers[x] != registers[y] {
-                                    pc += INSTRUCTION_LEN;
+                                KeyCode::KeyD if emulator.is_paused() => {
+                                    emulator.run_to(RunTarget::Draw);
+                                    window.set_title(&window_title(&emulator));
+                                    return;
                                 }
-                            }
-                            0xA000 => {
-                                // Annn - LD I, addr
-                                // Set I = nnn.
-                                // The value of register I is set to nnn.
-                                register_i = nnn;
-                            }
-                            0xB000 => {
-                                // Bnnn - JP V0, addr
-                                // Jump to location nnn + V0.
-                                // The program counter is set to nnn plus the value of V0.
-                                pc = nnn + registers[0] as u16;
-                                continue;
-                            }
-                            0xC000 => {
-                                // Cxkk - RND Vx, byte
-                                // Set Vx = random byte AND kk.
-                                // The interpreter generates a random number from 0 to 255, which is then ANDed with the value kk.
-                                // The results are stored in Vx.
-                                registers[x] = rand::random::<u8>() & kk;
-                            }
-                            0xD000 => {
-                                // Dxyn - DRW Vx, Vy, nibble
-                                // Display n-byte sprite starting at memory location I at (Vx, Vy), set VF = collision.
-                                // The interpreter reads n bytes from memory, starting at the address stored in I.
-                                // These bytes are then displayed as sprites on screen at coordinates (Vx, Vy).
-                                // Sprites are XORed onto the existing screen.
-                                // If this causes any pixels to be erased, VF is set to 1, otherwise it is set to 0.
-                                // If the sprite is positioned so part of it is outside the coordinates of the display, it wraps around to the opposite side of the screen.
-
-                                let width = 8u8; // 8 pixels
-                                let height = nibble as u8;
-
-                                registers[0xF] = 0;
-                                for y_pixel in 0..height {
-                                    let mut pixel = memory[register_i as usize + y_pixel as usize];
-                                    for x_pixel in 0..width {
-                                        if (pixel & 0x80) > 0 {
-                                            if world.borrow_mut().screen.toggle(
-                                                registers[x].wrapping_add(x_pixel),
-                                                registers[y].wrapping_add(y_pixel),
-                                            ) {
-                                                registers[0xF] = 1;
-                                            }
+                                KeyCode::KeyB if emulator.is_paused() => {
+                                    match emulator.step_back() {
+                                        Ok(true) => window.set_title(&window_title(&emulator)),
+                                        Ok(false) => {
+                                            println!("No more history to step back through")
                                         }
-                                        pixel <<= 1;
+                                        Err(err) => error!("Could not step back: {}", err),
                                     }
+                                    return;
                                 }
-                            }
-                            0xE000 => match kk {
-                                0x9E => {
-                                    // Ex9E - SKP Vx
-                                    // Skip next instruction if key with the value of Vx is pressed.
-                                    // Checks the keyboard, and if the key corresponding to the value of Vx is currently in the down position, PC is increased by 2.
-                                    if pressed_keys[registers[x] as usize] {
-                                        pc += INSTRUCTION_LEN;
+                                _ if (modifiers.shift_key() || modifiers.control_key())
+                                    && SLOT_KEYS.contains(&key_code) =>
+                                {
+                                    let slot = SLOT_KEYS
+                                        .iter()
+                                        .position(|&key| key == key_code)
+                                        .unwrap_or_default()
+                                        + 1;
+                                    let path = state::slot_path(Path::new(rom_path(&args)), slot);
+                                    if modifiers.shift_key() {
+                                        save_slot(&emulator, slot, &path);
+                                        if renderer.overlay.is_shown(Panel::Slots) {
+                                            renderer.overlay.set_slots(slot_previews(&args));
+                                        }
+                                    } else {
+                                        load_slot(&mut emulator, slot, &path);
+                                        window.set_title(&window_title(&emulator));
                                     }
                                 }
-                                0xA1 => {
-                                    // ExA1 - SKNP Vx
-                                    // Skip next instruction if key with the value of Vx is not pressed.
-                                    // Checks the keyboard, and if the key corresponding to the value of Vx is currently in the up position, PC is increased by 2.
-                                    if !pressed_keys[registers[x] as usize] {
-                                        pc += INSTRUCTION_LEN;
+                                KeyCode::F1 => renderer.overlay.toggle(Panel::Memory),
+                                KeyCode::F2 => renderer.overlay.toggle(Panel::Registers),
+                                KeyCode::F3 => renderer.overlay.toggle(Panel::Disassembly),
+                                KeyCode::F4 => {
+                                    renderer.overlay.toggle(Panel::Slots);
+                                    if renderer.overlay.is_shown(Panel::Slots) {
+                                        renderer.overlay.set_slots(slot_previews(&args));
                                     }
                                 }
-                                _ => unreachable!("Unknown opcode: {:#06X}", opcode),
-                            },
-                            0xF000 => match kk {
-                                0x07 => {
-                                    // Fx07 - LD Vx, DT
-                                    // Set Vx = delay timer value.
-                                    // The value of DT is placed into Vx.
-                                    registers[x] = delay_timer;
+                                KeyCode::F10 => renderer.overlay.toggle(Panel::Stats),
+                                KeyCode::KeyK if modifiers.control_key() => {
+                                    renderer.overlay.toggle(Panel::Keypad)
                                 }
-                                0x0A => {
-                                    // Fx0A - LD Vx, K
-                                    // Wait for a key press, store the value of the key in Vx.
-                                    // All execution stops until a key is pressed, then the value of that key is stored in Vx.
-                                    if waiting_for_key.is_none() {
-                                        paused = true;
-                                        waiting_for_key = Some(x);
+                                KeyCode::F6 => {
+                                    let palette =
+                                        Palette::next(world.borrow().palette(), custom_palette);
+                                    world.borrow_mut().set_palette(palette);
+                                    let status = format!(
+                                        "Palette: {}",
+                                        palette.map_or("default", |palette| palette.name)
+                                    );
+                                    println!("{}", status);
+                                    renderer.overlay.show_status(status);
+                                    settings.palette =
+                                        palette.map(|palette| palette.name.to_owned());
+                                    if let Some(colors) = &args.plane_colors {
+                                        settings.plane_colors =
+                                            Some(palette::format_plane_colors(colors));
                                     }
+                                    if let Err(err) = settings.save() {
+                                        error!("Could not save the palette: {}", err);
+                                    }
+                                }
+                                KeyCode::F11 => renderer.set_fullscreen(!renderer.is_fullscreen()),
+                                KeyCode::Enter if modifiers.alt_key() => {
+                                    renderer.set_fullscreen(!renderer.is_fullscreen())
                                 }
-                                0x15 => {
-                                    // Fx15 - LD DT, Vx
-                                    // Set delay timer = Vx.
-                                    // DT is set equal to the value of Vx.
-                                    delay_timer = registers[x];
+                                KeyCode::F7 => {
+                                    renderer.set_post_processing(!renderer.is_post_processing())
                                 }
-                                0x18 => {
-                                    // Fx18 - LD ST, Vx
-                                    // Set sound timer = Vx.
-                                    // ST is set equal to the value of Vx.
-                                    sound_timer = registers[x];
+                                #[cfg(not(target_arch = "wasm32"))]
+                                KeyCode::F12 => {
+                                    let path = screenshot::screenshot_path(
+                                        Path::new(rom_path(&args)),
+                                        args.screenshot_dir.as_deref(),
+                                    );
+                                    let plane_colors =
+                                        Palette::plane_colors(world.borrow().palette());
+                                    match screenshot::save_png(
+                                        &emulator.chip.display,
+                                        &plane_colors,
+                                        args.screenshot_scale,
+                                        &path,
+                                    ) {
+                                        Ok(()) => {
+                                            println!("Saved screenshot to {}", path.display());
+                                            renderer.overlay.show_status("Screenshot saved");
+                                        }
+                                        Err(err) => {
+                                            error!("Could not save the screenshot: {}", err)
+                                        }
+                                    }
                                 }
-                                0x1E => {
-                                    // Fx1E - ADD I, Vx
-                                    // Set I = I + Vx.
-                                    // The values of I and Vx are added, and the results are stored in I.
-                                    register_i += registers[x] as u16;
+                                KeyCode::F8 => {
+                                    print!("{}", ascii::encode(&emulator.chip.display))
                                 }
-                                0x29 => {
-                                    // Fx29 - LD F, Vx
-                                    // Set I = location of sprite for digit Vx.
-                                    // The value of I is set to the location for the hexadecimal sprite corresponding to the value of Vx.
-                                    register_i = (registers[x] * 5) as u16;
+                                KeyCode::F9 => {
+                                    let pc = emulator.chip.pc;
+                                    let set = emulator.debugger.toggle_breakpoint(pc);
+                                    println!(
+                                        "Breakpoint at {:#05X} {}",
+                                        pc,
+                                        if set { "set" } else { "removed" }
+                                    );
                                 }
-                                0x33 => {
-                                    // Fx33 - LD B, Vx
-                                    // Store BCD representation of Vx in memory locations I, I+1, and I+2.
-                                    // The interpreter takes the decimal value of Vx, and places the hundreds digit in memory at location in I, the tens digit at location I+1, and the ones digit at location I+2.
-                                    memory[register_i as usize] = registers[x] / 100;
-                                    memory[register_i as usize + 1] = (registers[x] / 10) % 10;
-                                    memory[register_i as usize + 2] = registers[x] % 10;
+                                KeyCode::F5 => {
+                                    emulator.reset();
+                                    window.set_title(&window_title(&emulator));
                                 }
-                                0x55 => {
-                                    // Fx55 - LD [I], Vx
-                                    // Store registers V0 through Vx in memory starting at location I.
-                                    // The interpreter copies the values of registers V0 through Vx into memory, starting at the address in I.
-                                    for i in 0..=x {
-                                        memory[register_i as usize + i] = registers[i];
-                                    }
+                                KeyCode::Equal | KeyCode::NumpadAdd => {
+                                    let ipf = emulator.instructions_per_frame;
+                                    emulator.set_instructions_per_frame(ipf + 1);
+                                    window.set_title(&window_title(&emulator));
+                                    renderer.overlay.show_status(speed_status(&emulator));
                                 }
-                                0x65 => {
-                                    // Fx65 - LD Vx, [I]
-                                    // Read registers V0 through Vx from memory starting at location I.
-                                    // The interpreter reads values from memory starting at location I into registers V0 through Vx.
-                                    for i in 0..=x {
-                                        registers[i] = memory[register_i as usize + i];
-                                    }
+                                KeyCode::Minus | KeyCode::NumpadSubtract => {
+                                    let ipf = emulator.instructions_per_frame;
+                                    emulator.set_instructions_per_frame(ipf.saturating_sub(1));
+                                    window.set_title(&window_title(&emulator));
+                                    renderer.overlay.show_status(speed_status(&emulator));
                                 }
-                                _ => unreachable!("Unknown opcode: {:#06X}", opcode),
-                            },
-                            _ => unreachable!("Unknown opcode: {:#06X}", opcode),
+                                _ => {}
+                            }
                         }
 
-                        pc += 2;
-                    }
-                }
-                WindowEvent::KeyboardInput {
-                    device_id,
-                    event,
-                    is_synthetic,
-                } => {
-                    if is_synthetic {
-                        return;
-                    }
-
-                    if let PhysicalKey::Code(key_code) = event.physical_key {
-                        if KeyCode::Space == key_code && event.state.is_pressed() {
-                            paused = !paused;
+                        if key_code == KeyCode::Backspace && !event.repeat {
+                            let rewinding = event.state.is_pressed();
+                            if rewinding
+                                && (emulator.recording.is_some() || emulator.playback.is_some())
+                            {
+                                println!("Can't rewind while recording or playing an input movie");
+                            } else if rewinding != emulator.is_rewinding() {
+                                emulator.set_rewinding(rewinding);
+                                window.set_title(&window_title(&emulator));
+                            }
                         }
 
-                        if let Some(key_index) = get_key_index(key_code) {
+                        if let Some(key_index) = keymap.key(key_code) {
+                            let keypad = &mut emulator.chip.keypad;
                             if event.state.is_pressed() {
-                                pressed_keys[key_index] = true;
-                                if let Some(waiting_x) = waiting_for_key {
-                                    registers[waiting_x] = key_index as u8;
-                                    paused = false;
-                                    waiting_for_key = None;
-                                }
+                                keypad.press(key_index);
                             } else {
-                                pressed_keys[key_index] = false;
+                                keypad.release(key_index);
                             }
                         }
                     }
                 }
+                // Touches reach the overlay instead once the keypad is shown.
+                WindowEvent::Touch(_) if !renderer.overlay.is_shown(Panel::Keypad) => {
+                    renderer.overlay.toggle(Panel::Keypad);
+                }
+                WindowEvent::ModifiersChanged(new_modifiers) => {
+                    modifiers = new_modifiers.state();
+                }
+                WindowEvent::Focused(focused) => {
+                    let mut emulator = emulator.lock().unwrap();
+                    if settings.mute_on_unfocus {
+                        emulator.set_muted(!focused);
+                    }
+                    if !focused && settings.pause_on_unfocus && !emulator.is_paused() {
+                        emulator.pause();
+                        paused_unfocused = true;
+                    } else if focused && std::mem::take(&mut paused_unfocused) {
+                        emulator.resume();
+                    }
+                    window.set_title(&window_title(&emulator));
+                }
+                WindowEvent::ScaleFactorChanged {
+                    scale_factor,
+                    mut inner_size_writer,
+                } => {
+                    // Otherwise winit keeps the logical size, which may no longer
+                    // be a whole number of physical pixels per CHIP-8 pixel.
+                    if let Some(scale) = args.scale.filter(|_| !renderer.is_fullscreen()) {
+                        let size = window_size(scale, scale_factor, args.rotate);
+                        let _ = inner_size_writer.request_inner_size(size);
+                    }
+                }
                 WindowEvent::Resized(new_size) => {
                     surface_configured = true;
                     renderer.resize(new_size);
                     window.request_redraw();
                 }
-                WindowEvent::CloseRequested => target.exit(),
+                WindowEvent::CloseRequested => {
+                    emulation_thread.stop();
+                    let emulator = emulator.lock().unwrap();
+                    if let Err(err) = write_reports(&args, &emulator, rom_len) {
+                        error!("Could not write coverage: {}", err);
+                    }
+                    if let Err(err) = save_recording(&args, &emulator) {
+                        error!("Could not save the input movie: {}", err);
+                    }
+                    if let Err(err) = autosave(&emulator, &rom) {
+                        error!("Could not save the state for --resume: {}", err);
+                    }
+                    target.exit();
+                }
                 _ => {}
             };
         }
@@ -574,30 +956,677 @@ async fn run(event_loop: EventLoop<()>, window: Window, rom: Vec<u8>) -> AppResu
     Ok(())
 }
 
-fn get_key_index(key_code: KeyCode) -> Option<usize> {
-    /*
-        1 2 3 4
-        Q W E R
-        A S D F
-        Z X C V
-    */
-    const KEY_MAP: [KeyCode; 16] = [
-        KeyCode::Digit1,
-        KeyCode::Digit2,
-        KeyCode::Digit3,
-        KeyCode::Digit4,
-        KeyCode::KeyQ,
-        KeyCode::KeyW,
-        KeyCode::KeyE,
-        KeyCode::KeyR,
-        KeyCode::KeyA,
-        KeyCode::KeyS,
-        KeyCode::KeyD,
-        KeyCode::KeyF,
-        KeyCode::KeyZ,
-        KeyCode::KeyX,
-        KeyCode::KeyC,
-        KeyCode::KeyV,
-    ];
-    KEY_MAP.iter().position(|&k| k == key_code)
+/// Renderer for `window`, on the CPU with `--no-gpu`.
+async fn create_renderer<'a>(
+    window: &'a Window,
+    world: Rc<RefCell<World>>,
+    surface_size: PhysicalSize<u32>,
+    args: &Args,
+) -> AppResult<Renderer<'a>> {
+    #[cfg(all(feature = "softbuffer", not(target_arch = "wasm32")))]
+    if args.no_gpu {
+        return Renderer::create_soft(window, world, surface_size);
+    }
+    Ok(Renderer::create(window, world, surface_size, args.present_mode).await)
+}
+
+/// Physical size of a window showing the 64x32 screen, turned by `rotation`,
+/// with every CHIP-8 pixel `scale` logical pixels wide at `scale_factor`.
+/// Pixels are rounded to whole physical pixels, so they are all as large and sharp.
+fn window_size(scale: u32, scale_factor: f64, rotation: Rotation) -> PhysicalSize<u32> {
+    let pixel = (scale as f64 * scale_factor).round().max(1.0) as u32;
+    let (width, height) = (SCREEN_WIDTH as u32 * pixel, SCREEN_HEIGHT as u32 * pixel);
+    if rotation.is_sideways() {
+        PhysicalSize::new(height, width)
+    } else {
+        PhysicalSize::new(width, height)
+    }
+}
+
+/// Opens the audio output, buffering `latency` if given, and plays the
+/// buzzer of `emulator` on it, beeping like `beep`.
+fn open_audio(
+    emulator: &Emulator,
+    beep: Beep,
+    latency: Option<Duration>,
+) -> AppResult<AudioOutput> {
+    let audio = AudioOutput::open(latency)?;
+    audio.play(&emulator.chip.audio, beep);
+    Ok(audio)
+}
+
+/// Status message shown when the speed changes.
+fn speed_status(emulator: &Emulator) -> String {
+    format!(
+        "Speed: {} instructions per frame",
+        emulator.instructions_per_frame
+    )
+}
+
+fn window_title(emulator: &Emulator) -> String {
+    if emulator.is_rewinding() {
+        return "CHIP-8 - rewinding".to_owned();
+    }
+    if !emulator.is_paused() {
+        return format!("CHIP-8 - {} IPF", emulator.instructions_per_frame);
+    }
+    match emulator.chip.next_instruction() {
+        Ok(instruction) => format!(
+            "CHIP-8 - paused at {:#05X}: {}",
+            emulator.chip.pc, instruction
+        ),
+        Err(_) => format!("CHIP-8 - paused at {:#05X}", emulator.chip.pc),
+    }
+}
+
+/// Sets up the machine from the command line.
+fn create_emulator(args: &Args, rom: &[u8], symbols: &Symbols) -> AppResult<Emulator> {
+    let detected = if args.platform.is_none() && !args.no_detect {
+        inspect::detect_platform(rom, args.start_address.unwrap_or(DEFAULT_PROGRAM_START))
+    } else {
+        None
+    };
+    if let Some(platform) = detected {
+        info!(
+            "ROM uses {} instructions, switching to that platform (disable with --no-detect)",
+            platform
+        );
+    }
+
+    let platform = args.platform.or(detected);
+    let mut config = platform.map_or(MachineConfig::new(), Platform::config);
+    if args.strict_memory {
+        config = config.with_memory_mode(MemoryMode::Strict);
+    }
+    if let Some(start_address) = args.start_address {
+        config = config.with_program_start(start_address);
+    }
+
+    let movie = args.play_input.as_deref().map(Movie::load).transpose()?;
+    if let Some(movie_config) = movie.as_ref().and_then(|movie| movie.config) {
+        config = movie_config;
+    }
+
+    let mut chip = Chip8::with_buses(config, Screen::new(), Keypad::new(), Buzzer::new());
+    chip.quirks = platform.map_or(Quirks::new(), Platform::quirks);
+    if args.shift_vy {
+        chip.quirks.shift_vy = true;
+    }
+    if args.vf_reset {
+        chip.quirks.vf_reset = true;
+    }
+    if args.display_wait {
+        chip.quirks.display_wait = true;
+    }
+    if let Some(sprite_edge) = args.sprite_edge {
+        chip.quirks.sprite_edge = sprite_edge;
+    }
+    if args.jump_vx {
+        chip.quirks.jump_vx = true;
+    }
+    if let Some(memory_increment) = args.memory_increment {
+        chip.quirks.memory_increment = memory_increment;
+    }
+    if args.key_wait_press {
+        chip.quirks.key_wait_release = false;
+    }
+    if let Some(movie) = &movie {
+        if !movie.matches_rom(rom) {
+            warn!("The input movie was recorded with a different ROM");
+        }
+        chip.quirks = movie.quirks;
+    }
+    // Tests and movies must not depend on chance or on the flags saved by earlier runs.
+    let testing = matches!(args.command, Some(Command::Test { .. }));
+    let reproducible = testing || movie.is_some() || args.record_input.is_some();
+    let seed = match &movie {
+        Some(movie) => Some(movie.rng_seed),
+        None if testing => Some(args.rng_seed.unwrap_or(0)),
+        None if args.record_input.is_some() => Some(args.rng_seed.unwrap_or_else(rand::random)),
+        None => args.rng_seed,
+    };
+    if let Some(seed) = seed {
+        info!("Using RNG seed {}", seed);
+        chip.seed_rng(seed);
+    }
+    let mut emulator = Emulator::new(chip);
+    emulator.load_rom(rom)?;
+    for location in &args.breakpoints {
+        emulator
+            .debugger
+            .add_breakpoint(resolve_address(location, symbols)?);
+    }
+    emulator.debugger.set_break_on_draw(args.break_on_draw);
+    emulator.debugger.set_break_on_clear(args.break_on_clear);
+    for range in &args.watchpoints {
+        emulator.debugger.add_watchpoint(range.clone());
+    }
+    if let Some(location) = &args.run_to {
+        let address = resolve_address(location, symbols)?;
+        emulator.debugger.run_to(RunTarget::Address(address));
+    }
+    emulator.history = History::new(args.history);
+    emulator.rewind = Rewind::new(args.rewind);
+    if args.profile || args.coverage.is_some() {
+        emulator.profiler = Some(Profiler::new());
+    }
+    if !reproducible {
+        emulator.persist_flags(FlagStore::for_rom(Path::new(rom_path(args)), rom))?;
+    }
+    let ipf = movie
+        .as_ref()
+        .map(|movie| movie.instructions_per_frame)
+        .or(args.ipf)
+        .unwrap_or(DEFAULT_INSTRUCTIONS_PER_FRAME);
+    emulator.set_instructions_per_frame(ipf);
+    if let (Some(_), Some(seed)) = (&args.record_input, seed) {
+        emulator.recording = Some(Movie::new(
+            rom,
+            seed,
+            *emulator.chip.config(),
+            emulator.chip.quirks,
+            emulator.instructions_per_frame,
+        ));
+    }
+    emulator.playback = movie.map(Playback::new);
+    if let Some(path) = &args.load_state {
+        emulator.load_state(&MachineState::load(path)?)?;
+    }
+    if args.resume {
+        match state::autosave_path(rom).filter(|path| path.exists()) {
+            Some(path) => emulator.load_state(&MachineState::load(&path)?)?,
+            None => info!("Nothing to resume, starting from the beginning"),
+        }
+    }
+    Ok(emulator)
+}
+
+/// Reads `--symbols`, or the `.sym` file next to the ROM if there is one.
+fn load_symbols(args: &Args) -> AppResult<Symbols> {
+    match &args.symbols {
+        Some(path) => Symbols::load(path),
+        None => {
+            let path = Path::new(rom_path(args)).with_extension("sym");
+            if path.is_file() {
+                info!("Loading symbols from {}", path.display());
+                Symbols::load(&path)
+            } else {
+                Ok(Symbols::new())
+            }
+        }
+    }
+}
+
+/// Parses an address, or looks up a label in `symbols`.
+fn resolve_address(location: &str, symbols: &Symbols) -> AppResult<u16> {
+    symbols.resolve(location).ok_or_else(|| {
+        AppError::InvalidArgument(format!("unknown address or label `{}`", location))
+    })
+}
+
+/// Keys saving to slots 1 to [`SLOT_COUNT`] with `Shift` and loading them with `Ctrl`.
+const SLOT_KEYS: [KeyCode; SLOT_COUNT] = [KeyCode::F1, KeyCode::F2, KeyCode::F3, KeyCode::F4];
+
+/// Save state slots of the ROM with their thumbnails, for the slot picker.
+fn slot_previews(args: &Args) -> Vec<SlotPreview> {
+    (1..=SLOT_COUNT)
+        .map(|slot| {
+            let path = state::slot_path(Path::new(rom_path(args)), slot);
+            if !path.exists() {
+                return SlotPreview {
+                    saved: false,
+                    thumbnail: None,
+                };
+            }
+            let thumbnail =
+                state::load_thumbnail(&path).and_then(|png| screenshot::decode_png(&png));
+            let thumbnail = match thumbnail {
+                Ok((width, height, rgb)) => Some(egui::ColorImage::from_rgb([width, height], &rgb)),
+                Err(err) => {
+                    warn!("Could not read the thumbnail of slot {}: {}", slot, err);
+                    None
+                }
+            };
+            SlotPreview {
+                saved: true,
+                thumbnail,
+            }
+        })
+        .collect()
+}
+
+fn save_slot(emulator: &Emulator, slot: usize, path: &Path) {
+    match emulator.chip.snapshot().save(path) {
+        Ok(()) => println!("Saved state to slot {}", slot),
+        Err(err) => error!("Could not save state to slot {}: {}", slot, err),
+    }
+}
+
+fn load_slot(emulator: &mut Emulator, slot: usize, path: &Path) {
+    // The movie would no longer match what happens.
+    if emulator.recording.is_some() || emulator.playback.is_some() {
+        println!("States can't be loaded while recording or playing an input movie");
+        return;
+    }
+    match MachineState::load(path).and_then(|state| emulator.load_state(&state)) {
+        Ok(()) => println!("Loaded state from slot {}", slot),
+        Err(err) => error!("Could not load state from slot {}: {}", slot, err),
+    }
+}
+
+/// ROM given with `--rom-path` or to `debug`, which clap requires unless another subcommand is used.
+fn rom_path(args: &Args) -> &str {
+    match &args.command {
+        Some(
+            Command::Debug { rom_path }
+            | Command::Disasm { rom_path }
+            | Command::Info { rom_path }
+            | Command::Test { rom_path, .. }
+            | Command::Bench { rom_path, .. },
+        ) => rom_path,
+        _ => args.rom_path.as_deref().expect("--rom-path is required"),
+    }
+}
+
+fn run_command(command: &Command) -> AppResult<()> {
+    match command {
+        Command::Check {
+            suite_dir,
+            frames,
+            ipf,
+        } => {
+            let reports = match suite_dir {
+                Some(suite_dir) => check::run_suite(suite_dir, &CHECKED_PLATFORMS, *frames, *ipf)?,
+                None => check::run_bundled(&CHECKED_PLATFORMS, *frames, *ipf)?,
+            };
+            let name_width = reports
+                .iter()
+                .map(|report| report.name.len())
+                .max()
+                .unwrap_or_default();
+            print!("{:name_width$}", "");
+            for platform in CHECKED_PLATFORMS {
+                print!("  {:>7}", platform.name());
+            }
+            println!();
+            for report in &reports {
+                print!("{:name_width$}", report.name);
+                for outcome in &report.outcomes {
+                    print!("  {:>7}", outcome.label());
+                }
+                println!();
+            }
+            Ok(())
+        }
+        Command::Diff { state_a, state_b } => {
+            let differences =
+                diff::diff(&MachineState::load(state_a)?, &MachineState::load(state_b)?);
+            if differences.is_empty() {
+                println!("No differences");
+            } else {
+                print!("{}", differences);
+            }
+            Ok(())
+        }
+        Command::Asm {
+            source,
+            output,
+            start_address,
+        } => {
+            let rom = assembler::assemble(&std::fs::read_to_string(source)?, *start_address)?;
+            std::fs::write(output, &rom)?;
+            info!("Assembled {} bytes into {}", rom.len(), output.display());
+            Ok(())
+        }
+        Command::Rom { tool } => run_rom_tool(tool),
+        Command::Debug { .. }
+        | Command::Disasm { .. }
+        | Command::Info { .. }
+        | Command::Test { .. }
+        | Command::Bench { .. } => {
+            unreachable!("runs with the ROM loaded")
+        }
+    }
+}
+
+fn run_rom_tool(tool: &RomTool) -> AppResult<()> {
+    let (rom, output) = match tool {
+        RomTool::Trim {
+            rom_path,
+            output,
+            fill,
+        } => (
+            romtools::trim(&std::fs::read(rom_path)?, *fill).to_vec(),
+            output,
+        ),
+        RomTool::Pad {
+            rom_path,
+            size,
+            output,
+            fill,
+        } => (
+            romtools::pad(&std::fs::read(rom_path)?, *size, *fill)?,
+            output,
+        ),
+        RomTool::Splice {
+            rom_path,
+            data_path,
+            output,
+            offset,
+        } => {
+            let rom = std::fs::read(rom_path)?;
+            let offset = offset.unwrap_or(rom.len());
+            (
+                romtools::splice(&rom, &std::fs::read(data_path)?, offset),
+                output,
+            )
+        }
+    };
+    std::fs::write(output, &rom)?;
+    info!("Wrote {} bytes to {}", rom.len(), output.display());
+    Ok(())
+}
+
+/// Parses a byte, in hexadecimal with a `0x` prefix or in decimal.
+fn parse_byte(s: &str) -> Result<u8, String> {
+    let result = match s.strip_prefix("0x").or(s.strip_prefix("0X")) {
+        Some(hex) => u8::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    result.map_err(|err| format!("invalid byte `{}`: {}", s, err))
+}
+
+/// Parses a number greater than zero.
+fn parse_positive(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(value) if value > 0.0 && value.is_finite() => Ok(value),
+        Ok(_) => Err(format!("expected a number greater than 0, got `{}`", s)),
+        Err(err) => Err(format!("invalid number `{}`: {}", s, err)),
+    }
+}
+
+/// Parses a size or file offset, in hexadecimal with a `0x` prefix or in decimal.
+fn parse_size(s: &str) -> Result<usize, String> {
+    let result = match s.strip_prefix("0x").or(s.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    result.map_err(|err| format!("invalid size `{}`: {}", s, err))
+}
+
+/// Parses a memory address, in hexadecimal with a `0x` prefix or in decimal.
+fn parse_address(s: &str) -> Result<u16, String> {
+    let result = match s.strip_prefix("0x").or(s.strip_prefix("0X")) {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    result.map_err(|err| format!("invalid address `{}`: {}", s, err))
+}
+
+/// Parses a single address or an inclusive `start-end` range.
+fn parse_address_range(s: &str) -> Result<RangeInclusive<u16>, String> {
+    let (start, end) = match s.split_once('-') {
+        Some((start, end)) => (parse_address(start)?, parse_address(end)?),
+        None => {
+            let address = parse_address(s)?;
+            (address, address)
+        }
+    };
+    if start > end {
+        return Err(format!("invalid range `{}`: start is after end", s));
+    }
+    Ok(start..=end)
+}
+
+/// Runs the ROM for `--cycles` instructions without creating a window or GPU device.
+fn run_headless(args: &Args, rom: &[u8]) -> AppResult<()> {
+    let mut emulator = create_emulator(args, rom, &load_symbols(args)?)?;
+    match args.frames {
+        Some(frames) => {
+            // Nobody can step back or rewind without a window.
+            emulator.history = History::new(0);
+            emulator.rewind = Rewind::new(0);
+            for _ in 0..frames {
+                if emulator.chip.exited {
+                    break;
+                }
+                emulator.step_frame()?;
+            }
+        }
+        None => run_cycles(&mut emulator, args.cycles)?,
+    }
+    emulator.save_flags()?;
+    write_reports(args, &emulator, rom.len())?;
+    save_recording(args, &emulator)?;
+    if let Some(path) = &args.dump_state {
+        emulator.chip.snapshot().save(path)?;
+    }
+
+    if let Some(path) = &args.screenshot {
+        let palette = match args.plane_colors {
+            Some(colors) => Some(Palette::custom(colors)),
+            None => args.palette,
+        };
+        if args.render_screenshot {
+            save_rendered_screenshot(args, &mut emulator.chip.display, palette, path)?;
+        } else {
+            screenshot::save_png(
+                &emulator.chip.display,
+                &Palette::plane_colors(palette),
+                args.screenshot_scale,
+                path,
+            )?;
+        }
+    }
+    let dump = if args.ascii {
+        ascii::encode(&emulator.chip.display)
+    } else {
+        pbm::encode(&emulator.chip.display)
+    };
+    match &args.dump {
+        Some(path) => std::fs::write(path, dump)?,
+        None if args.screenshot.is_none() => print!("{}", dump),
+        None => {}
+    }
+    Ok(())
+}
+
+/// Writes `screen` to the PNG image at `path` as the window would show it in
+/// `palette`, for `--render-screenshot`.
+fn save_rendered_screenshot(
+    args: &Args,
+    screen: &mut Screen,
+    palette: Option<Palette>,
+    path: &Path,
+) -> AppResult<()> {
+    let size = window_size(args.screenshot_scale, 1.0, args.rotate);
+    let mut renderer = pollster::block_on(OffscreenRenderer::create(size))?;
+    renderer.world.set_palette(palette);
+    renderer.world.camera.rotation = args.rotate;
+    match &args.shader {
+        Some(shader) => renderer.load_shader(shader)?,
+        None => renderer.set_post_processing(args.crt),
+    }
+    if let Some(bezel) = &args.bezel {
+        renderer.set_bezel(&Bezel::load(bezel)?);
+    }
+    renderer.set_color_correction(ColorCorrection {
+        gamma: args.gamma,
+        brightness: args.brightness,
+    });
+    screenshot::save_frame(&renderer.render(screen, 0.0)?, path)
+}
+
+/// Runs `test`, exiting with an error if the screen doesn't match `expect_hash`.
+fn run_test(args: &Args, rom: &[u8], expect_hash: Option<&str>) -> AppResult<()> {
+    let mut emulator = create_emulator(args, rom, &load_symbols(args)?)?;
+    run_cycles(&mut emulator, args.cycles)?;
+    let hash = sha1_smol::Sha1::from(pbm::encode(&emulator.chip.display))
+        .digest()
+        .to_string();
+    println!("{}", hash);
+    if let Some(expected) = expect_hash {
+        if !hash.eq_ignore_ascii_case(expected.trim()) {
+            error!(
+                "Screen hash {} doesn't match the expected {}",
+                hash, expected
+            );
+            std::process::exit(1);
+        }
+    }
+    Ok(())
+}
+
+/// Executes `cycles` instructions in frames of at most `--ipf`, or until the ROM exits.
+fn run_cycles(emulator: &mut Emulator, cycles: usize) -> AppResult<()> {
+    // Nobody can step back or rewind without a window.
+    emulator.history = History::new(0);
+    emulator.rewind = Rewind::new(0);
+
+    let ipf = emulator.instructions_per_frame.max(1);
+    let mut remaining = cycles;
+    while remaining > 0 && !emulator.chip.exited {
+        let instructions = remaining.min(ipf);
+        emulator.instructions_per_frame = instructions;
+        emulator.step_frame()?;
+        remaining -= instructions;
+    }
+    Ok(())
+}
+
+/// Saves the state for `--resume`, unless an input movie is being recorded or
+/// played, which starts from the beginning anyway.
+fn autosave(emulator: &Emulator, rom: &[u8]) -> AppResult<()> {
+    if emulator.recording.is_some() || emulator.playback.is_some() {
+        return Ok(());
+    }
+    let Some(path) = state::autosave_path(rom) else {
+        return Ok(());
+    };
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    emulator.chip.snapshot().save(&path)?;
+    info!("Saved the state to {} for --resume", path.display());
+    Ok(())
+}
+
+/// Palette chosen with F6 in an earlier run, if it still exists.
+fn saved_palette(settings: &Settings, custom: Option<Palette>) -> Option<Palette> {
+    let name = settings.palette.as_deref()?;
+    if name == "custom" {
+        if custom.is_none() {
+            warn!("Ignoring the saved custom palette, its colors are missing");
+        }
+        return custom;
+    }
+    name.parse()
+        .map_err(|err| warn!("Ignoring the saved palette {:?}: {}", name, err))
+        .ok()
+}
+
+/// Colors of the custom palette in the settings.
+fn saved_plane_colors(settings: &Settings) -> Option<[u32; 4]> {
+    let colors = settings.plane_colors.as_deref()?;
+    palette::parse_plane_colors(colors)
+        .map_err(|err| warn!("Ignoring the saved plane colors: {}", err))
+        .ok()
+}
+
+/// Buzzer frequency in the settings, if valid.
+fn saved_beep_freq(settings: &Settings) -> Option<f32> {
+    let frequency = settings.beep_freq?;
+    if frequency > 0.0 && frequency.is_finite() {
+        Some(frequency)
+    } else {
+        warn!(
+            "Ignoring the saved beep frequency {}, it must be greater than 0",
+            frequency
+        );
+        None
+    }
+}
+
+/// Buzzer waveform in the settings, if valid.
+fn saved_beep_wave(settings: &Settings) -> Option<Waveform> {
+    let name = settings.beep_wave.as_deref()?;
+    name.parse()
+        .map_err(|err| warn!("Ignoring the saved beep waveform {:?}: {}", name, err))
+        .ok()
+}
+
+/// Buzzer sample in the settings, if it loads.
+fn saved_beep_sample(settings: &Settings) -> Option<BeepSample> {
+    let path = settings.beep_sample.as_deref()?;
+    BeepSample::load(path)
+        .map_err(|err| warn!("Ignoring the saved beep sample: {}", err))
+        .ok()
+}
+
+/// Keyboard layout in the settings, if valid.
+fn saved_layout(settings: &Settings) -> Option<Layout> {
+    let name = settings.layout.as_deref()?;
+    name.parse()
+        .map_err(|err| warn!("Ignoring the saved keyboard layout {:?}: {}", name, err))
+        .ok()
+}
+
+/// Key bindings in the `[keymap]` table of the settings, skipping invalid ones.
+fn saved_keymap(settings: &Settings) -> Vec<(u8, KeyName)> {
+    settings
+        .keymap
+        .iter()
+        .filter_map(|(key, key_code)| {
+            keymap::parse_key(key)
+                .and_then(|key| Ok((key, keymap::parse_key_name(key_code)?)))
+                .map_err(|err| {
+                    warn!(
+                        "Ignoring the saved key binding {} = {:?}: {}",
+                        key, key_code, err
+                    )
+                })
+                .ok()
+        })
+        .collect()
+}
+
+/// Writes the `--record-input` movie.
+fn save_recording(args: &Args, emulator: &Emulator) -> AppResult<()> {
+    if let (Some(path), Some(movie)) = (&args.record_input, &emulator.recording) {
+        movie.save(path)?;
+        info!(
+            "Recorded {} frames of input to {}",
+            movie.frames.len(),
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Number of addresses listed in the `--profile` report.
+const PROFILE_ADDRESSES: usize = 20;
+
+/// Prints the `--profile` report and writes the `--coverage` file, as requested.
+fn write_reports(args: &Args, emulator: &Emulator, rom_len: usize) -> AppResult<()> {
+    let Some(profiler) = &emulator.profiler else {
+        return Ok(());
+    };
+    let memory = emulator.chip.memory();
+    if args.profile {
+        eprint!("{}", profiler.report(memory, PROFILE_ADDRESSES));
+    }
+    if let Some(path) = &args.coverage {
+        let start = emulator.chip.config().program_start as usize;
+        let range = start..start + rom_len;
+        let text = if path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            Coverage::new(profiler, range).to_json()
+        } else {
+            coverage::annotate(profiler, memory, range)
+        };
+        std::fs::write(path, text)?;
+    }
+    Ok(())
 }
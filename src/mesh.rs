@@ -12,6 +12,10 @@ pub struct Vertex {
 #[derive(Clone, Copy, Pod, Zeroable)]
 pub struct InstanceData {
     position: [f32; 2],
+    /// Pixel color, or fully transparent to use the default shading.
+    color: [f32; 4],
+    /// Opacity over the background, below 1 while a pixel fades out.
+    brightness: f32,
 }
 
 pub struct Mesh {
@@ -45,19 +49,62 @@ impl InstanceData {
     pub fn new(position: Vector2<f32>) -> Self {
         Self {
             position: position.into(),
+            color: [0.0; 4],
+            brightness: 1.0,
         }
     }
 
+    /// Instance drawn in `argb`, a `0xAARRGGBB` color.
+    pub fn with_color(position: Vector2<f32>, argb: u32) -> Self {
+        let channel = |shift: u32| ((argb >> shift) & 0xFF) as f32 / 255.0;
+        Self {
+            position: position.into(),
+            color: [channel(16), channel(8), channel(0), channel(24)],
+            brightness: 1.0,
+        }
+    }
+
+    /// The instance blended over the background, from 0 (invisible) to 1.
+    pub fn with_brightness(self, brightness: f32) -> Self {
+        Self { brightness, ..self }
+    }
+
+    /// Column and row of the pixel on the screen.
+    pub fn position(&self) -> [f32; 2] {
+        self.position
+    }
+
+    /// Red, green, blue and alpha, with alpha 0 for the default shading.
+    pub fn color(&self) -> [f32; 4] {
+        self.color
+    }
+
+    pub fn brightness(&self) -> f32 {
+        self.brightness
+    }
+
     pub fn description() -> wgpu::VertexBufferLayout<'static> {
         use std::mem;
         wgpu::VertexBufferLayout {
             array_stride: mem::size_of::<InstanceData>() as wgpu::BufferAddress,
             step_mode: wgpu::VertexStepMode::Instance,
-            attributes: &[wgpu::VertexAttribute {
-                offset: 0,
-                shader_location: 1,
-                format: wgpu::VertexFormat::Float32x2,
-            }],
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 6]>() as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32,
+                },
+            ],
         }
     }
 }
@@ -0,0 +1,102 @@
+//! Input movies: the keys held in every frame, together with everything else
+//! needed to replay a run exactly, for tool-assisted runs and bug reports.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::MachineConfig,
+    error::{AppError, AppResult},
+    quirks::Quirks,
+};
+
+/// Version written to new movies. Older versions are still played, newer ones
+/// are rejected.
+pub const MOVIE_VERSION: u32 = 2;
+
+/// Recorded run, saved as JSON.
+///
+/// Playing it back starts the ROM on the same machine with the same RNG seed,
+/// quirks and speed and holds the recorded keys frame by frame. Resets, single steps and
+/// speed changes during the recording are not part of it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Movie {
+    pub version: u32,
+    /// SHA-1 of the ROM the movie was recorded with.
+    pub rom_sha1: String,
+    pub rng_seed: u64,
+    /// Memory layout, display and instruction set of the machine, `None` in
+    /// version 1 movies, which are played on the machine the command line sets up.
+    #[serde(default)]
+    pub config: Option<MachineConfig>,
+    pub quirks: Quirks,
+    pub instructions_per_frame: usize,
+    /// Keys held during each frame, see [`Keypad::mask`](crate::keypad::Keypad::mask).
+    pub frames: Vec<u16>,
+}
+
+impl Movie {
+    pub fn new(
+        rom: &[u8],
+        rng_seed: u64,
+        config: MachineConfig,
+        quirks: Quirks,
+        instructions_per_frame: usize,
+    ) -> Self {
+        Self {
+            version: MOVIE_VERSION,
+            rom_sha1: sha1_smol::Sha1::from(rom).digest().to_string(),
+            rng_seed,
+            config: Some(config),
+            quirks,
+            instructions_per_frame,
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn load(path: &Path) -> AppResult<Self> {
+        let movie: Self = serde_json::from_str(&std::fs::read_to_string(path)?).map_err(|_| {
+            AppError::InvalidArgument(format!("malformed movie {}", path.display()))
+        })?;
+        if movie.version == 0 || movie.version > MOVIE_VERSION {
+            return Err(AppError::InvalidArgument(format!(
+                "movie {} has unsupported version {}",
+                path.display(),
+                movie.version
+            )));
+        }
+        Ok(movie)
+    }
+
+    pub fn save(&self, path: &Path) -> AppResult<()> {
+        let json = serde_json::to_string(self).expect("movies are always serializable");
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+
+    /// Whether the movie was recorded with `rom`.
+    pub fn matches_rom(&self, rom: &[u8]) -> bool {
+        self.rom_sha1 == sha1_smol::Sha1::from(rom).digest().to_string()
+    }
+}
+
+/// Playback position in a [`Movie`].
+#[derive(Debug, Clone)]
+pub struct Playback {
+    movie: Movie,
+    frame: usize,
+}
+
+impl Playback {
+    pub fn new(movie: Movie) -> Self {
+        Self { movie, frame: 0 }
+    }
+
+    /// Keys to hold in the next frame, or `None` once the movie is over.
+    pub fn next_frame(&mut self) -> Option<u16> {
+        let keys = self.movie.frames.get(self.frame).copied();
+        self.frame += 1;
+        keys
+    }
+}
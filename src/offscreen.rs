@@ -0,0 +1,177 @@
+//! Rendering without a window, into a texture read back as an image, for
+//! golden-image tests of the whole render stack: palettes, phosphor decay,
+//! color correction, bezels and post-processing shaders. The debugging
+//! overlay needs a window and is not drawn.
+
+use std::{path::Path, sync::mpsc};
+
+use image::RgbaImage;
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    bezel::Bezel,
+    error::{AppError, AppResult},
+    scene::{ColorCorrection, Scene},
+    screen::Screen,
+    world::World,
+};
+
+/// Format of the frames, read back as is.
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+const BYTES_PER_PIXEL: u32 = 4;
+
+pub struct OffscreenRenderer {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    size: PhysicalSize<u32>,
+    texture: wgpu::Texture,
+    /// Rows of the texture, each padded to the alignment copies require.
+    buffer: wgpu::Buffer,
+    scene: Scene,
+    /// What is drawn, with its palette, decay and camera.
+    pub world: World,
+}
+
+impl OffscreenRenderer {
+    /// Renders frames of `size`, as a window of that size would show them.
+    pub async fn create(size: PhysicalSize<u32>) -> AppResult<Self> {
+        let size = PhysicalSize::new(size.width.max(1), size.height.max(1));
+        let instance = wgpu::Instance::default();
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                force_fallback_adapter: false,
+                compatible_surface: None,
+            })
+            .await
+            .ok_or_else(|| AppError::Internal("no graphics adapter found".into()))?;
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: None,
+                    required_features: wgpu::Features::empty(),
+                    required_limits: wgpu::Limits::downlevel_webgl2_defaults()
+                        .using_resolution(adapter.limits()),
+                },
+                None,
+            )
+            .await
+            .map_err(|err| AppError::Internal(Box::new(err)))?;
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Offscreen Frame"),
+            size: wgpu::Extent3d {
+                width: size.width,
+                height: size.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Offscreen Readback"),
+            size: (padded_row_len(size.width) * size.height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let scene = Scene::new(&device, FORMAT, size);
+
+        Ok(Self {
+            device,
+            queue,
+            size,
+            texture,
+            buffer,
+            scene,
+            world: World::new(size),
+        })
+    }
+
+    /// Turns the CRT effect, or the shader loaded with
+    /// [`OffscreenRenderer::load_shader`], on or off.
+    pub fn set_post_processing(&mut self, enabled: bool) {
+        self.scene.set_post_processing(&self.device, enabled);
+    }
+
+    /// Compiles the WGSL post-processing shader at `path` and turns it on.
+    pub fn load_shader(&mut self, path: &Path) -> AppResult<()> {
+        self.scene.load_shader(&self.device, path)
+    }
+
+    pub fn set_bezel(&mut self, bezel: &Bezel) {
+        self.scene
+            .set_bezel(&self.device, &self.queue, &mut self.world, bezel);
+    }
+
+    pub fn set_color_correction(&mut self, correction: ColorCorrection) {
+        self.scene.set_color_correction(&self.queue, correction);
+    }
+
+    /// Draws `screen` and reads the frame back. `time` is the number of
+    /// seconds passed to animated post-processing shaders, fixed so the
+    /// same frame can be rendered again.
+    pub fn render(&mut self, screen: &mut Screen, time: f32) -> AppResult<RgbaImage> {
+        self.world.set_screen(screen);
+        self.scene.update(&self.queue, &self.world);
+        self.scene.prepare(&self.queue, &mut self.world);
+
+        let view = self
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Offscreen Encoder"),
+            });
+        self.scene
+            .draw(&self.queue, &mut encoder, &self.world, &view, time);
+        let padded_row_len = padded_row_len(self.size.width);
+        encoder.copy_texture_to_buffer(
+            self.texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &self.buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row_len),
+                    rows_per_image: None,
+                },
+            },
+            self.texture.size(),
+        );
+        self.queue.submit(Some(encoder.finish()));
+
+        let slice = self.buffer.slice(..);
+        let (sender, receiver) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        receiver
+            .recv()
+            .map_err(|err| AppError::Internal(Box::new(err)))?
+            .map_err(|err| AppError::Internal(Box::new(err)))?;
+
+        let row_len = (self.size.width * BYTES_PER_PIXEL) as usize;
+        let pixels = slice
+            .get_mapped_range()
+            .chunks_exact(padded_row_len as usize)
+            .flat_map(|row| &row[..row_len])
+            .copied()
+            .collect();
+        self.buffer.unmap();
+        Ok(
+            RgbaImage::from_raw(self.size.width, self.size.height, pixels)
+                .expect("the frame is as large as the image"),
+        )
+    }
+}
+
+/// Bytes of a row of `width` pixels in the readback buffer.
+fn padded_row_len(width: u32) -> u32 {
+    let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    (width * BYTES_PER_PIXEL).div_ceil(alignment) * alignment
+}
@@ -0,0 +1,639 @@
+//! Debugging panels drawn with egui on top of the emulated screen, along
+//! with the on-screen keypad, the pause indicator, status messages and errors.
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use egui::{Align2, Color32, FontId, RichText, TextureHandle, TextureOptions};
+use winit::{event::WindowEvent, window::Window};
+
+use crate::{
+    debugger::call_stack, instruction::Instruction, state::MachineState, symbols::Symbols,
+};
+
+/// Bytes per row of the memory panel.
+const MEMORY_ROW_LEN: usize = 16;
+/// Instructions listed before and after PC in the disassembly panel.
+const DISASSEMBLY_BEFORE: usize = 8;
+const DISASSEMBLY_AFTER: usize = 16;
+const PC_COLOR: Color32 = Color32::from_rgb(0x30, 0x80, 0x30);
+const I_COLOR: Color32 = Color32::from_rgb(0x30, 0x50, 0xA0);
+const ERROR_COLOR: Color32 = Color32::from_rgb(0xFF, 0x80, 0x80);
+/// Size of a thumbnail pixel in the slot picker.
+const THUMBNAIL_SCALE: f32 = 2.0;
+/// How long a message from [`Overlay::show_status`] stays up.
+const STATUS_DURATION: Duration = Duration::from_secs(2);
+/// Opacity of the black drawn over the screen while paused, and behind text.
+const PAUSED_DIM: u8 = 160;
+/// How often the FPS and IPS counters are updated.
+const STATS_INTERVAL: Duration = Duration::from_secs(1);
+/// CHIP-8 keys of the on-screen keypad row by row, arranged like the
+/// COSMAC VIP's hex keypad.
+const KEYPAD_LAYOUT: [usize; 16] = [
+    0x1, 0x2, 0x3, 0xC, 0x4, 0x5, 0x6, 0xD, 0x7, 0x8, 0x9, 0xE, 0xA, 0x0, 0xB, 0xF,
+];
+/// Width and height of an on-screen keypad button, big enough for a finger.
+const KEYPAD_BUTTON_SIZE: f32 = 56.0;
+
+/// Panels that can be shown on the overlay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Panel {
+    /// Hex dump of memory, with the bytes at PC and I highlighted.
+    Memory,
+    /// Registers, timers and the call stack.
+    Registers,
+    /// Decoded instructions around PC.
+    Disassembly,
+    /// Save state slots with their thumbnails, see [`Overlay::set_slots`].
+    Slots,
+    /// Frames drawn and instructions emulated per second, see [`Overlay::count_frame`].
+    Stats,
+    /// Buttons for the CHIP-8 keys, to play on touchscreens, see [`Overlay::touched_keys`].
+    Keypad,
+}
+
+/// Save state slot shown in the slot picker.
+pub struct SlotPreview {
+    pub saved: bool,
+    /// Screen at the time of saving, if the state has a thumbnail.
+    pub thumbnail: Option<egui::ColorImage>,
+}
+
+/// Slot with its thumbnail uploaded.
+struct Slot {
+    saved: bool,
+    thumbnail: Option<TextureHandle>,
+}
+
+/// Rates shown by [`Panel::Stats`], measured over [`STATS_INTERVAL`].
+struct Stats {
+    since: Instant,
+    /// Frames counted since `since`.
+    frames: u32,
+    /// [`Chip8::executed`](crate::chip::Chip8::executed) at `since`.
+    executed: u64,
+    /// Rates over the last interval, `None` until the first one ends.
+    fps: Option<f64>,
+    ips: Option<f64>,
+}
+
+impl Stats {
+    fn new(executed: u64) -> Self {
+        Self {
+            since: Instant::now(),
+            frames: 0,
+            executed,
+            fps: None,
+            ips: None,
+        }
+    }
+}
+
+pub struct Overlay {
+    context: egui::Context,
+    state: egui_winit::State,
+    /// Created when first rendering, for the target's format.
+    renderer: Option<egui_wgpu::Renderer>,
+    show_memory: bool,
+    show_registers: bool,
+    show_disassembly: bool,
+    show_slots: bool,
+    show_stats: bool,
+    show_keypad: bool,
+    /// Counted since the stats were shown, `None` until the first frame.
+    stats: Option<Stats>,
+    slots: Vec<Slot>,
+    /// Slot clicked in the slot picker, until taken with [`Overlay::take_picked_slot`].
+    picked_slot: Option<usize>,
+    /// CHIP-8 keys held down on the on-screen keypad, a bit per key.
+    touched_keys: u16,
+    symbols: Arc<Symbols>,
+    paused: bool,
+    /// Message shown at the bottom of the window, with when it was shown.
+    status: Option<(String, Instant)>,
+    /// Error shown at the top of the window until cleared.
+    error: Option<String>,
+    /// Whether the pause indicator, the status message or the error changed
+    /// since the last [`Overlay::needs_redraw`].
+    changed: bool,
+}
+
+impl Overlay {
+    /// Overlay for `window`, with textures up to `max_texture_side` pixels
+    /// wide if the GPU limits them.
+    pub fn new(window: &Window, max_texture_side: Option<usize>) -> Self {
+        let context = egui::Context::default();
+        let state = egui_winit::State::new(
+            context.clone(),
+            egui::ViewportId::ROOT,
+            window,
+            Some(window.scale_factor() as f32),
+            max_texture_side,
+        );
+        Self {
+            context,
+            state,
+            renderer: None,
+            show_memory: false,
+            show_registers: false,
+            show_disassembly: false,
+            show_slots: false,
+            show_stats: false,
+            show_keypad: false,
+            stats: None,
+            slots: Vec::new(),
+            picked_slot: None,
+            touched_keys: 0,
+            symbols: Arc::new(Symbols::new()),
+            paused: false,
+            status: None,
+            error: None,
+            changed: false,
+        }
+    }
+
+    /// Dims the screen and shows "PAUSED" over it while `paused`.
+    pub fn set_paused(&mut self, paused: bool) {
+        if paused != self.paused {
+            self.paused = paused;
+            self.changed = true;
+        }
+    }
+
+    /// Shows `text` at the bottom of the window for a couple of seconds.
+    pub fn show_status(&mut self, text: impl Into<String>) {
+        self.status = Some((text.into(), Instant::now()));
+        self.changed = true;
+    }
+
+    /// Shows `error` at the top of the window until replaced or cleared with `None`.
+    pub fn set_error(&mut self, error: Option<String>) {
+        if error != self.error {
+            self.error = error;
+            self.changed = true;
+        }
+    }
+
+    /// Labels shown in the disassembly and call stack.
+    pub fn set_symbols(&mut self, symbols: Arc<Symbols>) {
+        self.symbols = symbols;
+    }
+
+    /// Slots listed by the slot picker, numbered from 1.
+    pub fn set_slots(&mut self, slots: Vec<SlotPreview>) {
+        self.slots = slots
+            .into_iter()
+            .enumerate()
+            .map(|(index, slot)| Slot {
+                saved: slot.saved,
+                thumbnail: slot.thumbnail.map(|image| {
+                    self.context.load_texture(
+                        format!("slot {}", index + 1),
+                        image,
+                        TextureOptions::NEAREST,
+                    )
+                }),
+            })
+            .collect();
+    }
+
+    /// The slot picked to be loaded in the slot picker, if any.
+    pub fn take_picked_slot(&mut self) -> Option<usize> {
+        self.picked_slot.take()
+    }
+
+    /// CHIP-8 keys held down on the on-screen keypad when last rendered, as
+    /// a mask like [`Keypad::mask`](crate::keypad::Keypad::mask).
+    pub fn touched_keys(&self) -> u16 {
+        self.touched_keys
+    }
+
+    /// Whether any panel is shown.
+    pub fn is_visible(&self) -> bool {
+        self.show_memory
+            || self.show_registers
+            || self.show_disassembly
+            || self.show_slots
+            || self.show_stats
+            || self.show_keypad
+    }
+
+    /// Whether anything is drawn by [`Overlay::render`].
+    pub fn has_content(&self) -> bool {
+        self.is_visible() || self.paused || self.status.is_some() || self.error.is_some()
+    }
+
+    /// Whether the overlay has to be drawn again even if the screen is
+    /// unchanged: while panels or a status message are shown, which can
+    /// change every frame, and once after pausing or unpausing.
+    pub fn needs_redraw(&mut self) -> bool {
+        let changed = std::mem::take(&mut self.changed);
+        changed || self.is_visible() || self.status.is_some()
+    }
+
+    /// Whether a panel showing the machine state is shown. The state only
+    /// needs to be provided to [`Overlay::render`] while this is true.
+    pub fn shows_machine(&self) -> bool {
+        self.show_memory || self.show_registers || self.show_disassembly
+    }
+
+    pub fn is_shown(&self, panel: Panel) -> bool {
+        match panel {
+            Panel::Memory => self.show_memory,
+            Panel::Registers => self.show_registers,
+            Panel::Disassembly => self.show_disassembly,
+            Panel::Slots => self.show_slots,
+            Panel::Stats => self.show_stats,
+            Panel::Keypad => self.show_keypad,
+        }
+    }
+
+    pub fn toggle(&mut self, panel: Panel) {
+        match panel {
+            Panel::Memory => self.show_memory = !self.show_memory,
+            Panel::Registers => self.show_registers = !self.show_registers,
+            Panel::Disassembly => self.show_disassembly = !self.show_disassembly,
+            Panel::Slots => self.show_slots = !self.show_slots,
+            Panel::Stats => {
+                self.show_stats = !self.show_stats;
+                self.stats = None;
+            }
+            Panel::Keypad => {
+                self.show_keypad = !self.show_keypad;
+                self.touched_keys = 0;
+            }
+        }
+    }
+
+    /// Counts a frame drawn, with the machine having executed `executed`
+    /// instructions so far, see [`Chip8::executed`](crate::chip::Chip8::executed).
+    pub fn count_frame(&mut self, executed: u64) {
+        if !self.show_stats {
+            return;
+        }
+        let stats = self.stats.get_or_insert_with(|| Stats::new(executed));
+        stats.frames += 1;
+        let elapsed = stats.since.elapsed();
+        if elapsed >= STATS_INTERVAL {
+            let seconds = elapsed.as_secs_f64();
+            stats.fps = Some(stats.frames as f64 / seconds);
+            stats.ips = Some(executed.wrapping_sub(stats.executed) as f64 / seconds);
+            stats.since = Instant::now();
+            stats.frames = 0;
+            stats.executed = executed;
+        }
+    }
+
+    /// Passes a window event to the panels. Returns whether they consumed it.
+    pub fn handle_event(&mut self, window: &Window, event: &WindowEvent) -> bool {
+        self.is_visible() && self.state.on_window_event(window, event).consumed
+    }
+
+    /// Draws the visible panels, the pause indicator, the status message and
+    /// the error onto `view`, on top of what is already there.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render(
+        &mut self,
+        window: &Window,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        format: wgpu::TextureFormat,
+        size: [u32; 2],
+        machine: Option<&MachineState>,
+    ) {
+        if self
+            .status
+            .as_ref()
+            .is_some_and(|(_, shown)| shown.elapsed() >= STATUS_DURATION)
+        {
+            self.status = None;
+        }
+        let input = self.state.take_egui_input(window);
+        let output = self.context.run(input, |context| {
+            // Below the panels, which stay usable while paused.
+            let painter = context.layer_painter(egui::LayerId::background());
+            let rect = context.screen_rect();
+            if self.paused {
+                painter.rect_filled(rect, 0.0, Color32::from_black_alpha(PAUSED_DIM));
+                painter.text(
+                    rect.center(),
+                    Align2::CENTER_CENTER,
+                    "PAUSED",
+                    FontId::proportional(48.0),
+                    Color32::WHITE,
+                );
+            }
+            if let Some((text, _)) = &self.status {
+                let corner = rect.left_bottom() + egui::vec2(12.0, -12.0);
+                let text = painter.layout_no_wrap(
+                    text.clone(),
+                    FontId::proportional(18.0),
+                    Color32::WHITE,
+                );
+                text_box(&painter, corner, Align2::LEFT_BOTTOM, text);
+            }
+            if let Some(error) = &self.error {
+                let corner = rect.left_top() + egui::vec2(12.0, 12.0);
+                let text = painter.layout(
+                    error.clone(),
+                    FontId::monospace(14.0),
+                    ERROR_COLOR,
+                    rect.width() - 36.0,
+                );
+                text_box(&painter, corner, Align2::LEFT_TOP, text);
+            }
+            if self.show_stats {
+                let format =
+                    |rate: Option<f64>| rate.map_or("-".to_owned(), |rate| format!("{:.0}", rate));
+                let (fps, ips) = self
+                    .stats
+                    .as_ref()
+                    .map_or((None, None), |stats| (stats.fps, stats.ips));
+                let text = format!("{} FPS\n{} IPS", format(fps), format(ips));
+                let corner = rect.right_top() + egui::vec2(-12.0, 12.0);
+                let text = painter.layout_no_wrap(text, FontId::proportional(18.0), Color32::WHITE);
+                text_box(&painter, corner, Align2::RIGHT_TOP, text);
+            }
+            if let Some(machine) = machine {
+                if self.show_memory {
+                    memory_panel(context, machine);
+                }
+                if self.show_registers {
+                    registers_panel(context, machine, &self.symbols);
+                }
+                if self.show_disassembly {
+                    disassembly_panel(context, machine, &self.symbols);
+                }
+            }
+            if self.show_slots {
+                slots_panel(context, &self.slots, &mut self.picked_slot);
+            }
+            if self.show_keypad {
+                self.touched_keys = keypad_panel(context);
+            }
+        });
+        self.state
+            .handle_platform_output(window, output.platform_output);
+
+        let screen = egui_wgpu::ScreenDescriptor {
+            size_in_pixels: size,
+            pixels_per_point: output.pixels_per_point,
+        };
+        let primitives = self
+            .context
+            .tessellate(output.shapes, output.pixels_per_point);
+        let renderer = self
+            .renderer
+            .get_or_insert_with(|| egui_wgpu::Renderer::new(device, format, None, 1));
+        for (id, delta) in &output.textures_delta.set {
+            renderer.update_texture(device, queue, *id, delta);
+        }
+        let commands = renderer.update_buffers(device, queue, encoder, &primitives, &screen);
+        queue.submit(commands);
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Overlay Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            renderer.render(&mut render_pass, &primitives, &screen);
+        }
+        for id in &output.textures_delta.free {
+            renderer.free_texture(id);
+        }
+    }
+}
+
+/// Draws `text` on a dark background, placed at `corner` as `align` says.
+fn text_box(painter: &egui::Painter, corner: egui::Pos2, align: Align2, text: Arc<egui::Galley>) {
+    let position = align.anchor_size(corner, text.size()).min;
+    let background = egui::Rect::from_min_size(position, text.size()).expand(6.0);
+    painter.rect_filled(background, 4.0, Color32::from_black_alpha(PAUSED_DIM));
+    painter.galley(position, text, Color32::WHITE);
+}
+
+fn memory_panel(context: &egui::Context, machine: &MachineState) {
+    egui::Window::new("Memory")
+        .default_pos([16.0, 16.0])
+        .default_height(320.0)
+        .show(context, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(RichText::new(" PC ").monospace().background_color(PC_COLOR));
+                ui.label(RichText::new(" I ").monospace().background_color(I_COLOR));
+            });
+            ui.separator();
+
+            let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+            let rows = machine.memory.len().div_ceil(MEMORY_ROW_LEN);
+            egui::ScrollArea::vertical().show_rows(ui, row_height, rows, |ui, range| {
+                ui.spacing_mut().item_spacing.x = 4.0;
+                for row in range {
+                    let start = row * MEMORY_ROW_LEN;
+                    let end = (start + MEMORY_ROW_LEN).min(machine.memory.len());
+                    ui.horizontal(|ui| {
+                        ui.label(RichText::new(format!("{:04X}", start)).monospace().weak());
+                        for address in start..end {
+                            let mut text =
+                                RichText::new(format!("{:02X}", machine.memory[address]))
+                                    .monospace();
+                            let pc = machine.pc as usize;
+                            if address == pc || address == pc + 1 {
+                                text = text.background_color(PC_COLOR);
+                            } else if address == machine.register_i as usize {
+                                text = text.background_color(I_COLOR);
+                            }
+                            ui.label(text);
+                        }
+                    });
+                }
+            });
+        });
+}
+
+fn slots_panel(context: &egui::Context, slots: &[Slot], picked: &mut Option<usize>) {
+    egui::Window::new("Save states")
+        .default_pos([16.0, 360.0])
+        .resizable(false)
+        .show(context, |ui| {
+            ui.label(RichText::new("Shift+F1-F4 saves, Ctrl+F1-F4 or a click loads").weak());
+            ui.horizontal(|ui| {
+                for (index, slot) in slots.iter().enumerate() {
+                    ui.vertical(|ui| {
+                        ui.label(format!("Slot {}", index + 1));
+                        match &slot.thumbnail {
+                            Some(texture) => {
+                                let image = egui::Image::from_texture(texture)
+                                    .fit_to_exact_size(texture.size_vec2() * THUMBNAIL_SCALE)
+                                    .sense(egui::Sense::click());
+                                if ui.add(image).clicked() {
+                                    *picked = Some(index + 1);
+                                }
+                            }
+                            None if slot.saved => {
+                                if ui.button("Load").clicked() {
+                                    *picked = Some(index + 1);
+                                }
+                            }
+                            None => {
+                                ui.label(RichText::new("empty").weak());
+                            }
+                        }
+                    });
+                }
+            });
+        });
+}
+
+/// Shows the on-screen keypad, returning the keys held down on it.
+fn keypad_panel(context: &egui::Context) -> u16 {
+    let mut touched = 0;
+    egui::Window::new("Keypad")
+        .anchor(Align2::RIGHT_BOTTOM, [-16.0, -16.0])
+        .resizable(false)
+        .show(context, |ui| {
+            egui::Grid::new("keypad").show(ui, |ui| {
+                for row in KEYPAD_LAYOUT.chunks(4) {
+                    for &key in row {
+                        let label = RichText::new(format!("{:X}", key)).monospace().size(24.0);
+                        let button = egui::Button::new(label)
+                            .min_size(egui::vec2(KEYPAD_BUTTON_SIZE, KEYPAD_BUTTON_SIZE));
+                        // Held for as long as the finger or mouse is down on it.
+                        if ui.add(button).is_pointer_button_down_on() {
+                            touched |= 1 << key;
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+    touched
+}
+
+fn registers_panel(context: &egui::Context, machine: &MachineState, symbols: &Symbols) {
+    egui::Window::new("Registers")
+        .default_pos([480.0, 16.0])
+        .resizable(false)
+        .show(context, |ui| {
+            egui::Grid::new("registers").striped(true).show(ui, |ui| {
+                for (row, registers) in machine.registers.chunks(4).enumerate() {
+                    for (column, value) in registers.iter().enumerate() {
+                        ui.label(
+                            RichText::new(format!("V{:X}", row * 4 + column))
+                                .monospace()
+                                .weak(),
+                        );
+                        ui.label(RichText::new(format!("{:02X}", value)).monospace());
+                    }
+                    ui.end_row();
+                }
+            });
+            ui.separator();
+
+            egui::Grid::new("special registers").show(ui, |ui| {
+                let special = [
+                    ("PC", format!("{:04X}", machine.pc)),
+                    ("I", format!("{:04X}", machine.register_i)),
+                    ("SP", format!("{}", machine.sp)),
+                    ("DT", format!("{:02X}", machine.delay_timer)),
+                    ("ST", format!("{:02X}", machine.sound_timer)),
+                ];
+                for (name, value) in special {
+                    ui.label(RichText::new(name).monospace().weak());
+                    ui.label(RichText::new(value).monospace());
+                    ui.end_row();
+                }
+            });
+            ui.separator();
+
+            ui.label("Call stack");
+            let sp = machine.sp as usize;
+            let frames = call_stack(&machine.stack[..sp], &machine.call_entries[..sp]);
+            if frames.is_empty() {
+                ui.label(RichText::new("empty").weak());
+            }
+            egui::Grid::new("call stack").striped(true).show(ui, |ui| {
+                // Most recent call first, so the current subroutine is on top.
+                for (depth, frame) in frames.iter().enumerate().rev() {
+                    ui.label(RichText::new(format!("{:2}", depth)).monospace().weak());
+                    let entry = symbols
+                        .name(frame.entry)
+                        .map_or_else(|| format!("{:04X}", frame.entry), str::to_owned);
+                    let mut text = RichText::new(entry).monospace();
+                    if depth + 1 == frames.len() {
+                        text = text.background_color(PC_COLOR);
+                    }
+                    ui.label(text);
+                    ui.label(
+                        RichText::new(format!("from {}", location(symbols, frame.caller)))
+                            .monospace()
+                            .weak(),
+                    );
+                    ui.end_row();
+                }
+            });
+        });
+}
+
+fn disassembly_panel(context: &egui::Context, machine: &MachineState, symbols: &Symbols) {
+    egui::Window::new("Disassembly")
+        .default_pos([480.0, 320.0])
+        .resizable(false)
+        .show(context, |ui| {
+            // Instructions are not aligned in general, so decoding starts a fixed
+            // number of words before PC and may show data as instructions there.
+            let pc = machine.pc as usize;
+            let mut address = pc.saturating_sub(DISASSEMBLY_BEFORE * 2);
+            let mut after = 0;
+            while address < machine.memory.len() && after <= DISASSEMBLY_AFTER {
+                let instruction = Instruction::decode_memory(&machine.memory, address);
+                let size = instruction.map_or(2, |instruction| instruction.size() as usize);
+                let bytes = machine.memory[address..(address + size).min(machine.memory.len())]
+                    .iter()
+                    .map(|byte| format!("{:02X}", byte))
+                    .collect::<String>();
+                let mnemonic = match instruction {
+                    Some(instruction) => match symbols.operand_name(instruction) {
+                        Some(name) => format!("{:<16}; {}", instruction, name),
+                        None => instruction.to_string(),
+                    },
+                    None => "???".to_owned(),
+                };
+                if let Some(name) = symbols.name(address as u16) {
+                    ui.label(RichText::new(format!(": {}", name)).monospace().strong());
+                }
+                let mut text =
+                    RichText::new(format!("{:04X}  {:<8}  {}", address, bytes, mnemonic))
+                        .monospace();
+                if address == pc {
+                    text = text.background_color(PC_COLOR);
+                }
+                ui.label(text);
+                if address >= pc {
+                    after += 1;
+                }
+                address += size;
+            }
+        });
+}
+
+/// `address` relative to the nearest label, or in hexadecimal without symbols.
+fn location(symbols: &Symbols, address: u16) -> String {
+    if symbols.is_empty() {
+        format!("{:04X}", address)
+    } else {
+        symbols.describe(address)
+    }
+}
@@ -0,0 +1,135 @@
+//! Built-in color palettes for the screen.
+//!
+//! Without a palette, lit pixels are drawn with the default shading on black,
+//! and those of the second XO-CHIP plane in the grays of
+//! [`DEFAULT_PLANE_COLORS`]. CHIP-8X programs choose their own colors and
+//! ignore palettes.
+
+use std::{fmt, str::FromStr};
+
+use crate::screen::DEFAULT_PLANE_COLORS;
+
+/// Colors as `0xAARRGGBB`, indexed by the plane bits of a pixel: the
+/// background, the first plane, the second XO-CHIP plane and both planes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    pub name: &'static str,
+    pub colors: [u32; 4],
+}
+
+/// Palettes selectable with `--palette`, in the order they are cycled through.
+pub const PALETTES: [Palette; 5] = [
+    Palette {
+        name: "octo",
+        colors: [0xFF996600, 0xFFFFCC00, 0xFFFF6600, 0xFF662200],
+    },
+    Palette {
+        name: "gameboy",
+        colors: [0xFF9BBC0F, 0xFF0F380F, 0xFF306230, 0xFF8BAC0F],
+    },
+    Palette {
+        name: "amber",
+        colors: [0xFF000000, 0xFFFFB000, 0xFF805800, 0xFFFFD880],
+    },
+    Palette {
+        name: "paperwhite",
+        colors: [0xFFF0F0E8, 0xFF202020, 0xFF808080, 0xFF505050],
+    },
+    Palette {
+        name: "inverted",
+        colors: [0xFFFFFFFF, 0xFF000000, 0xFF555555, 0xFFAAAAAA],
+    },
+];
+
+impl Palette {
+    /// Palette of the user's own colors, from `--plane-colors` or the settings.
+    pub fn custom(colors: [u32; 4]) -> Self {
+        Self {
+            name: "custom",
+            colors,
+        }
+    }
+
+    pub fn background(&self) -> u32 {
+        self.colors[0]
+    }
+
+    /// Color of a pixel with the plane bits `pixel`.
+    pub fn color(&self, pixel: u8) -> u32 {
+        self.colors[(pixel & 0b11) as usize]
+    }
+
+    /// Palette following `current` when cycling: the built-in ones in order,
+    /// the `custom` one if there is one, then the default shading.
+    pub fn next(current: Option<Palette>, custom: Option<Palette>) -> Option<Palette> {
+        let mut palettes = PALETTES.iter().copied().chain(custom);
+        match current {
+            None => palettes.next(),
+            Some(current) => palettes
+                .skip_while(|palette| palette.name != current.name)
+                .nth(1),
+        }
+    }
+
+    /// Colors of every plane combination, the default ones for `None`.
+    pub fn plane_colors(palette: Option<Palette>) -> [u32; 4] {
+        palette.map_or(DEFAULT_PLANE_COLORS, |palette| palette.colors)
+    }
+}
+
+/// Parses an `RRGGBB` color, optionally prefixed with `#`, as `0xAARRGGBB`.
+pub fn parse_color(s: &str) -> Result<u32, String> {
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    if hex.len() != 6 {
+        return Err(format!("expected a color like #FFCC00, got {:?}", s));
+    }
+    u32::from_str_radix(hex, 16)
+        .map(|rgb| 0xFF000000 | rgb)
+        .map_err(|_| format!("expected a color like #FFCC00, got {:?}", s))
+}
+
+/// Parses the four colors of [`Palette::colors`] separated by commas, such as
+/// `#996600,#FFCC00,#FF6600,#662200`.
+pub fn parse_plane_colors(s: &str) -> Result<[u32; 4], String> {
+    let colors = s
+        .split(',')
+        .map(|color| parse_color(color.trim()))
+        .collect::<Result<Vec<_>, _>>()?;
+    colors.try_into().map_err(|colors: Vec<u32>| {
+        format!(
+            "expected 4 colors (background, plane 1, plane 2 and both), got {}",
+            colors.len()
+        )
+    })
+}
+
+/// Formats colors as read by [`parse_plane_colors`].
+pub fn format_plane_colors(colors: &[u32; 4]) -> String {
+    let colors: Vec<_> = colors
+        .iter()
+        .map(|color| format!("#{:06X}", color & 0xFFFFFF))
+        .collect();
+    colors.join(",")
+}
+
+impl FromStr for Palette {
+    type Err = String;
+
+    /// Parses the name of a built-in palette.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PALETTES
+            .iter()
+            .find(|palette| palette.name == s)
+            .copied()
+            .ok_or_else(|| {
+                let names: Vec<_> = PALETTES.iter().map(|palette| palette.name).collect();
+                format!("expected one of {}", names.join(", "))
+            })
+    }
+}
+
+impl fmt::Display for Palette {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name)
+    }
+}
@@ -0,0 +1,50 @@
+//! Plain text PBM (`P1`) images of the framebuffer, as written by headless runs.
+
+use crate::screen::Screen;
+
+/// Encodes the screen as a plain text PBM image, with lit pixels black.
+pub fn encode(screen: &Screen) -> String {
+    let mut pbm = format!("P1\n{} {}\n", screen.width(), screen.height());
+    for row in screen.rows() {
+        for &pixel in row {
+            pbm.push(if pixel != 0 { '1' } else { '0' });
+        }
+        pbm.push('\n');
+    }
+    pbm
+}
+
+/// Decodes a plain text PBM image into its width, height and pixels row by
+/// row, or `None` if it is malformed.
+pub fn decode(pbm: &str) -> Option<(usize, usize, Vec<bool>)> {
+    let mut content = pbm
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default())
+        .collect::<Vec<_>>()
+        .join("\n");
+    content = content.trim_start().to_owned();
+    let rest = content.strip_prefix("P1")?;
+    let mut tokens = rest.split_whitespace();
+    let width = tokens.next()?.parse().ok()?;
+    let height = tokens.next()?.parse().ok()?;
+    let pixels = tokens
+        .flat_map(str::chars)
+        .map(|c| match c {
+            '0' => Some(false),
+            '1' => Some(true),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    (pixels.len() == width * height).then_some((width, height, pixels))
+}
+
+/// Whether the screen shows exactly the picture of a decoded PBM image.
+pub fn matches(screen: &Screen, width: usize, height: usize, pixels: &[bool]) -> bool {
+    screen.width() == width
+        && screen.height() == height
+        && screen
+            .rows()
+            .flatten()
+            .zip(pixels)
+            .all(|(&pixel, &expected)| (pixel != 0) == expected)
+}
@@ -0,0 +1,275 @@
+//! Post-processing pass applying an effect to the whole screen, such as the
+//! built-in CRT look (scanlines, slightly curved glass and darker corners)
+//! or a custom WGSL shader.
+//!
+//! The screen is drawn into an offscreen texture the size of the window,
+//! which this pass then draws to the window through the effect's shader.
+//! Every shader is prefixed with `shaders/postprocess.wgsl`, declaring the
+//! bindings and the vertex stage, and only defines `fs_main`.
+
+use std::path::Path;
+
+use bytemuck::{Pod, Zeroable};
+use winit::dpi::PhysicalSize;
+
+use crate::error::{AppError, AppResult};
+
+const PRELUDE: &str = include_str!("shaders/postprocess.wgsl");
+const CRT_SHADER: &str = include_str!("shaders/crt.wgsl");
+
+/// Uniforms of the `globals` binding.
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct Globals {
+    time: f32,
+    _padding: f32,
+    resolution: [f32; 2],
+}
+
+pub struct PostProcess {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    globals_buffer: wgpu::Buffer,
+    format: wgpu::TextureFormat,
+    size: PhysicalSize<u32>,
+    /// Screen drawn in the current frame.
+    view: wgpu::TextureView,
+    bind_group: wgpu::BindGroup,
+    animated: bool,
+}
+
+impl PostProcess {
+    /// The CRT effect, drawing to targets of `format` from a frame of `size`.
+    pub fn crt(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        size: PhysicalSize<u32>,
+    ) -> Self {
+        Self::new(device, format, size, CRT_SHADER, false).expect("the CRT shader is valid")
+    }
+
+    /// Effect of the WGSL shader at `path`. Custom shaders may depend on
+    /// `globals.time`, so they are redrawn every frame.
+    pub fn load(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        size: PhysicalSize<u32>,
+        path: &Path,
+    ) -> AppResult<Self> {
+        let source = std::fs::read_to_string(path)?;
+        Self::new(device, format, size, &source, true)
+            .map_err(|err| AppError::InvalidArgument(format!("shader {}: {}", path.display(), err)))
+    }
+
+    fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        size: PhysicalSize<u32>,
+        source: &str,
+        animated: bool,
+    ) -> Result<Self, wgpu::Error> {
+        // Report mistakes in the shader as errors, rather than panicking.
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Post-processing Shader"),
+            source: wgpu::ShaderSource::Wgsl(format!("{}\n{}", PRELUDE, source).into()),
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Post-processing Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Post-processing Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Post-processing Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                compilation_options: Default::default(),
+                targets: &[Some(format.into())],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+        if let Some(err) = pollster::block_on(device.pop_error_scope()) {
+            return Err(err);
+        }
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Post-processing Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let globals_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Post-processing Globals"),
+            size: std::mem::size_of::<Globals>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let (view, bind_group) = create_frame(
+            device,
+            &bind_group_layout,
+            &sampler,
+            &globals_buffer,
+            format,
+            size,
+        );
+        Ok(Self {
+            pipeline,
+            bind_group_layout,
+            sampler,
+            globals_buffer,
+            format,
+            size,
+            view,
+            bind_group,
+            animated,
+        })
+    }
+
+    /// Whether the effect changes over time, so it must be redrawn every
+    /// frame even if the screen is unchanged.
+    pub fn is_animated(&self) -> bool {
+        self.animated
+    }
+
+    /// Recreates the frame texture to match a resized window.
+    pub fn resize(&mut self, device: &wgpu::Device, size: PhysicalSize<u32>) {
+        self.size = size;
+        (self.view, self.bind_group) = create_frame(
+            device,
+            &self.bind_group_layout,
+            &self.sampler,
+            &self.globals_buffer,
+            self.format,
+            size,
+        );
+    }
+
+    /// Where to draw the screen before [`PostProcess::render`].
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    /// Draws the frame to `target` with the effect, `time` seconds after the
+    /// window opened.
+    pub fn render(
+        &self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        time: f32,
+    ) {
+        let globals = Globals {
+            time,
+            _padding: 0.0,
+            resolution: [self.size.width as f32, self.size.height as f32],
+        };
+        queue.write_buffer(&self.globals_buffer, 0, bytemuck::cast_slice(&[globals]));
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Post-processing Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+/// Texture of `size` to draw a frame into, and the bind group sampling it.
+fn create_frame(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    sampler: &wgpu::Sampler,
+    globals_buffer: &wgpu::Buffer,
+    format: wgpu::TextureFormat,
+    size: PhysicalSize<u32>,
+) -> (wgpu::TextureView, wgpu::BindGroup) {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Post-processing Frame"),
+        size: wgpu::Extent3d {
+            width: size.width.max(1),
+            height: size.height.max(1),
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Post-processing Bind Group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: globals_buffer.as_entire_binding(),
+            },
+        ],
+    });
+    (view, bind_group)
+}
@@ -0,0 +1,109 @@
+//! Execution counts per address and instruction, for finding the hot spots of a ROM.
+
+use std::{collections::HashMap, fmt::Write};
+
+use crate::{config::XO_CHIP_MEMORY_SIZE, instruction::Instruction};
+
+/// Counts how often every address and every kind of instruction executes.
+#[derive(Debug, Clone)]
+pub struct Profiler {
+    /// Counts by address, as far as the 16-bit program counter reaches.
+    addresses: Vec<u64>,
+    /// Counts by opcode pattern, see [`Instruction::pattern`].
+    instructions: HashMap<&'static str, u64>,
+    total: u64,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self {
+            addresses: vec![0; XO_CHIP_MEMORY_SIZE],
+            instructions: HashMap::new(),
+            total: 0,
+        }
+    }
+
+    /// Counts `instruction` executed at `pc`.
+    pub fn record(&mut self, pc: u16, instruction: Instruction) {
+        self.addresses[pc as usize % XO_CHIP_MEMORY_SIZE] += 1;
+        *self.instructions.entry(instruction.pattern()).or_default() += 1;
+        self.total += 1;
+    }
+
+    /// Number of instructions executed.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// How often the instruction at `address` executed.
+    pub fn executed(&self, address: u16) -> u64 {
+        self.addresses[address as usize % XO_CHIP_MEMORY_SIZE]
+    }
+
+    /// Up to `count` addresses that executed most often, with their counts.
+    pub fn hot_addresses(&self, count: usize) -> Vec<(u16, u64)> {
+        let mut addresses = self
+            .addresses
+            .iter()
+            .enumerate()
+            .filter(|(_, &executed)| executed > 0)
+            .map(|(address, &executed)| (address as u16, executed))
+            .collect::<Vec<_>>();
+        addresses.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        addresses.truncate(count);
+        addresses
+    }
+
+    /// Executed opcode patterns, most frequent first.
+    pub fn hot_instructions(&self) -> Vec<(&'static str, u64)> {
+        let mut instructions = self
+            .instructions
+            .iter()
+            .map(|(&pattern, &executed)| (pattern, executed))
+            .collect::<Vec<_>>();
+        instructions.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        instructions
+    }
+
+    /// Readable report of the `count` hottest addresses, disassembled from
+    /// `memory`, followed by the counts of every opcode pattern.
+    pub fn report(&self, memory: &[u8], count: usize) -> String {
+        let percent = |executed: u64| executed as f64 * 100.0 / self.total.max(1) as f64;
+        let mut text = String::new();
+        let _ = writeln!(text, "Executed {} instructions", self.total);
+
+        let _ = writeln!(text, "\nHottest addresses:");
+        for (address, executed) in self.hot_addresses(count) {
+            let instruction = match Instruction::decode_memory(memory, address as usize) {
+                Some(instruction) => instruction.to_string(),
+                None => "???".to_owned(),
+            };
+            let _ = writeln!(
+                text,
+                "  {:#06X}  {:6.2}%  {:>10}  {}",
+                address,
+                percent(executed),
+                executed,
+                instruction
+            );
+        }
+
+        let _ = writeln!(text, "\nInstructions:");
+        for (pattern, executed) in self.hot_instructions() {
+            let _ = writeln!(
+                text,
+                "  {:<9}  {:6.2}%  {:>10}",
+                pattern,
+                percent(executed),
+                executed
+            );
+        }
+        text
+    }
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
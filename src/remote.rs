@@ -0,0 +1,291 @@
+//! WebSocket server letting external tools, such as browser-based debuggers,
+//! inspect and control a running emulator.
+//!
+//! Clients send JSON commands like `{"command": "step", "count": 10}` and get
+//! a `state` message with the complete [`MachineState`] back after every
+//! command, as well as every [`STATE_INTERVAL`] while connected. Commands that
+//! fail are answered with an `error` message instead.
+
+use std::{
+    io,
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tungstenite::{Message, WebSocket};
+
+use crate::{
+    debugger::{RunTarget, MAX_STEP_COUNT},
+    emulator::Emulator,
+    error::AppResult,
+    state::MachineState,
+};
+
+/// How often the machine state is streamed to clients without being asked.
+pub const STATE_INTERVAL: Duration = Duration::from_millis(100);
+/// How long reading a command may block before the state is streamed.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Connection errors are boxed, as they are large and only end a session.
+type SessionResult = Result<(), Box<tungstenite::Error>>;
+
+/// Requests accepted from clients, tagged by their `command` field.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Command {
+    /// Sends the state right away.
+    State,
+    Pause,
+    Resume,
+    /// Executes `count` instructions, 1 if not given and at most
+    /// [`MAX_STEP_COUNT`].
+    Step {
+        #[serde(default = "default_step_count")]
+        count: usize,
+    },
+    /// Executes one 60 Hz frame.
+    StepFrame,
+    /// Resumes until the program counter reaches `address`.
+    RunTo {
+        address: u16,
+    },
+    /// Resumes until right before the next `Dxyn` or `00E0`.
+    RunToDraw,
+    /// Undoes the last instruction, see [`Emulator::step_back`].
+    StepBack,
+    Reset,
+    /// Writes `bytes` to memory starting at `address`.
+    Poke {
+        address: u16,
+        bytes: Vec<u8>,
+    },
+    AddBreakpoint {
+        address: u16,
+    },
+    RemoveBreakpoint {
+        address: u16,
+    },
+    ClearBreakpoints,
+    /// Watches the inclusive range from `start` to `end`, or only `start`.
+    AddWatchpoint {
+        start: u16,
+        end: Option<u16>,
+    },
+    ClearWatchpoints,
+    /// Enables or disables pausing before every `Dxyn` and `00E0`. Omitted
+    /// fields are left as they are.
+    BreakOn {
+        draw: Option<bool>,
+        clear: Option<bool>,
+    },
+}
+
+fn default_step_count() -> usize {
+    1
+}
+
+/// Messages sent to clients, tagged by their `type` field.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Reply {
+    State {
+        paused: bool,
+        instructions_per_frame: usize,
+        breakpoints: Vec<u16>,
+        machine: Box<MachineState>,
+    },
+    Error {
+        message: String,
+    },
+}
+
+impl Reply {
+    fn state(emulator: &Emulator) -> Self {
+        Self::State {
+            paused: emulator.is_paused(),
+            instructions_per_frame: emulator.instructions_per_frame,
+            breakpoints: emulator.debugger.breakpoints().collect(),
+            machine: Box::new(emulator.chip.snapshot()),
+        }
+    }
+}
+
+/// Listens for WebSocket clients on a background thread, serving each one on
+/// a thread of its own.
+pub struct RemoteServer {
+    local_addr: SocketAddr,
+}
+
+impl RemoteServer {
+    pub fn spawn(addr: impl ToSocketAddrs, emulator: Arc<Mutex<Emulator>>) -> AppResult<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        thread::Builder::new()
+            .name("remote".into())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    let stream = match stream {
+                        Ok(stream) => stream,
+                        Err(err) => {
+                            warn!("Could not accept remote client: {}", err);
+                            continue;
+                        }
+                    };
+                    let emulator = Arc::clone(&emulator);
+                    let _ = thread::Builder::new()
+                        .name("remote client".into())
+                        .spawn(move || serve(stream, emulator));
+                }
+            })?;
+        Ok(Self { local_addr })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+fn serve(stream: TcpStream, emulator: Arc<Mutex<Emulator>>) {
+    let peer = stream
+        .peer_addr()
+        .map_or_else(|_| "unknown".to_owned(), |addr| addr.to_string());
+    let mut socket = match tungstenite::accept(stream) {
+        Ok(socket) => socket,
+        Err(err) => {
+            warn!("Remote client {} failed the handshake: {}", peer, err);
+            return;
+        }
+    };
+    info!("Remote client {} connected", peer);
+    match run_session(&mut socket, &emulator) {
+        Ok(()) => info!("Remote client {} disconnected", peer),
+        Err(err) if matches!(*err, tungstenite::Error::ConnectionClosed) => {
+            info!("Remote client {} disconnected", peer);
+        }
+        Err(err) => warn!("Remote client {} disconnected: {}", peer, err),
+    }
+}
+
+fn run_session(socket: &mut WebSocket<TcpStream>, emulator: &Mutex<Emulator>) -> SessionResult {
+    socket
+        .get_ref()
+        .set_read_timeout(Some(POLL_INTERVAL))
+        .map_err(tungstenite::Error::from)?;
+    let mut last_state = Instant::now();
+    send(socket, &Reply::state(&emulator.lock().unwrap()))?;
+    loop {
+        match socket.read() {
+            Ok(Message::Text(text)) => {
+                let reply = match serde_json::from_str(&text) {
+                    Ok(command) => execute(&mut emulator.lock().unwrap(), command),
+                    Err(err) => Reply::Error {
+                        message: format!("invalid command: {}", err),
+                    },
+                };
+                send(socket, &reply)?;
+                last_state = Instant::now();
+            }
+            Ok(Message::Close(_)) => return Ok(()),
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(err))
+                if matches!(
+                    err.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) => {}
+            Err(err) => return Err(err.into()),
+        }
+        if last_state.elapsed() >= STATE_INTERVAL {
+            send(socket, &Reply::state(&emulator.lock().unwrap()))?;
+            last_state = Instant::now();
+        }
+    }
+}
+
+fn send(socket: &mut WebSocket<TcpStream>, reply: &Reply) -> SessionResult {
+    let text = serde_json::to_string(reply).expect("replies are always serializable");
+    Ok(socket.send(Message::Text(text))?)
+}
+
+/// Applies `command`, answering with the resulting state or what went wrong.
+fn execute(emulator: &mut Emulator, command: Command) -> Reply {
+    let result = match command {
+        Command::State => Ok(()),
+        Command::Pause => {
+            emulator.pause();
+            Ok(())
+        }
+        Command::Resume => {
+            emulator.resume();
+            Ok(())
+        }
+        Command::Step { count } if count > MAX_STEP_COUNT => {
+            Err(format!("at most {} steps at once", MAX_STEP_COUNT))
+        }
+        Command::Step { count } => {
+            (0..count).try_for_each(|_| emulator.step_instruction().map_err(|err| err.to_string()))
+        }
+        Command::RunTo { address } => {
+            emulator.run_to(RunTarget::Address(address));
+            Ok(())
+        }
+        Command::RunToDraw => {
+            emulator.run_to(RunTarget::Draw);
+            Ok(())
+        }
+        Command::StepFrame => emulator.step_frame().map_err(|err| err.to_string()),
+        Command::StepBack => match emulator.step_back() {
+            Ok(true) => Ok(()),
+            Ok(false) => Err("no more history to step back through".to_owned()),
+            Err(err) => Err(err.to_string()),
+        },
+        Command::Reset => {
+            emulator.reset();
+            Ok(())
+        }
+        Command::Poke { address, bytes } => emulator
+            .poke(address as usize, &bytes)
+            .map_err(|err| err.to_string()),
+        Command::AddBreakpoint { address } => {
+            emulator.debugger.add_breakpoint(address);
+            Ok(())
+        }
+        Command::RemoveBreakpoint { address } => {
+            emulator.debugger.remove_breakpoint(address);
+            Ok(())
+        }
+        Command::ClearBreakpoints => {
+            emulator.debugger.clear_breakpoints();
+            Ok(())
+        }
+        Command::AddWatchpoint { start, end } => {
+            let end = end.unwrap_or(start);
+            if start <= end {
+                emulator.debugger.add_watchpoint(start..=end);
+                Ok(())
+            } else {
+                Err("watchpoint starts after its end".to_owned())
+            }
+        }
+        Command::ClearWatchpoints => {
+            emulator.debugger.clear_watchpoints();
+            Ok(())
+        }
+        Command::BreakOn { draw, clear } => {
+            if let Some(draw) = draw {
+                emulator.debugger.set_break_on_draw(draw);
+            }
+            if let Some(clear) = clear {
+                emulator.debugger.set_break_on_clear(clear);
+            }
+            Ok(())
+        }
+    };
+    match result {
+        Ok(()) => Reply::state(emulator),
+        Err(message) => Reply::Error { message },
+    }
+}
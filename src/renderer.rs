@@ -1,42 +1,100 @@
-use bytemuck::{Pod, Zeroable};
-use cgmath::{prelude::*, Vector2, Vector3};
-use log::info;
+use log::warn;
 use std::{
-    borrow::Cow,
     cell::RefCell,
+    path::{Path, PathBuf},
     rc::Rc,
-    time::{Duration, SystemTime},
+    str::FromStr,
+    time::{Duration, Instant, SystemTime},
 };
-use wgpu::{util::DeviceExt, Color, ShaderModule};
 use winit::{
-    dpi::{LogicalSize, PhysicalSize, Size},
-    event::{Event, WindowEvent},
-    event_loop::EventLoop,
-    window::Window,
+    dpi::PhysicalSize,
+    window::{Fullscreen, Window},
 };
 
 use crate::{
-    camera::CameraUniform,
-    mesh::{InstanceData, Mesh, Vertex},
+    bezel::Bezel,
+    emulator::FRAME_DURATION,
+    error::AppResult,
+    overlay::Overlay,
+    scene::{ColorCorrection, Scene},
     world::World,
 };
+#[cfg(all(feature = "softbuffer", not(target_arch = "wasm32")))]
+use crate::{error::AppError, soft::SoftRenderer};
 
-const OPAQUE_SHADER: &str = include_str!("shaders/opaque.wgsl");
+/// How long to wait before checking for a new frame again without vsync.
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// How frames are presented, trading latency for tearing.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PresentMode {
+    /// Wait for vertical sync, without tearing. Supported everywhere.
+    #[default]
+    Fifo,
+    /// Replace the frame queued for the next vertical sync, without tearing
+    /// and with less latency.
+    Mailbox,
+    /// Present at once, with the least latency but possibly tearing.
+    Immediate,
+}
+
+impl FromStr for PresentMode {
+    type Err = &'static str;
+
+    /// Parses `fifo`, `mailbox` or `immediate`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fifo" => Ok(Self::Fifo),
+            "mailbox" => Ok(Self::Mailbox),
+            "immediate" => Ok(Self::Immediate),
+            _ => Err("expected one of `fifo`, `mailbox` or `immediate`"),
+        }
+    }
+}
+
+impl From<PresentMode> for wgpu::PresentMode {
+    fn from(mode: PresentMode) -> Self {
+        match mode {
+            PresentMode::Fifo => Self::Fifo,
+            PresentMode::Mailbox => Self::Mailbox,
+            PresentMode::Immediate => Self::Immediate,
+        }
+    }
+}
 
 pub struct Renderer<'a> {
+    window: &'a Window,
     world: Rc<RefCell<World>>,
-    shader: ShaderModule,
 
-    adapter: wgpu::Adapter,
-    surface: wgpu::Surface<'a>,
-    device: wgpu::Device,
-    queue: wgpu::Queue,
-    config: wgpu::SurfaceConfiguration,
+    backend: Backend<'a>,
     surface_size: winit::dpi::PhysicalSize<u32>,
 
-    square_mesh: Mesh,
-    camera_uniform: CameraUniform,
-    camera_buffer: wgpu::Buffer,
+    /// Whether the last presented frame is out of date regardless of the screen,
+    /// e.g. after a resize.
+    stale: bool,
+    /// When the window opened, for the time passed to post-processing shaders.
+    start: Instant,
+    /// Shader loaded with [`Renderer::load_shader`] and when it was last
+    /// modified, to reload it when it changes.
+    shader: Option<(PathBuf, Option<SystemTime>)>,
+
+    /// Debugging panels drawn on top of the screen.
+    pub overlay: Overlay,
+}
+
+/// What draws the frames.
+#[allow(clippy::large_enum_variant)]
+enum Backend<'a> {
+    Gpu {
+        surface: wgpu::Surface<'a>,
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        config: wgpu::SurfaceConfiguration,
+        scene: Scene,
+    },
+    /// The CPU, without post-processing, bezels or the overlay.
+    #[cfg(all(feature = "softbuffer", not(target_arch = "wasm32")))]
+    Soft(SoftRenderer<'a>),
 }
 
 impl<'a> Renderer<'a> {
@@ -45,6 +103,7 @@ impl<'a> Renderer<'a> {
         // world: World,
         world: Rc<RefCell<World>>,
         surface_size: PhysicalSize<u32>,
+        present_mode: PresentMode,
     ) -> Renderer<'a> {
         let instance = wgpu::Instance::default();
         let surface = instance.create_surface(window).unwrap();
@@ -76,173 +135,304 @@ impl<'a> Renderer<'a> {
         let mut config = surface
             .get_default_config(&adapter, surface_size.width, surface_size.height)
             .unwrap();
+        // Colors are given as sRGB values, which a non-sRGB surface shows as
+        // they are. Which format comes first differs between backends.
+        let formats = surface.get_capabilities(&adapter).formats;
+        if let Some(&format) = formats.iter().find(|format| !format.is_srgb()) {
+            config.format = format;
+        }
+        let supported = surface.get_capabilities(&adapter).present_modes;
+        if supported.contains(&present_mode.into()) {
+            config.present_mode = present_mode.into();
+        } else {
+            warn!(
+                "Present mode {:?} is not supported, using Fifo instead",
+                present_mode
+            );
+            config.present_mode = wgpu::PresentMode::Fifo;
+        }
         surface.configure(&device, &config);
 
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: None,
-            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(OPAQUE_SHADER)),
-        });
-
-        let square_mesh = Mesh::create_square(&device);
-
-        let camera_uniform = CameraUniform::new();
-        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Camera Buffer"),
-            contents: bytemuck::cast_slice(&[camera_uniform]),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
-
-        Self {
-            world,
-            shader,
+        let scene = Scene::new(&device, config.format, surface_size);
+        let overlay = Overlay::new(
+            window,
+            Some(device.limits().max_texture_dimension_2d as usize),
+        );
+        let backend = Backend::Gpu {
             surface,
-            adapter,
             device,
             queue,
             config,
+            scene,
+        };
+        Self::with_backend(window, world, backend, surface_size, overlay)
+    }
+
+    /// Renderer drawing on the CPU with softbuffer, for when there is no
+    /// usable GPU.
+    #[cfg(all(feature = "softbuffer", not(target_arch = "wasm32")))]
+    pub fn create_soft(
+        window: &'a Window,
+        world: Rc<RefCell<World>>,
+        surface_size: PhysicalSize<u32>,
+    ) -> AppResult<Renderer<'a>> {
+        let backend = Backend::Soft(SoftRenderer::new(window, surface_size)?);
+        let overlay = Overlay::new(window, None);
+        Ok(Self::with_backend(
+            window,
+            world,
+            backend,
             surface_size,
-            square_mesh,
-            camera_uniform,
-            camera_buffer,
+            overlay,
+        ))
+    }
+
+    fn with_backend(
+        window: &'a Window,
+        world: Rc<RefCell<World>>,
+        backend: Backend<'a>,
+        surface_size: PhysicalSize<u32>,
+        overlay: Overlay,
+    ) -> Renderer<'a> {
+        Self {
+            window,
+            world,
+            backend,
+            surface_size,
+            stale: true,
+            start: Instant::now(),
+            shader: None,
+            overlay,
         }
     }
 
     pub fn resize(&mut self, new_size: PhysicalSize<u32>) {
         if new_size.width > 0 && new_size.height > 0 {
             self.surface_size = new_size;
-            self.config.width = new_size.width;
-            self.config.height = new_size.height;
-            self.surface.configure(&self.device, &self.config);
+            match &mut self.backend {
+                Backend::Gpu {
+                    surface,
+                    device,
+                    config,
+                    scene,
+                    ..
+                } => {
+                    config.width = new_size.width;
+                    config.height = new_size.height;
+                    surface.configure(device, config);
+                    scene.resize(device, new_size);
+                }
+                #[cfg(all(feature = "softbuffer", not(target_arch = "wasm32")))]
+                Backend::Soft(soft) => {
+                    if let Err(err) = soft.resize(new_size) {
+                        warn!("Could not resize the window buffer: {}", err);
+                    }
+                }
+            }
+            self.stale = true;
 
             self.world.borrow_mut().resize(new_size);
         }
     }
 
     pub fn update(&mut self) {
-        self.camera_uniform.update(&self.world.borrow().camera);
-        self.queue.write_buffer(
-            &self.camera_buffer,
-            0,
-            bytemuck::cast_slice(&[self.camera_uniform]),
-        );
+        match &mut self.backend {
+            Backend::Gpu { queue, scene, .. } => scene.update(queue, &self.world.borrow()),
+            #[cfg(all(feature = "softbuffer", not(target_arch = "wasm32")))]
+            Backend::Soft(_) => {}
+        }
     }
 
-    pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        let instances = self.world.borrow().get_instances();
-        let instance_buffer = self
-            .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Instance Buffer"),
-                contents: bytemuck::cast_slice(&instances),
-                usage: wgpu::BufferUsages::VERTEX,
-            });
-
-        let camera_bind_group_layout =
-            self.device
-                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                    label: Some("Camera Bind Group Layout"),
-                    entries: &[wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::VERTEX,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Uniform,
-                            has_dynamic_offset: false,
-                            min_binding_size: None,
-                        },
-                        count: None,
-                    }],
-                });
-        let camera_bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Camera Bind Group"),
-            layout: &camera_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: self.camera_buffer.as_entire_binding(),
-            }],
-        });
+    /// Draws the world and returns whether a frame was presented.
+    ///
+    /// While the screen is unchanged and no overlay is shown the previous
+    /// frame stays up and nothing is drawn, so idle ROMs cost no GPU work.
+    pub fn render(&mut self) -> Result<bool, wgpu::SurfaceError> {
+        let (surface, device, queue, config, scene) = match &mut self.backend {
+            Backend::Gpu {
+                surface,
+                device,
+                queue,
+                config,
+                scene,
+            } => (surface, device, queue, config, scene),
+            #[cfg(all(feature = "softbuffer", not(target_arch = "wasm32")))]
+            Backend::Soft(soft) => {
+                let mut world = self.world.borrow_mut();
+                if !soft.prepare(&mut world) && !self.stale {
+                    return Ok(false);
+                }
+                self.stale = false;
+                if let Err(err) = soft.draw(&world) {
+                    warn!("Could not present the frame: {}", err);
+                }
+                return Ok(true);
+            }
+        };
+        let changed = scene.prepare(queue, &mut self.world.borrow_mut());
+        if !changed && !self.stale && !self.overlay.needs_redraw() {
+            return Ok(false);
+        }
+        self.stale = false;
 
-        let pipeline_layout = self
-            .device
-            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[&camera_bind_group_layout],
-                push_constant_ranges: &[],
-            });
-        let render_pipeline = self
-            .device
-            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: None,
-                layout: Some(&pipeline_layout),
-                vertex: wgpu::VertexState {
-                    module: &self.shader,
-                    entry_point: "vs_main",
-                    buffers: &[Vertex::description(), InstanceData::description()],
-                    compilation_options: Default::default(),
-                },
-                fragment: Some(wgpu::FragmentState {
-                    module: &self.shader,
-                    entry_point: "fs_main",
-                    compilation_options: Default::default(),
-                    targets: &[Some(
-                        self.surface.get_capabilities(&self.adapter).formats[0].into(),
-                    )],
-                }),
-                primitive: wgpu::PrimitiveState::default(),
-                depth_stencil: None,
-                multisample: wgpu::MultisampleState::default(),
-                multiview: None,
-            });
-
-        /// TODO
-        let frame = self
-            .surface
+        let frame = surface
             .get_current_texture()
             .expect("Failed to acquire next swap chain texture");
         let view = frame
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
 
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Render Encoder"),
-            });
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
-                timestamp_writes: None,
-                occlusion_query_set: None,
-            });
-
-            render_pass.set_pipeline(&render_pipeline);
-
-            render_pass.set_bind_group(0, &camera_bind_group, &[]);
-
-            render_pass.set_vertex_buffer(0, self.square_mesh.vertex_buffer.slice(..));
-            render_pass.set_vertex_buffer(1, instance_buffer.slice(..));
-            render_pass.set_index_buffer(
-                self.square_mesh.index_buffer.slice(..),
-                wgpu::IndexFormat::Uint16,
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Render Encoder"),
+        });
+        let time = self.start.elapsed().as_secs_f32();
+        scene.draw(queue, &mut encoder, &self.world.borrow(), &view, time);
+
+        if self.overlay.has_content() {
+            let world = self.world.borrow();
+            self.overlay.render(
+                self.window,
+                device,
+                queue,
+                &mut encoder,
+                &view,
+                config.format,
+                [config.width, config.height],
+                world.machine.as_ref(),
             );
-            render_pass.draw_indexed(0..self.square_mesh.indices_len, 0, 0..instances.len() as _);
         }
 
-        self.queue.submit(Some(encoder.finish()));
+        queue.submit(Some(encoder.finish()));
         frame.present();
 
+        Ok(true)
+    }
+
+    pub fn is_post_processing(&self) -> bool {
+        match &self.backend {
+            Backend::Gpu { scene, .. } => scene.is_post_processing(),
+            #[cfg(all(feature = "softbuffer", not(target_arch = "wasm32")))]
+            Backend::Soft(_) => false,
+        }
+    }
+
+    /// Turns the post-processing effect on or off, the CRT effect unless a
+    /// shader was loaded with [`Renderer::load_shader`]. Drawing on the CPU
+    /// there is none.
+    pub fn set_post_processing(&mut self, enabled: bool) {
+        match &mut self.backend {
+            Backend::Gpu { device, scene, .. } => scene.set_post_processing(device, enabled),
+            #[cfg(all(feature = "softbuffer", not(target_arch = "wasm32")))]
+            Backend::Soft(_) => return,
+        }
+        self.stale = true;
+    }
+
+    /// Compiles the WGSL post-processing shader at `path` and turns it on,
+    /// replacing the CRT effect.
+    pub fn load_shader(&mut self, path: &Path) -> AppResult<()> {
+        let (device, scene) = match &mut self.backend {
+            Backend::Gpu { device, scene, .. } => (device, scene),
+            #[cfg(all(feature = "softbuffer", not(target_arch = "wasm32")))]
+            Backend::Soft(_) => {
+                return Err(AppError::InvalidArgument(
+                    "post-processing shaders need the GPU".into(),
+                ))
+            }
+        };
+        let modified = modified_time(path);
+        scene.load_shader(device, path)?;
+        self.shader = Some((path.to_owned(), modified));
+        self.stale = true;
         Ok(())
     }
 
+    /// Compiles the shader loaded with [`Renderer::load_shader`] again if its
+    /// file was modified since, keeping the effect on or off. Returns `None`
+    /// if it was not modified. If it fails to compile, the previous version
+    /// stays in use.
+    pub fn reload_shader(&mut self) -> Option<AppResult<()>> {
+        let (path, modified) = self.shader.as_mut()?;
+        let now_modified = modified_time(path);
+        if now_modified == *modified {
+            return None;
+        }
+        *modified = now_modified;
+        let (device, scene) = match &mut self.backend {
+            Backend::Gpu { device, scene, .. } => (device, scene),
+            #[cfg(all(feature = "softbuffer", not(target_arch = "wasm32")))]
+            Backend::Soft(_) => return None,
+        };
+        let enabled = scene.is_post_processing();
+        let result = scene.load_shader(device, path);
+        scene.set_post_processing(device, enabled);
+        self.stale = true;
+        Some(result)
+    }
+
+    /// Draws `bezel` around the screen, which is then fitted into its hole.
+    pub fn set_bezel(&mut self, bezel: &Bezel) -> AppResult<()> {
+        let (device, queue, scene) = match &mut self.backend {
+            Backend::Gpu {
+                device,
+                queue,
+                scene,
+                ..
+            } => (device, queue, scene),
+            #[cfg(all(feature = "softbuffer", not(target_arch = "wasm32")))]
+            Backend::Soft(_) => {
+                return Err(AppError::InvalidArgument("bezels need the GPU".into()))
+            }
+        };
+        scene.set_bezel(device, queue, &mut self.world.borrow_mut(), bezel);
+        self.stale = true;
+        Ok(())
+    }
+
+    /// Adjusts the gamma and brightness of the screen.
+    pub fn set_color_correction(&mut self, correction: ColorCorrection) {
+        match &mut self.backend {
+            Backend::Gpu { queue, scene, .. } => scene.set_color_correction(queue, correction),
+            #[cfg(all(feature = "softbuffer", not(target_arch = "wasm32")))]
+            Backend::Soft(soft) => soft.set_color_correction(correction),
+        }
+        self.stale = true;
+    }
+
+    /// How long to wait when [`Renderer::render`] presented nothing, before
+    /// rendering again. With vsync that is a frame, like presenting would
+    /// have taken. Without it, short enough to show the next emulated frame
+    /// with little latency. Drawing on the CPU paces like vsync.
+    pub fn idle_wait(&self) -> Duration {
+        match &self.backend {
+            Backend::Gpu { config, .. } if config.present_mode != wgpu::PresentMode::Fifo => {
+                POLL_INTERVAL
+            }
+            _ => FRAME_DURATION,
+        }
+    }
+
+    pub fn is_fullscreen(&self) -> bool {
+        self.window.fullscreen().is_some()
+    }
+
+    /// Switches between the window and borderless fullscreen, where the
+    /// screen is letterboxed rather than stretched to the monitor.
+    pub fn set_fullscreen(&mut self, fullscreen: bool) {
+        self.window
+            .set_fullscreen(fullscreen.then_some(Fullscreen::Borderless(None)));
+        self.world.borrow_mut().camera.letterbox = fullscreen;
+        self.stale = true;
+    }
+
     pub fn surface_size(&self) -> PhysicalSize<u32> {
         self.surface_size
     }
 }
+
+/// When the file at `path` was last modified, if that can be told.
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
@@ -0,0 +1,212 @@
+//! Terminal debugger reading commands from stdin while the window runs.
+
+use std::{
+    fmt::Write,
+    io::{self, BufRead},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use crate::{
+    ascii,
+    debugger::{self, MAX_STEP_COUNT},
+    emulator::Emulator,
+    symbols::Symbols,
+};
+
+/// Bytes shown by `x` without a count.
+const DEFAULT_DUMP_LEN: usize = 16;
+const DUMP_ROW_LEN: usize = 16;
+
+const HELP: &str = "\
+Commands:
+  b [ADDR|LABEL]      toggle a breakpoint, or list them
+  bd, bc              toggle pausing before every draw (Dxyn) or clear (00E0)
+  s [N]               pause and step N instructions (1 by default, at most 10000)
+  c                   continue
+  p                   pause
+  x[/N] ADDR|LABEL    dump N bytes of memory (16 by default)
+  reg                 show registers, timers and the call stack
+  screen              print the screen as text art
+  poke ADDR BYTE...   write bytes to memory
+  help                show this help
+";
+
+/// A parsed REPL command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplCommand {
+    ListBreakpoints,
+    ToggleBreakpoint(u16),
+    ToggleBreakOnDraw,
+    ToggleBreakOnClear,
+    Step(usize),
+    Continue,
+    Pause,
+    Dump { address: u16, len: usize },
+    Registers,
+    Screen,
+    Poke { address: u16, bytes: Vec<u8> },
+    Help,
+}
+
+impl ReplCommand {
+    /// Parses a line such as `x/32 0x300`, resolving labels with `symbols`.
+    pub fn parse(line: &str, symbols: &Symbols) -> Result<Self, String> {
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            return Err("empty command".to_owned());
+        };
+        let address = |word: Option<&str>| -> Result<u16, String> {
+            let word = word.ok_or("missing address")?;
+            symbols
+                .resolve(word)
+                .ok_or_else(|| format!("unknown address or label `{}`", word))
+        };
+        let parsed = match command {
+            "b" | "break" => match words.next() {
+                Some(word) => Self::ToggleBreakpoint(address(Some(word))?),
+                None => Self::ListBreakpoints,
+            },
+            "bd" => Self::ToggleBreakOnDraw,
+            "bc" => Self::ToggleBreakOnClear,
+            "s" | "step" => Self::Step(match words.next() {
+                Some(count) => match count.parse() {
+                    Ok(count) if count <= MAX_STEP_COUNT => count,
+                    Ok(_) => return Err(format!("at most {} steps at once", MAX_STEP_COUNT)),
+                    Err(_) => return Err(format!("invalid count `{}`", count)),
+                },
+                None => 1,
+            }),
+            "c" | "continue" => Self::Continue,
+            "p" | "pause" => Self::Pause,
+            "reg" | "registers" => Self::Registers,
+            "screen" => Self::Screen,
+            "poke" => {
+                let address = address(words.next())?;
+                let bytes = words
+                    .map(|word| {
+                        symbols
+                            .resolve(word)
+                            .and_then(|value| u8::try_from(value).ok())
+                            .ok_or_else(|| format!("invalid byte `{}`", word))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                if bytes.is_empty() {
+                    return Err("nothing to poke".to_owned());
+                }
+                Self::Poke { address, bytes }
+            }
+            "help" | "?" => Self::Help,
+            _ => match command.strip_prefix('x') {
+                Some("") => Self::Dump {
+                    address: address(words.next())?,
+                    len: DEFAULT_DUMP_LEN,
+                },
+                Some(count) if count.starts_with('/') => Self::Dump {
+                    len: count[1..]
+                        .parse()
+                        .map_err(|_| format!("invalid count `{}`", &count[1..]))?,
+                    address: address(words.next())?,
+                },
+                _ => return Err(format!("unknown command `{}`, try `help`", command)),
+            },
+        };
+        Ok(parsed)
+    }
+
+    /// Applies the command to `emulator`, returning what to print.
+    pub fn execute(&self, emulator: &mut Emulator, symbols: &Symbols) -> String {
+        match *self {
+            Self::ListBreakpoints => {
+                let mut text = String::new();
+                for address in emulator.debugger.breakpoints() {
+                    let _ = writeln!(text, "{:#05X}  {}", address, symbols.describe(address));
+                }
+                if text.is_empty() {
+                    text.push_str("No breakpoints\n");
+                }
+                text
+            }
+            Self::ToggleBreakpoint(address) => {
+                let set = emulator.debugger.toggle_breakpoint(address);
+                format!(
+                    "Breakpoint at {:#05X} {}\n",
+                    address,
+                    if set { "set" } else { "removed" }
+                )
+            }
+            Self::ToggleBreakOnDraw => {
+                let enabled = !emulator.debugger.break_on_draw();
+                emulator.debugger.set_break_on_draw(enabled);
+                format!("Break on draw {}\n", if enabled { "on" } else { "off" })
+            }
+            Self::ToggleBreakOnClear => {
+                let enabled = !emulator.debugger.break_on_clear();
+                emulator.debugger.set_break_on_clear(enabled);
+                format!("Break on clear {}\n", if enabled { "on" } else { "off" })
+            }
+            Self::Step(count) => {
+                emulator.pause();
+                for _ in 0..count {
+                    if let Err(err) = emulator.step_instruction() {
+                        return format!("{}\n{}", err, debugger::describe(&emulator.chip, symbols));
+                    }
+                }
+                debugger::describe(&emulator.chip, symbols)
+            }
+            Self::Continue => {
+                emulator.resume();
+                String::new()
+            }
+            Self::Pause => {
+                emulator.pause();
+                debugger::describe(&emulator.chip, symbols)
+            }
+            Self::Dump { address, len } => dump(emulator.chip.memory(), address as usize, len),
+            Self::Registers => debugger::describe(&emulator.chip, symbols),
+            Self::Screen => ascii::encode(&emulator.chip.display),
+            Self::Poke { address, ref bytes } => match emulator.poke(address as usize, bytes) {
+                Ok(()) => format!("Wrote {} bytes at {:#05X}\n", bytes.len(), address),
+                Err(err) => format!("{}\n", err),
+            },
+            Self::Help => HELP.to_owned(),
+        }
+    }
+}
+
+/// Hex dump of `len` bytes of `memory` from `address`, stopping at its end.
+fn dump(memory: &[u8], address: usize, len: usize) -> String {
+    let end = address.saturating_add(len).min(memory.len());
+    let mut text = String::new();
+    for row in (address..end).step_by(DUMP_ROW_LEN) {
+        let _ = write!(text, "{:04X}:", row);
+        for byte in &memory[row..(row + DUMP_ROW_LEN).min(end)] {
+            let _ = write!(text, " {:02X}", byte);
+        }
+        text.push('\n');
+    }
+    text
+}
+
+/// Reads commands from stdin on a background thread until it is closed.
+pub fn spawn(emulator: Arc<Mutex<Emulator>>, symbols: Arc<Symbols>) -> io::Result<()> {
+    thread::Builder::new().name("repl".into()).spawn(move || {
+        print!("{}", HELP);
+        for line in io::stdin().lock().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            match ReplCommand::parse(&line, &symbols) {
+                Ok(command) => print!(
+                    "{}",
+                    command.execute(&mut emulator.lock().unwrap(), &symbols)
+                ),
+                Err(err) => println!("{}", err),
+            }
+        }
+    })?;
+    Ok(())
+}
@@ -0,0 +1,179 @@
+//! Recent frames kept compressed, for rewinding while playing.
+
+use std::collections::VecDeque;
+
+use crate::{
+    bus::AudioBus, chip::Chip8, error::AppResult, keypad::Keypad, screen::Screen,
+    state::MachineState,
+};
+
+/// Frames kept by default, thirty seconds of play.
+pub const DEFAULT_REWIND_FRAMES: usize = 1800;
+
+/// Changes turning one byte string into another: runs of changed bytes, each
+/// stored as its offset from the end of the previous run and its length as
+/// little-endian `u32`s, followed by the new bytes.
+///
+/// Consecutive frames differ in few bytes, so this is usually tiny.
+#[derive(Debug, Clone)]
+struct Patch {
+    len: usize,
+    runs: Vec<u8>,
+}
+
+impl Patch {
+    /// Changes from `from` to `to`.
+    fn new(from: &[u8], to: &[u8]) -> Self {
+        let mut runs = Vec::new();
+        let mut offset = 0;
+        let mut i = 0;
+        while i < to.len() {
+            if from.get(i) == Some(&to[i]) {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < to.len() && from.get(i) != Some(&to[i]) {
+                i += 1;
+            }
+            runs.extend_from_slice(&((start - offset) as u32).to_le_bytes());
+            runs.extend_from_slice(&((i - start) as u32).to_le_bytes());
+            runs.extend_from_slice(&to[start..i]);
+            offset = i;
+        }
+        Self {
+            len: to.len(),
+            runs,
+        }
+    }
+
+    /// Turns `bytes` into what the patch was made to.
+    fn apply(&self, bytes: &mut Vec<u8>) {
+        bytes.resize(self.len, 0);
+        let mut offset = 0;
+        let mut runs = self.runs.as_slice();
+        while let [a, b, c, d, e, f, g, h, rest @ ..] = runs {
+            let start = offset + u32::from_le_bytes([*a, *b, *c, *d]) as usize;
+            let len = u32::from_le_bytes([*e, *f, *g, *h]) as usize;
+            bytes[start..start + len].copy_from_slice(&rest[..len]);
+            offset = start + len;
+            runs = &rest[len..];
+        }
+    }
+}
+
+/// Difference from a frame to the one before it. The registers and other
+/// small parts are kept whole, memory and screen as patches.
+#[derive(Debug, Clone)]
+pub(crate) struct Delta {
+    state: MachineState,
+    memory: Patch,
+    screen: Patch,
+    mega_screen: Patch,
+}
+
+impl Delta {
+    /// Difference from `next` back to `previous`, the state of the frame before it.
+    pub(crate) fn new(mut previous: MachineState, next: &MachineState) -> Self {
+        let memory = Patch::new(&next.memory, &previous.memory);
+        let screen = Patch::new(&next.screen, &previous.screen);
+        let mega_screen = Patch::new(&next.mega_screen, &previous.mega_screen);
+        previous.memory = Vec::new();
+        previous.screen = Vec::new();
+        previous.mega_screen = Vec::new();
+        Self {
+            state: previous,
+            memory,
+            screen,
+            mega_screen,
+        }
+    }
+
+    /// Turns `next` back into the state of the frame before it.
+    pub(crate) fn undo(self, next: MachineState) -> MachineState {
+        let (mut memory, mut screen, mut mega_screen) =
+            (next.memory, next.screen, next.mega_screen);
+        self.memory.apply(&mut memory);
+        self.screen.apply(&mut screen);
+        self.mega_screen.apply(&mut mega_screen);
+        MachineState {
+            memory,
+            screen,
+            mega_screen,
+            ..self.state
+        }
+    }
+}
+
+/// Ring buffer of the states at the start of the last frames.
+///
+/// Only the newest state is kept whole, each older one as the [`Delta`] from
+/// the state after it, so rewinding undoes the deltas newest first.
+#[derive(Debug, Clone)]
+pub struct Rewind {
+    latest: Option<MachineState>,
+    deltas: VecDeque<Delta>,
+    capacity: usize,
+}
+
+impl Rewind {
+    /// Keeps up to `frames` frames, or nothing if it is 0.
+    pub fn new(frames: usize) -> Self {
+        Self {
+            latest: None,
+            deltas: VecDeque::new(),
+            capacity: frames,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    /// Number of frames that can be rewound.
+    pub fn len(&self) -> usize {
+        self.deltas.len() + usize::from(self.latest.is_some())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.latest.is_none()
+    }
+
+    pub fn clear(&mut self) {
+        self.latest = None;
+        self.deltas.clear();
+    }
+
+    /// Adds the current state of `chip` as the newest frame, dropping the
+    /// oldest one if the buffer is full. Does nothing if disabled.
+    pub fn push<A: AudioBus>(&mut self, chip: &Chip8<Screen, Keypad, A>) {
+        if !self.is_enabled() {
+            return;
+        }
+        let state = chip.snapshot();
+        if let Some(previous) = self.latest.take() {
+            self.deltas.push_back(Delta::new(previous, &state));
+            if self.deltas.len() == self.capacity {
+                self.deltas.pop_front();
+            }
+        }
+        self.latest = Some(state);
+    }
+
+    /// Returns `chip` to the newest frame and drops it, so the next call goes
+    /// back further. Returns `false` if there is none.
+    ///
+    /// The keys held now are kept, rather than those held back then.
+    pub fn rewind<A: AudioBus>(&mut self, chip: &mut Chip8<Screen, Keypad, A>) -> AppResult<bool> {
+        let Some(mut state) = self.latest.take() else {
+            return Ok(false);
+        };
+        state.keypad = chip.keypad.keys;
+        chip.restore(&state)?;
+
+        if let Some(delta) = self.deltas.pop_back() {
+            self.latest = Some(delta.undo(state));
+        }
+        Ok(true)
+    }
+}
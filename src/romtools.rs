@@ -0,0 +1,38 @@
+//! Edits of ROM images, for preparing them for real hardware.
+
+use crate::error::{AppError, AppResult};
+
+/// `rom` without the trailing `fill` bytes padding it out.
+pub fn trim(rom: &[u8], fill: u8) -> &[u8] {
+    let end = rom
+        .iter()
+        .rposition(|&byte| byte != fill)
+        .map_or(0, |last| last + 1);
+    &rom[..end]
+}
+
+/// `rom` padded with `fill` bytes to exactly `size` bytes.
+pub fn pad(rom: &[u8], size: usize, fill: u8) -> AppResult<Vec<u8>> {
+    if rom.len() > size {
+        return Err(AppError::InvalidArgument(format!(
+            "the ROM is already {} bytes, longer than {}",
+            rom.len(),
+            size
+        )));
+    }
+    let mut padded = rom.to_vec();
+    padded.resize(size, fill);
+    Ok(padded)
+}
+
+/// `rom` with `data` written over it from `offset`, growing it with zeros as
+/// needed. An offset at the end of the ROM appends `data`.
+pub fn splice(rom: &[u8], data: &[u8], offset: usize) -> Vec<u8> {
+    let mut spliced = rom.to_vec();
+    let end = offset + data.len();
+    if spliced.len() < end {
+        spliced.resize(end, 0);
+    }
+    spliced[offset..end].copy_from_slice(data);
+    spliced
+}
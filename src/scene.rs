@@ -0,0 +1,396 @@
+//! Drawing of the world to a texture: the screen, then the bezel, through
+//! the post-processing effect if there is one. Shared by the window
+//! [`Renderer`](crate::renderer::Renderer) and the
+//! [`OffscreenRenderer`](crate::offscreen::OffscreenRenderer).
+
+use std::{borrow::Cow, path::Path};
+
+use wgpu::util::DeviceExt;
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    bezel::{Bezel, BezelPass},
+    camera::CameraUniform,
+    error::AppResult,
+    megachip::{MEGA_SCREEN_HEIGHT, MEGA_SCREEN_WIDTH},
+    mesh::{InstanceData, Mesh, Vertex},
+    postprocess::PostProcess,
+    world::World,
+};
+
+const OPAQUE_SHADER: &str = include_str!("shaders/opaque.wgsl");
+/// Instances of the largest screen with every pixel drawn.
+const MAX_INSTANCES: usize = MEGA_SCREEN_WIDTH * MEGA_SCREEN_HEIGHT;
+
+/// Adjustment of the screen colors, the same on every backend.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorCorrection {
+    /// Above 1 brightens the midtones, below 1 darkens them.
+    pub gamma: f32,
+    /// Factor all colors are multiplied by, after the gamma.
+    pub brightness: f32,
+}
+
+impl ColorCorrection {
+    /// Corrects a color channel in `0.0..=1.0`, the same way the screen
+    /// shader does.
+    pub fn apply(&self, channel: f32) -> f32 {
+        (self.brightness * channel.max(0.0).powf(1.0 / self.gamma)).clamp(0.0, 1.0)
+    }
+}
+
+impl Default for ColorCorrection {
+    fn default() -> Self {
+        Self {
+            gamma: 1.0,
+            brightness: 1.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ColorUniform {
+    gamma: f32,
+    brightness: f32,
+    /// 1 if the target is sRGB and colors are to be written linear, so its
+    /// encoding gives back the intended colors.
+    linear_output: f32,
+    _padding: f32,
+}
+
+impl ColorUniform {
+    fn new(correction: &ColorCorrection, format: wgpu::TextureFormat) -> Self {
+        Self {
+            gamma: correction.gamma,
+            brightness: correction.brightness,
+            linear_output: if format.is_srgb() { 1.0 } else { 0.0 },
+            _padding: 0.0,
+        }
+    }
+}
+
+pub struct Scene {
+    /// Format of the targets drawn to.
+    format: wgpu::TextureFormat,
+    size: PhysicalSize<u32>,
+
+    square_mesh: Mesh,
+    camera_uniform: CameraUniform,
+    camera_buffer: wgpu::Buffer,
+    camera_bind_group: wgpu::BindGroup,
+    color_correction: ColorCorrection,
+    color_buffer: wgpu::Buffer,
+    render_pipeline: wgpu::RenderPipeline,
+    /// Pixels drawn in the current frame, reused to avoid allocating.
+    instances: Vec<InstanceData>,
+    /// Room for [`MAX_INSTANCES`], of which the first `instances.len()` are drawn.
+    instance_buffer: wgpu::Buffer,
+    /// Whether pixels are still fading out, see [`World::set_decay`].
+    fading: bool,
+    /// Effect applied to the screen while `post_processing` is set, the CRT
+    /// effect unless a custom shader was loaded.
+    post_process: Option<PostProcess>,
+    post_processing: bool,
+    /// Image drawn around the screen, see [`Scene::set_bezel`].
+    bezel: Option<BezelPass>,
+}
+
+impl Scene {
+    /// Scene drawn to targets of `format` and `size`.
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        size: PhysicalSize<u32>,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(OPAQUE_SHADER)),
+        });
+
+        let square_mesh = Mesh::create_square(device);
+
+        let camera_uniform = CameraUniform::new();
+        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Camera Buffer"),
+            contents: bytemuck::cast_slice(&[camera_uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let color_uniform = ColorUniform::new(&ColorCorrection::default(), format);
+        let color_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Color Buffer"),
+            contents: bytemuck::cast_slice(&[color_uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let camera_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Camera Bind Group Layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Camera Bind Group"),
+            layout: &camera_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: camera_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: color_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Render Pipeline Layout"),
+            bind_group_layouts: &[&camera_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[Vertex::description(), InstanceData::description()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        let instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Instance Buffer"),
+            size: (MAX_INSTANCES * std::mem::size_of::<InstanceData>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            format,
+            size,
+            square_mesh,
+            camera_uniform,
+            camera_buffer,
+            camera_bind_group,
+            color_correction: ColorCorrection::default(),
+            color_buffer,
+            render_pipeline,
+            instances: Vec::with_capacity(MAX_INSTANCES),
+            instance_buffer,
+            fading: false,
+            post_process: None,
+            post_processing: false,
+            bezel: None,
+        }
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, size: PhysicalSize<u32>) {
+        self.size = size;
+        if let Some(post_process) = &mut self.post_process {
+            post_process.resize(device, size);
+        }
+    }
+
+    /// Uploads the camera of `world`.
+    pub fn update(&mut self, queue: &wgpu::Queue, world: &World) {
+        self.camera_uniform.update(&world.camera);
+        queue.write_buffer(
+            &self.camera_buffer,
+            0,
+            bytemuck::cast_slice(&[self.camera_uniform]),
+        );
+    }
+
+    /// Uploads the pixels of `world` if its screen changed or pixels are
+    /// fading. Returns whether the next frame differs from the last one
+    /// drawn, including by an animated post-processing effect.
+    pub fn prepare(&mut self, queue: &wgpu::Queue, world: &mut World) -> bool {
+        if world.screen.take_dirty() || self.fading {
+            self.fading = world.get_instances(&mut self.instances);
+            if !self.instances.is_empty() {
+                queue.write_buffer(
+                    &self.instance_buffer,
+                    0,
+                    bytemuck::cast_slice(&self.instances),
+                );
+            }
+            return true;
+        }
+        self.active_post_process()
+            .is_some_and(PostProcess::is_animated)
+    }
+
+    /// Draws the pixels last prepared to `target`, `time` seconds into the
+    /// post-processing effect.
+    pub fn draw(
+        &self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        world: &World,
+        target: &wgpu::TextureView,
+        time: f32,
+    ) {
+        let post_process = self.active_post_process();
+        let scene_target = post_process.map_or(target, PostProcess::view);
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: scene_target,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(self.clear_color(world.background())),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            let [x, y, width, height] = world.camera.viewport();
+            render_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+            render_pass.set_pipeline(&self.render_pipeline);
+
+            render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+
+            render_pass.set_vertex_buffer(0, self.square_mesh.vertex_buffer.slice(..));
+            render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+            render_pass.set_index_buffer(
+                self.square_mesh.index_buffer.slice(..),
+                wgpu::IndexFormat::Uint16,
+            );
+            render_pass.draw_indexed(
+                0..self.square_mesh.indices_len,
+                0,
+                0..self.instances.len() as _,
+            );
+        }
+
+        if let (Some(bezel), Some(rect)) = (&self.bezel, world.camera.bezel_viewport()) {
+            bezel.render(queue, encoder, scene_target, rect);
+        }
+
+        if let Some(post_process) = post_process {
+            post_process.render(queue, encoder, target, time);
+        }
+    }
+
+    fn active_post_process(&self) -> Option<&PostProcess> {
+        self.post_process.as_ref().filter(|_| self.post_processing)
+    }
+
+    pub fn is_post_processing(&self) -> bool {
+        self.post_processing
+    }
+
+    /// Turns the post-processing effect on or off, the CRT effect unless a
+    /// shader was loaded with [`Scene::load_shader`].
+    pub fn set_post_processing(&mut self, device: &wgpu::Device, enabled: bool) {
+        if enabled && self.post_process.is_none() {
+            self.post_process = Some(PostProcess::crt(device, self.format, self.size));
+        }
+        self.post_processing = enabled;
+    }
+
+    /// Compiles the WGSL post-processing shader at `path` and turns it on,
+    /// replacing the CRT effect.
+    pub fn load_shader(&mut self, device: &wgpu::Device, path: &Path) -> AppResult<()> {
+        self.post_process = Some(PostProcess::load(device, self.format, self.size, path)?);
+        self.post_processing = true;
+        Ok(())
+    }
+
+    /// Draws `bezel` around the screen of `world`, which is then fitted into
+    /// its hole.
+    pub fn set_bezel(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        world: &mut World,
+        bezel: &Bezel,
+    ) {
+        self.bezel = Some(BezelPass::new(device, queue, self.format, bezel));
+        world.camera.bezel = Some(bezel.layout());
+    }
+
+    /// Adjusts the gamma and brightness of the screen.
+    pub fn set_color_correction(&mut self, queue: &wgpu::Queue, correction: ColorCorrection) {
+        self.color_correction = correction;
+        let color_uniform = ColorUniform::new(&correction, self.format);
+        queue.write_buffer(
+            &self.color_buffer,
+            0,
+            bytemuck::cast_slice(&[color_uniform]),
+        );
+    }
+
+    /// Clear color for the `0xAARRGGBB` background, corrected like the
+    /// screen shader corrects pixels.
+    fn clear_color(&self, argb: u32) -> wgpu::Color {
+        let srgb = self.format.is_srgb();
+        let channel = |shift: u32| {
+            let value = self
+                .color_correction
+                .apply(((argb >> shift) & 0xFF) as f32 / 255.0);
+            // wgpu takes the clear color of sRGB targets as linear.
+            if srgb {
+                srgb_to_linear(value) as f64
+            } else {
+                value as f64
+            }
+        };
+        wgpu::Color {
+            r: channel(16),
+            g: channel(8),
+            b: channel(0),
+            a: ((argb >> 24) & 0xFF) as f64 / 255.0,
+        }
+    }
+}
+
+/// Converts an sRGB encoded color channel to linear, as the screen shader does.
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
@@ -0,0 +1,161 @@
+//! PNG images of the framebuffer, for documentation, visual tests and save
+//! state thumbnails.
+
+use std::path::{Path, PathBuf};
+
+use image::RgbaImage;
+
+use crate::{
+    error::{AppError, AppResult},
+    screen::{Screen, DEFAULT_PLANE_COLORS},
+};
+
+/// Size of a CHIP-8 pixel in screenshots by default.
+pub const DEFAULT_SCREENSHOT_SCALE: u32 = 8;
+/// Largest width of thumbnails. Wider screens are scaled down to fit.
+pub const THUMBNAIL_WIDTH: usize = 128;
+
+/// Color of the pixel at (`x`, `y`) as `0xAARRGGBB` in the default colors:
+/// white on black, or the CHIP-8X colors.
+pub fn pixel_color(screen: &Screen, x: usize, y: usize) -> u32 {
+    screen.color(x, y, &DEFAULT_PLANE_COLORS)
+}
+
+/// Writes the screen to a PNG image at `path`, every pixel `scale` image
+/// pixels wide and high, in `plane_colors` (see [`Screen::color`]).
+pub fn save_png(
+    screen: &Screen,
+    plane_colors: &[u32; 4],
+    scale: u32,
+    path: &Path,
+) -> AppResult<()> {
+    let scale = scale.max(1) as usize;
+    let (width, height) = (screen.width() * scale, screen.height() * scale);
+    let data = rgb(screen, plane_colors, width, height, |x, y| {
+        (x / scale, y / scale)
+    });
+    std::fs::write(path, encode_png(width, height, &data)?)?;
+    Ok(())
+}
+
+/// Writes a frame drawn by [`OffscreenRenderer`](crate::offscreen::OffscreenRenderer)
+/// to a PNG image at `path`, dropping its alpha.
+pub fn save_frame(frame: &RgbaImage, path: &Path) -> AppResult<()> {
+    let data: Vec<u8> = frame
+        .pixels()
+        .flat_map(|pixel| &pixel.0[..3])
+        .copied()
+        .collect();
+    let png = encode_png(frame.width() as usize, frame.height() as usize, &data)?;
+    std::fs::write(path, png)?;
+    Ok(())
+}
+
+/// Path for a screenshot of the ROM at `rom_path` taken now, named after the
+/// ROM and the UTC time like `pong-20240131-235959.png`, in `dir` or next to
+/// the ROM. A number is appended if that file already exists.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn screenshot_path(rom_path: &Path, dir: Option<&Path>) -> PathBuf {
+    let dir = dir
+        .or_else(|| rom_path.parent())
+        .unwrap_or_else(|| Path::new(""));
+    let name = rom_path
+        .file_stem()
+        .map_or("screenshot".into(), |stem| stem.to_string_lossy());
+    let seconds = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let stem = format!("{}-{}", name, timestamp(seconds));
+    let mut path = dir.join(format!("{}.png", stem));
+    let mut number = 2;
+    while path.exists() {
+        path = dir.join(format!("{}-{}.png", stem, number));
+        number += 1;
+    }
+    path
+}
+
+/// Formats seconds since the Unix epoch as `YYYYMMDD-HHMMSS` in UTC.
+#[cfg(not(target_arch = "wasm32"))]
+fn timestamp(seconds: u64) -> String {
+    let (days, time) = (seconds / 86400, seconds % 86400);
+    // Civil date from days since the epoch, counting in 400-year eras of
+    // years starting in March so leap days come last.
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+/// PNG image of the screen at most [`THUMBNAIL_WIDTH`] pixels wide, at one
+/// image pixel per screen pixel if it fits.
+pub fn thumbnail(screen: &Screen) -> AppResult<Vec<u8>> {
+    let step = screen.width().div_ceil(THUMBNAIL_WIDTH).max(1);
+    let (width, height) = (screen.width() / step, screen.height() / step);
+    let data = rgb(screen, &DEFAULT_PLANE_COLORS, width, height, |x, y| {
+        (x * step, y * step)
+    });
+    encode_png(width, height, &data)
+}
+
+/// Decodes a PNG image written by this module, into its width, height and
+/// 8-bit RGB pixels.
+pub fn decode_png(png: &[u8]) -> AppResult<(usize, usize, Vec<u8>)> {
+    let mut reader = png::Decoder::new(png).read_info()?;
+    let mut data = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut data)?;
+    if info.color_type != png::ColorType::Rgb || info.bit_depth != png::BitDepth::Eight {
+        return Err(AppError::InvalidState("unexpected thumbnail format"));
+    }
+    data.truncate(info.buffer_size());
+    Ok((info.width as usize, info.height as usize, data))
+}
+
+/// RGB pixels of a `width` by `height` image, taking the color of each from
+/// the screen pixel `pixel` maps it to.
+fn rgb(
+    screen: &Screen,
+    plane_colors: &[u32; 4],
+    width: usize,
+    height: usize,
+    pixel: impl Fn(usize, usize) -> (usize, usize),
+) -> Vec<u8> {
+    let mut data = Vec::with_capacity(width * height * 3);
+    for y in 0..height {
+        for x in 0..width {
+            let (x, y) = pixel(x, y);
+            data.extend_from_slice(&screen.color(x, y, plane_colors).to_be_bytes()[1..]);
+        }
+    }
+    data
+}
+
+fn encode_png(width: usize, height: usize, data: &[u8]) -> AppResult<Vec<u8>> {
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(data)?;
+    writer.finish()?;
+    Ok(png)
+}
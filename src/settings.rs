@@ -0,0 +1,89 @@
+//! User settings kept between runs, in `rusty-chip8/settings.toml` under the
+//! user's config directory.
+
+use std::{collections::BTreeMap, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, AppResult};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Name of the palette last chosen with the palette hotkey, or `None`
+    /// for the default shading.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub palette: Option<String>,
+    /// Colors of the `custom` palette: the background, the first and second
+    /// XO-CHIP planes and both, as in `--plane-colors`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plane_colors: Option<String>,
+    /// Frequency of the buzzer in Hz, as in `--beep-freq`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub beep_freq: Option<f32>,
+    /// Waveform of the buzzer, as in `--beep-wave`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub beep_wave: Option<String>,
+    /// Sound file the buzzer plays, as in `--beep-sample`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub beep_sample: Option<PathBuf>,
+    /// Milliseconds the buzzer takes to fade in and out, as in `--beep-envelope`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub beep_envelope: Option<u64>,
+    /// Sound buffered ahead in milliseconds, as in `--audio-latency`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audio_latency: Option<u64>,
+    /// Pause the emulator while the window is in the background.
+    #[serde(skip_serializing_if = "is_false")]
+    pub pause_on_unfocus: bool,
+    /// Silence the buzzer while the window is in the background.
+    #[serde(skip_serializing_if = "is_false")]
+    pub mute_on_unfocus: bool,
+    /// Keyboard layout typed on, as in `--layout`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layout: Option<String>,
+    /// The `[keymap]` table, binding CHIP-8 keys `0`-`F` to physical keys
+    /// named as in `--keymap`, in place of their default keys.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub keymap: BTreeMap<String, String>,
+}
+
+impl Settings {
+    /// Where the settings are kept, `None` if there is no config directory.
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("rusty-chip8").join("settings.toml"))
+    }
+
+    /// Reads the settings, or the defaults if none were saved yet.
+    pub fn load() -> AppResult<Self> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(err.into()),
+        };
+        toml::from_str(&text).map_err(|err| {
+            AppError::InvalidArgument(format!("malformed settings {}: {}", path.display(), err))
+        })
+    }
+
+    pub fn save(&self) -> AppResult<()> {
+        let Some(path) = Self::path() else {
+            return Err(AppError::InvalidArgument(
+                "no config directory to save the settings in".to_owned(),
+            ));
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let text = toml::to_string(self).expect("settings are always serializable");
+        std::fs::write(path, text)?;
+        Ok(())
+    }
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
@@ -0,0 +1,24 @@
+@group(0) @binding(0)
+var bezel: texture_2d<f32>;
+@group(0) @binding(1)
+var bezel_sampler: sampler;
+
+// Left and top edges, width and height of the bezel in window pixels.
+@group(0) @binding(2)
+var<uniform> rect: vec4<f32>;
+
+// A single triangle covering the whole window.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let uv = (position.xy - rect.xy) / rect.zw;
+    let color = textureSampleLevel(bezel, bezel_sampler, clamp(uv, vec2<f32>(0.0), vec2<f32>(1.0)), 0.0);
+    // Black bars around the bezel, where its aspect ratio differs from the window's.
+    let outside = any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0));
+    return select(color, vec4<f32>(0.0, 0.0, 0.0, 1.0), outside);
+}
@@ -0,0 +1,24 @@
+// How far the corners bend outward.
+const CURVATURE: f32 = 0.06;
+// Height of a scanline in window pixels, and how much darker its gap is.
+const SCANLINE_HEIGHT: f32 = 3.0;
+const SCANLINE_DEPTH: f32 = 0.3;
+const VIGNETTE: f32 = 0.25;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Barrel distortion: sample further out the further from the center.
+    var centered = in.uv * 2.0 - 1.0;
+    centered *= 1.0 + CURVATURE * dot(centered, centered);
+    let uv = centered * 0.5 + 0.5;
+    if (any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0))) {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+
+    var color = textureSampleLevel(screen, screen_sampler, uv, 0.0).rgb;
+    let scanline = 1.0 - SCANLINE_DEPTH * (0.5 - 0.5 * cos(in.position.y * 6.2831853 / SCANLINE_HEIGHT));
+    let edge = uv * (1.0 - uv);
+    let vignette = pow(edge.x * edge.y * 16.0, VIGNETTE);
+    color *= scanline * vignette;
+    return vec4<f32>(color, 1.0);
+}
@@ -4,16 +4,29 @@ struct Camera {
 @group(0) @binding(0)
 var<uniform> camera: Camera;
 
+struct ColorCorrection {
+    gamma: f32,
+    brightness: f32,
+    // 1.0 when writing to an sRGB surface, which expects linear colors.
+    linear_output: f32,
+}
+@group(0) @binding(1)
+var<uniform> color_correction: ColorCorrection;
+
 struct VertexInput {
     @location(0) position: vec3<f32>,
 }
 
 struct InstanceInput {
     @location(1) position: vec2<f32>,
+    @location(2) color: vec4<f32>,
+    @location(3) brightness: f32,
 }
 
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    @location(1) brightness: f32,
 }
 
 @vertex
@@ -23,15 +36,37 @@ fn vs_main(vertex: VertexInput, instance: InstanceInput) -> VertexOutput {
         (vertex.position + vec3(instance.position, 0.0)),
         1.0,
     );
+    out.color = instance.color;
+    out.brightness = instance.brightness;
     return out;
 }
 
+// Applies the gamma and brightness to an sRGB color, keeping the result the
+// same whether or not the surface encodes to sRGB itself.
+fn correct(color: vec3<f32>) -> vec3<f32> {
+    let corrected = clamp(
+        color_correction.brightness * pow(max(color, vec3(0.0)), vec3(1.0 / color_correction.gamma)),
+        vec3(0.0),
+        vec3(1.0),
+    );
+    let linear = select(
+        pow((corrected + 0.055) / 1.055, vec3(2.4)),
+        corrected / 12.92,
+        corrected <= vec3(0.04045),
+    );
+    return select(corrected, linear, color_correction.linear_output > 0.5);
+}
+
 @fragment
 fn fs_main(out: VertexOutput) -> @location(0) vec4<f32> {
-    return vec4<f32>(
+    // Pixels with a palette color carry it, fading ones blend with the background.
+    if (out.color.a > 0.0) {
+        return vec4<f32>(correct(out.color.rgb), out.brightness);
+    }
+    let shade = vec3<f32>(
         smoothstep(0.4, 1.0, sin(out.position.x * 0.6) + 1.0),
         smoothstep(0.4, 1.0, cos(out.position.y * 0.3) + 1.0),
         1.0,
-        1.0,
     );
+    return vec4<f32>(correct(shade), out.brightness);
 }
@@ -0,0 +1,33 @@
+// Prepended to every post-processing shader: crt.wgsl and those given with
+// --shader, which only define `fs_main`.
+
+// The emulated screen as drawn, the size of the window.
+@group(0) @binding(0)
+var screen: texture_2d<f32>;
+@group(0) @binding(1)
+var screen_sampler: sampler;
+
+struct Globals {
+    // Seconds since the window opened.
+    time: f32,
+    // Size of the window in pixels.
+    resolution: vec2<f32>,
+}
+@group(0) @binding(2)
+var<uniform> globals: Globals;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    // Position in the screen texture, from (0, 0) at the top left to (1, 1).
+    @location(0) uv: vec2<f32>,
+}
+
+// A single triangle covering the whole window.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
@@ -0,0 +1,189 @@
+//! Drawing the screen on the CPU with softbuffer, for `--no-gpu` on machines
+//! without a working graphics driver, such as VMs reached over X forwarding.
+//! Palettes, fading, rotation and color correction look as they do on the
+//! GPU. Bezels, post-processing and the debugging overlay need the GPU.
+
+use std::{num::NonZeroU32, thread, time::Instant};
+
+use softbuffer::{Context, SoftBufferError, Surface};
+use winit::{dpi::PhysicalSize, window::Window};
+
+use crate::{
+    camera::Rotation,
+    emulator::FRAME_DURATION,
+    error::{AppError, AppResult},
+    mesh::InstanceData,
+    scene::ColorCorrection,
+    world::World,
+};
+
+pub struct SoftRenderer<'a> {
+    surface: Surface<&'a Window, &'a Window>,
+    size: PhysicalSize<u32>,
+    instances: Vec<InstanceData>,
+    /// Whether pixels were still fading when last prepared.
+    fading: bool,
+    /// Every 8-bit color channel value after color correction.
+    levels: [u8; 256],
+    /// When the last frame was presented, to present at most one per 60 Hz
+    /// frame as vsync would.
+    presented: Option<Instant>,
+}
+
+impl<'a> SoftRenderer<'a> {
+    pub fn new(window: &'a Window, size: PhysicalSize<u32>) -> AppResult<Self> {
+        let context = Context::new(window).map_err(internal)?;
+        let surface = Surface::new(&context, window).map_err(internal)?;
+        let mut renderer = Self {
+            surface,
+            size,
+            instances: Vec::new(),
+            fading: false,
+            levels: [0; 256],
+            presented: None,
+        };
+        renderer.resize(size)?;
+        renderer.set_color_correction(ColorCorrection::default());
+        Ok(renderer)
+    }
+
+    pub fn resize(&mut self, size: PhysicalSize<u32>) -> AppResult<()> {
+        if let (Some(width), Some(height)) =
+            (NonZeroU32::new(size.width), NonZeroU32::new(size.height))
+        {
+            self.surface.resize(width, height).map_err(internal)?;
+            self.size = size;
+        }
+        Ok(())
+    }
+
+    pub fn set_color_correction(&mut self, correction: ColorCorrection) {
+        self.levels = std::array::from_fn(|value| {
+            (correction.apply(value as f32 / 255.0) * 255.0).round() as u8
+        });
+    }
+
+    /// Takes the pixels of `world` if its screen changed or pixels are
+    /// fading. Returns whether the next frame differs from the last one drawn.
+    pub fn prepare(&mut self, world: &mut World) -> bool {
+        if world.screen.take_dirty() || self.fading {
+            self.fading = world.get_instances(&mut self.instances);
+            return true;
+        }
+        false
+    }
+
+    /// Draws the pixels last prepared and presents them, first waiting out
+    /// the rest of the frame since the last one was presented.
+    pub fn draw(&mut self, world: &World) -> AppResult<()> {
+        let width = self.size.width as usize;
+        let height = self.size.height as usize;
+        let levels = &self.levels;
+        let background = correct(levels, world.background());
+        let mut buffer = self.surface.buffer_mut().map_err(internal)?;
+        buffer.fill(background);
+
+        let [left, top, viewport_width, viewport_height] = world.camera.viewport();
+        let resolution = world.camera.resolution;
+        let rotation = world.camera.rotation;
+        for instance in &self.instances {
+            let [x, y] = instance.position();
+            let (u0, v0) = rotate(rotation, x / resolution.x, y / resolution.y);
+            let (u1, v1) = rotate(rotation, (x + 1.0) / resolution.x, (y + 1.0) / resolution.y);
+            // Window pixels with their centers inside, like the GPU fills them.
+            let columns = span(
+                left + u0.min(u1) * viewport_width,
+                left + u0.max(u1) * viewport_width,
+                width,
+            );
+            let rows = span(
+                top + v0.min(v1) * viewport_height,
+                top + v0.max(v1) * viewport_height,
+                height,
+            );
+
+            let [red, green, blue, alpha] = instance.color();
+            let brightness = instance.brightness();
+            let color = (alpha > 0.0).then(|| correct_channels(levels, red, green, blue));
+            for row in rows {
+                for column in columns.clone() {
+                    let color = color.unwrap_or_else(|| {
+                        let (red, green, blue) = shade(column as f32 + 0.5, row as f32 + 0.5);
+                        correct_channels(levels, red, green, blue)
+                    });
+                    let pixel = &mut buffer[row * width + column];
+                    *pixel = blend(*pixel, color, brightness);
+                }
+            }
+        }
+
+        if let Some(presented) = self.presented {
+            thread::sleep((presented + FRAME_DURATION).saturating_duration_since(Instant::now()));
+        }
+        buffer.present().map_err(internal)?;
+        self.presented = Some(Instant::now());
+        Ok(())
+    }
+}
+
+/// Softbuffer's errors may hold window handles, which can't be sent between
+/// threads, so only their message is kept.
+fn internal(err: SoftBufferError) -> AppError {
+    AppError::Internal(err.to_string().into())
+}
+
+/// Corrects an `0xAARRGGBB` color with `levels` into the `0x00RRGGBB`
+/// softbuffer takes.
+fn correct(levels: &[u8; 256], argb: u32) -> u32 {
+    let channel = |shift: u32| ((argb >> shift) & 0xFF) as f32 / 255.0;
+    correct_channels(levels, channel(16), channel(8), channel(0))
+}
+
+fn correct_channels(levels: &[u8; 256], red: f32, green: f32, blue: f32) -> u32 {
+    let level = |channel: f32| levels[(channel.clamp(0.0, 1.0) * 255.0).round() as usize] as u32;
+    (level(red) << 16) | (level(green) << 8) | level(blue)
+}
+
+/// Where a point `u` across and `v` down the screen, from 0 to 1, is shown
+/// with the screen turned clockwise by `rotation`.
+fn rotate(rotation: Rotation, u: f32, v: f32) -> (f32, f32) {
+    match rotation {
+        Rotation::None => (u, v),
+        Rotation::Quarter => (1.0 - v, u),
+        Rotation::Half => (1.0 - u, 1.0 - v),
+        Rotation::ThreeQuarters => (v, 1.0 - u),
+    }
+}
+
+/// Indices of the pixels up to `len` whose centers lie from `start` up to `end`.
+fn span(start: f32, end: f32, len: usize) -> std::ops::Range<usize> {
+    let index = |edge: f32| ((edge - 0.5).ceil().max(0.0) as usize).min(len);
+    index(start)..index(end)
+}
+
+/// Red, green and blue of the default shading at a window position, as the
+/// screen shader computes it.
+fn shade(x: f32, y: f32) -> (f32, f32, f32) {
+    let smoothstep = |value: f32| {
+        let t = ((value - 0.4) / 0.6).clamp(0.0, 1.0);
+        t * t * (3.0 - 2.0 * t)
+    };
+    (
+        smoothstep((x * 0.6).sin() + 1.0),
+        smoothstep((y * 0.3).cos() + 1.0),
+        1.0,
+    )
+}
+
+/// Mixes two `0x00RRGGBB` colors, `color` covering `amount` of `below`.
+fn blend(below: u32, color: u32, amount: f32) -> u32 {
+    if amount >= 1.0 {
+        return color;
+    }
+    let channel = |shift: u32| {
+        let below = ((below >> shift) & 0xFF) as f32;
+        let color = ((color >> shift) & 0xFF) as f32;
+        ((below + (color - below) * amount).round() as u32) << shift
+    };
+    channel(16) | channel(8) | channel(0)
+}
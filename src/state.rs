@@ -0,0 +1,491 @@
+//! Save states: complete snapshots of a machine.
+//!
+//! On disk a state is a little-endian binary container:
+//!
+//! | Offset | Size | Contents                                           |
+//! |--------|------|----------------------------------------------------|
+//! | 0      | 4    | Magic number, `C8SS`                               |
+//! | 4      | 2    | Format version, [`STATE_VERSION`]                  |
+//! | 6      | 4    | CRC-32 (IEEE) of everything after the header       |
+//! | 10     | 4    | Size of the thumbnail, `n`                         |
+//! | 14     | n    | Thumbnail of the screen, a PNG image               |
+//! | 14 + n | rest | Payload, the [`MachineState`] encoded by `bincode` |
+//!
+//! The payload includes the quirks and the variant of the machine: its
+//! [`MachineConfig`], with the memory size, stack depth, program start, entry
+//! point, initial resolution and whether it is a CHIP-8X or Mega-Chip. Files of unknown versions,
+//! with a wrong checksum or saved on another variant are rejected with an
+//! error.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bus::{AudioBus, AUDIO_PATTERN_SIZE},
+    chip::{Chip8, FLAG_COUNT, REGISTER_COUNT},
+    config::{MachineConfig, MAX_MEMORY_SIZE, MAX_STACK_DEPTH},
+    core::error::ExecResult,
+    error::{AppError, AppResult},
+    instruction::Instruction,
+    keypad::{Keypad, KEY_COUNT},
+    megachip::{MegaRegisters, MegaScreen, MEGA_SCREEN_HEIGHT, MEGA_SCREEN_WIDTH, PALETTE_SIZE},
+    platform::Platform,
+    quirks::Quirks,
+    rng::Rng,
+    screen::{ColorZones, Resolution, Screen, COLOR_ZONE_COUNT},
+    screenshot,
+};
+
+/// Start of every save state file.
+pub const STATE_MAGIC: [u8; 4] = *b"C8SS";
+/// Version of the save state format written. Bumped whenever [`MachineState`] changes.
+pub const STATE_VERSION: u16 = 1;
+const HEADER_SIZE: usize = 10;
+/// Size of [`MachineState::mega_screen`] on the Mega-Chip display: the
+/// palette, the back buffer's entries and colors and the picture's colors.
+const MEGA_SCREEN_SIZE: usize = PALETTE_SIZE * 4 + MEGA_SCREEN_WIDTH * MEGA_SCREEN_HEIGHT * 9;
+
+/// Number of save state slots per ROM.
+pub const SLOT_COUNT: usize = 4;
+
+/// Where save state `slot` of the ROM at `rom_path` is kept: next to it, with
+/// a `.state<slot>` extension.
+pub fn slot_path(rom_path: &Path, slot: usize) -> PathBuf {
+    rom_path.with_extension(format!("state{}", slot))
+}
+
+/// Where the state of the ROM `rom` is saved on exit, in the user's data
+/// directory and keyed by the ROM's SHA-1. `None` if there is no data directory.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn autosave_path(rom: &[u8]) -> Option<PathBuf> {
+    let sha1 = sha1_smol::Sha1::from(rom).digest();
+    dirs::data_dir().map(|dir| {
+        dir.join("rusty-chip8")
+            .join("autosave")
+            .join(format!("{}.state", sha1))
+    })
+}
+
+/// Complete snapshot of a machine, suitable for inspecting or restoring it later.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MachineState {
+    pub memory: Vec<u8>,
+    pub registers: [u8; REGISTER_COUNT],
+    pub register_i: u32,
+    pub pc: u16,
+    pub sp: u8,
+    pub stack: Vec<u16>,
+    /// Address each `CALL` on the stack entered, as long as `stack`.
+    pub call_entries: Vec<u16>,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub waiting_for_key: Option<u8>,
+    pub key_wait_pressed: Option<u8>,
+    pub waiting_for_vblank: bool,
+    pub exited: bool,
+    /// Visible pixels, row by row, at `resolution`.
+    /// Each pixel holds one bit per plane, or its Mega-Chip palette entry.
+    pub screen: Vec<u8>,
+    /// Mega-Chip palette and buffers on its display, empty on the others:
+    /// the palette, the palette entries and colors of the back buffer, then
+    /// the colors shown, each color a little-endian `0xAARRGGBB`.
+    pub mega_screen: Vec<u8>,
+    pub resolution: Resolution,
+    /// Bit mask of the selected XO-CHIP planes.
+    pub planes: u8,
+    /// CHIP-8X background color, if the color board is enabled.
+    pub background_color: Option<u8>,
+    /// CHIP-8X foreground color of each zone.
+    pub foreground_colors: Vec<u8>,
+    pub audio_pattern: [u8; AUDIO_PATTERN_SIZE],
+    pub pitch: u8,
+    pub mega: MegaRegisters,
+    pub keypad: [bool; KEY_COUNT],
+    pub rng_state: u64,
+    pub flags: [u8; FLAG_COUNT],
+    pub quirks: Quirks,
+    /// Layout of the machine the state was saved on.
+    pub config: MachineConfig,
+}
+
+impl MachineState {
+    /// Reads a save state written by [`MachineState::save`].
+    pub fn load(path: &Path) -> AppResult<Self> {
+        Self::decode(&std::fs::read(path)?)
+    }
+
+    /// Writes the state in the container format described in the [module docs](self).
+    pub fn save(&self, path: &Path) -> AppResult<()> {
+        std::fs::write(path, self.encode()?)?;
+        Ok(())
+    }
+
+    pub fn encode(&self) -> AppResult<Vec<u8>> {
+        let thumbnail = screenshot::thumbnail(&self.display()?)?;
+        let payload = bincode::serialize(self).expect("machine states are always serializable");
+        let mut body = Vec::with_capacity(4 + thumbnail.len() + payload.len());
+        body.extend_from_slice(&(thumbnail.len() as u32).to_le_bytes());
+        body.extend_from_slice(&thumbnail);
+        body.extend_from_slice(&payload);
+
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + body.len());
+        bytes.extend_from_slice(&STATE_MAGIC);
+        bytes.extend_from_slice(&STATE_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&crc32fast::hash(&body).to_le_bytes());
+        bytes.extend_from_slice(&body);
+        Ok(bytes)
+    }
+
+    pub fn decode(bytes: &[u8]) -> AppResult<Self> {
+        let (_, payload) = split_container(bytes)?;
+        let state: Self = bincode::deserialize(payload)
+            .map_err(|_| AppError::InvalidState("malformed save state"))?;
+        state.validate()?;
+        Ok(state)
+    }
+
+    /// Checks that the state describes some machine, so files written with a
+    /// matching checksum but nonsensical contents are rejected before anything
+    /// indexes into them.
+    fn validate(&self) -> AppResult<()> {
+        if self.memory.is_empty() || self.memory.len() > MAX_MEMORY_SIZE {
+            return Err(AppError::InvalidState("memory size out of range"));
+        }
+        if self.stack.len() > MAX_STACK_DEPTH {
+            return Err(AppError::InvalidState("stack depth out of range"));
+        }
+        if self.memory.len() != self.config.memory_size
+            || self.stack.len() != self.config.stack_depth
+            || self.call_entries.len() != self.stack.len()
+        {
+            return Err(AppError::InvalidState("machine layout mismatch"));
+        }
+        if self.sp as usize > self.stack.len() {
+            return Err(AppError::InvalidState("stack pointer out of range"));
+        }
+        if let Some(sample) = self.mega.sample {
+            if sample.range().end > self.memory.len() {
+                return Err(AppError::InvalidState("sound out of memory"));
+            }
+        }
+        self.display()?;
+        Ok(())
+    }
+
+    /// The screen of the state.
+    pub fn display(&self) -> AppResult<Screen> {
+        let mut display = Screen::new();
+        display.set_resolution(self.resolution);
+        if self.screen.len() != display.width() * display.height() {
+            return Err(AppError::InvalidState("screen size mismatch"));
+        }
+        if self.background_color.is_some() && self.foreground_colors.len() != COLOR_ZONE_COUNT {
+            return Err(AppError::InvalidState("color zone count mismatch"));
+        }
+
+        display.pixels[..self.screen.len()].copy_from_slice(&self.screen);
+        if let Some(mega) = &mut display.mega {
+            if self.mega_screen.len() != MEGA_SCREEN_SIZE {
+                return Err(AppError::InvalidState("Mega-Chip screen size mismatch"));
+            }
+            decode_mega_screen(&self.mega_screen, mega);
+        }
+        display.select_planes(self.planes);
+        display.colors = self.background_color.map(|background| {
+            let mut colors = ColorZones::new();
+            colors.background = background % 4;
+            for (zone, &color) in colors.foreground.iter_mut().zip(&self.foreground_colors) {
+                *zone = color % 8;
+            }
+            colors
+        });
+        Ok(display)
+    }
+}
+
+/// Bytes of `mega` as stored in [`MachineState::mega_screen`].
+fn encode_mega_screen(mega: &MegaScreen) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(MEGA_SCREEN_SIZE);
+    bytes.extend(mega.palette.iter().flat_map(|color| color.to_le_bytes()));
+    bytes.extend_from_slice(&mega.back_indices);
+    bytes.extend(mega.back.iter().flat_map(|color| color.to_le_bytes()));
+    bytes.extend(mega.front.iter().flat_map(|color| color.to_le_bytes()));
+    bytes
+}
+
+/// Fills `mega` from `bytes` written by [`encode_mega_screen`].
+fn decode_mega_screen(bytes: &[u8], mega: &mut MegaScreen) {
+    let (palette, rest) = bytes.split_at(PALETTE_SIZE * 4);
+    let (back_indices, rest) = rest.split_at(mega.back_indices.len());
+    let (back, front) = rest.split_at(mega.back.len() * 4);
+    fn colors(bytes: &[u8]) -> impl Iterator<Item = u32> + '_ {
+        bytes
+            .chunks_exact(4)
+            .map(|color| u32::from_le_bytes(color.try_into().expect("chunks are 4 bytes")))
+    }
+    for (entry, color) in mega.palette.iter_mut().zip(colors(palette)) {
+        *entry = color;
+    }
+    mega.back_indices.copy_from_slice(back_indices);
+    for (pixel, color) in mega.back.iter_mut().zip(colors(back)) {
+        *pixel = color;
+    }
+    for (pixel, color) in mega.front.iter_mut().zip(colors(front)) {
+        *pixel = color;
+    }
+}
+
+/// Reads the thumbnail of a save state written by [`MachineState::save`], a
+/// PNG image.
+pub fn load_thumbnail(path: &Path) -> AppResult<Vec<u8>> {
+    let bytes = std::fs::read(path)?;
+    let (thumbnail, _) = split_container(&bytes)?;
+    Ok(thumbnail.to_vec())
+}
+
+/// Checks the header of a save state file and splits the rest into its
+/// thumbnail and payload.
+fn split_container(bytes: &[u8]) -> AppResult<(&[u8], &[u8])> {
+    let Some((header, rest)) = bytes.split_first_chunk::<HEADER_SIZE>() else {
+        return Err(AppError::InvalidState("truncated save state"));
+    };
+    if header[..4] != STATE_MAGIC {
+        return Err(AppError::InvalidState("not a save state"));
+    }
+    let version = u16::from_le_bytes([header[4], header[5]]);
+    if version > STATE_VERSION {
+        return Err(AppError::InvalidState(
+            "save state from a newer version of the emulator",
+        ));
+    }
+    if version != STATE_VERSION {
+        return Err(AppError::InvalidState("unsupported save state version"));
+    }
+    let crc = u32::from_le_bytes([header[6], header[7], header[8], header[9]]);
+    if crc32fast::hash(rest) != crc {
+        return Err(AppError::InvalidState(
+            "corrupt save state, checksum mismatch",
+        ));
+    }
+
+    let Some((size, rest)) = rest.split_first_chunk::<4>() else {
+        return Err(AppError::InvalidState("truncated save state"));
+    };
+    let size = u32::from_le_bytes(*size) as usize;
+    if size > rest.len() {
+        return Err(AppError::InvalidState("truncated save state"));
+    }
+    Ok(rest.split_at(size))
+}
+
+impl<A: AudioBus> Chip8<Screen, Keypad, A> {
+    /// Like [`Chip8::step`], calling `on_instruction` with the state of the
+    /// machine and the instruction once it executed successfully.
+    ///
+    /// The machine is snapshotted for every instruction, which slows down
+    /// emulation.
+    pub fn step_with(
+        &mut self,
+        on_instruction: impl FnOnce(&MachineState, Instruction),
+    ) -> ExecResult<()> {
+        if let Some(instruction) = self.execute_next()? {
+            on_instruction(&self.snapshot(), instruction);
+        }
+        Ok(())
+    }
+
+    /// Like [`Chip8::run_frame`], calling `on_instruction` as
+    /// [`Chip8::step_with`] does, and `on_frame` with the screen once the
+    /// frame is complete.
+    ///
+    /// These hooks allow tracing or visualizing execution without
+    /// reimplementing the interpreter loop.
+    pub fn run_frame_with(
+        &mut self,
+        instructions: usize,
+        mut on_instruction: impl FnMut(&MachineState, Instruction),
+        on_frame: impl FnOnce(&Screen),
+    ) -> ExecResult<()> {
+        for _ in 0..instructions {
+            self.step_with(&mut on_instruction)?;
+        }
+        self.tick_timers();
+        on_frame(&self.display);
+        Ok(())
+    }
+
+    pub fn snapshot(&self) -> MachineState {
+        MachineState {
+            memory: self.memory().to_vec(),
+            registers: self.registers,
+            register_i: self.register_i,
+            pc: self.pc,
+            sp: self.sp,
+            stack: self.stack[..self.config().stack_depth].to_vec(),
+            call_entries: self.call_entries[..self.config().stack_depth].to_vec(),
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            waiting_for_key: self.waiting_for_key.map(|x| x as u8),
+            key_wait_pressed: self.key_wait_pressed,
+            waiting_for_vblank: self.waiting_for_vblank,
+            exited: self.exited,
+            screen: self.display.rows().flatten().copied().collect(),
+            mega_screen: self
+                .display
+                .mega
+                .as_deref()
+                .map_or(Vec::new(), encode_mega_screen),
+            resolution: self.display.resolution(),
+            planes: self.display.selected_planes(),
+            background_color: self.display.colors.map(|colors| colors.background),
+            foreground_colors: self
+                .display
+                .colors
+                .map_or(Vec::new(), |colors| colors.foreground.to_vec()),
+            audio_pattern: self.audio_pattern,
+            pitch: self.pitch,
+            mega: self.mega,
+            keypad: self.keypad.keys,
+            rng_state: self.rng.state(),
+            flags: self.flags,
+            quirks: self.quirks,
+            config: *self.config(),
+        }
+    }
+
+    pub fn restore(&mut self, state: &MachineState) -> AppResult<()> {
+        let config = *self.config();
+        // How memory accesses out of bounds are handled is up to the command line.
+        let saved = state.config.with_memory_mode(config.memory_mode);
+        if let Some(difference) = difference(&saved, &config) {
+            return Err(AppError::IncompatibleState(format!(
+                "the state was saved on {} but the machine is {}, with {}; start with the \
+                 --platform it was saved on",
+                platform_names(&saved),
+                platform_names(&config),
+                difference
+            )));
+        }
+        let display = state.display()?;
+        if state.sp as usize > config.stack_depth {
+            return Err(AppError::InvalidState("stack pointer out of range"));
+        }
+
+        self.memory[..config.memory_size].copy_from_slice(&state.memory);
+        self.registers = state.registers;
+        self.register_i = state.register_i;
+        self.pc = state.pc;
+        self.sp = state.sp;
+        self.stack[..config.stack_depth].copy_from_slice(&state.stack);
+        self.call_entries[..config.stack_depth].copy_from_slice(&state.call_entries);
+        self.delay_timer = state.delay_timer;
+        self.sound_timer = state.sound_timer;
+        self.waiting_for_key = state.waiting_for_key.map(|x| x as usize & 0xF);
+        self.key_wait_pressed = state.key_wait_pressed.map(|key| key & 0xF);
+        self.waiting_for_vblank = state.waiting_for_vblank;
+        self.exited = state.exited;
+        self.display = display;
+        self.keypad.keys = state.keypad;
+        self.rng = Rng::from_state(state.rng_state);
+        self.flags = state.flags;
+        self.quirks = state.quirks;
+        self.audio_pattern = state.audio_pattern;
+        self.pitch = state.pitch;
+        self.audio.set_pattern(&self.audio_pattern);
+        self.audio.set_pitch(self.pitch);
+        self.audio.set_tone(self.sound_timer > 0);
+        // Rewinding restores every frame, so a sound only restarts if it
+        // changed. Sounds played once are not played again.
+        if state.mega.sample != self.mega.sample {
+            self.audio.stop_sample();
+            if let Some(sample) = state.mega.sample.filter(|sample| sample.looping) {
+                self.audio
+                    .play_sample(&self.memory[sample.range()], sample.rate, true);
+            }
+        }
+        self.mega = state.mega;
+        Ok(())
+    }
+}
+
+/// Names the platforms whose machine is `config`, as in "vip, chip48 or schip".
+fn platform_names(config: &MachineConfig) -> String {
+    let names: Vec<_> = Platform::ALL
+        .into_iter()
+        .filter(|platform| {
+            let mut platform_config = platform.config().with_memory_mode(config.memory_mode);
+            // Mega-Chip machines get only the memory their ROM needs.
+            if platform_config.megachip {
+                platform_config.memory_size = config.memory_size;
+            }
+            platform_config == *config
+        })
+        .map(Platform::name)
+        .collect();
+    match names.split_last() {
+        None => "a custom machine".to_owned(),
+        Some((last, [])) => last.to_string(),
+        Some((last, rest)) => format!("{} or {}", rest.join(", "), last),
+    }
+}
+
+/// Describes the first way `machine` differs from `saved`, the machine a
+/// state was saved on, or `None` if the state can be loaded on it.
+fn difference(saved: &MachineConfig, machine: &MachineConfig) -> Option<String> {
+    let address = |address: u16| format!("{:#05X}", address);
+    let resolution = |resolution: Resolution| {
+        let (width, height) = resolution.size();
+        format!("{}x{}", width, height)
+    };
+    Some(if saved.memory_size != machine.memory_size {
+        format!(
+            "{} KB of memory instead of {} KB",
+            machine.memory_size / 1024,
+            saved.memory_size / 1024
+        )
+    } else if saved.stack_depth != machine.stack_depth {
+        format!(
+            "a {} level stack instead of {} levels",
+            machine.stack_depth, saved.stack_depth
+        )
+    } else if saved.chip8x != machine.chip8x {
+        if machine.chip8x {
+            "the CHIP-8X instructions".to_owned()
+        } else {
+            "no CHIP-8X instructions".to_owned()
+        }
+    } else if saved.megachip != machine.megachip {
+        if machine.megachip {
+            "the Mega-Chip instructions".to_owned()
+        } else {
+            "no Mega-Chip instructions".to_owned()
+        }
+    } else if saved.program_start != machine.program_start {
+        format!(
+            "programs loaded at {} instead of {}",
+            address(machine.program_start),
+            address(saved.program_start)
+        )
+    } else if saved.entry_point != machine.entry_point {
+        format!(
+            "execution starting at {} instead of {}",
+            address(machine.start_address()),
+            address(saved.start_address())
+        )
+    } else if saved.resolution != machine.resolution {
+        format!(
+            "a {} display at reset instead of {}",
+            resolution(machine.resolution),
+            resolution(saved.resolution)
+        )
+    } else if saved.font_address != machine.font_address {
+        format!(
+            "the font at {} instead of {}",
+            address(machine.font_address),
+            address(saved.font_address)
+        )
+    } else {
+        return None;
+    })
+}
@@ -0,0 +1,105 @@
+//! Label names for addresses, loaded from symbol files written by assemblers
+//! such as Octo.
+
+use std::{collections::BTreeMap, path::Path};
+
+use crate::{error::AppResult, instruction::Instruction};
+
+/// Labels of a program, looked up by address or name.
+#[derive(Debug, Default, Clone)]
+pub struct Symbols {
+    /// One name per address, the first one listed if there are several.
+    names: BTreeMap<u16, String>,
+    addresses: BTreeMap<String, u16>,
+}
+
+impl Symbols {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads a symbol file, see [`Symbols::parse`].
+    pub fn load(path: &Path) -> AppResult<Self> {
+        Ok(Self::parse(&std::fs::read_to_string(path)?))
+    }
+
+    /// Parses lines pairing a label with an address, in either order and
+    /// separated by spaces, `=` or `:`, such as `main 0x202`, `main = 514` or
+    /// `0x202: main`. Text after `#` or `;` and lines without both a label and
+    /// an address are ignored.
+    pub fn parse(text: &str) -> Self {
+        let mut symbols = Self::new();
+        for line in text.lines() {
+            let line = line.split(['#', ';']).next().unwrap_or_default();
+            let mut address = None;
+            let mut name = None;
+            for token in line
+                .split(|c: char| c.is_whitespace() || c == '=' || c == ':')
+                .filter(|token| !token.is_empty())
+            {
+                match parse_number(token) {
+                    Some(value) if address.is_none() => address = Some(value),
+                    Some(_) => {}
+                    None if name.is_none() => name = Some(token),
+                    None => {}
+                }
+            }
+            if let (Some(address), Some(name)) = (address, name) {
+                symbols.insert(name, address);
+            }
+        }
+        symbols
+    }
+
+    pub fn insert(&mut self, name: &str, address: u16) {
+        self.names.entry(address).or_insert_with(|| name.to_owned());
+        self.addresses.insert(name.to_owned(), address);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.addresses.is_empty()
+    }
+
+    /// Label at exactly `address`.
+    pub fn name(&self, address: u16) -> Option<&str> {
+        self.names.get(&address).map(String::as_str)
+    }
+
+    pub fn address(&self, name: &str) -> Option<u16> {
+        self.addresses.get(name).copied()
+    }
+
+    /// Parses an address in hexadecimal with a `0x` prefix or in decimal, or
+    /// looks it up as a label.
+    pub fn resolve(&self, location: &str) -> Option<u16> {
+        parse_number(location).or_else(|| self.address(location))
+    }
+
+    /// `address` relative to the closest label at or before it, like
+    /// `draw-score+4`, or in hexadecimal if there is none.
+    pub fn describe(&self, address: u16) -> String {
+        match self.names.range(..=address).next_back() {
+            Some((&label, name)) if label == address => name.clone(),
+            Some((&label, name)) => format!("{}+{}", name, address - label),
+            None => format!("{:#05X}", address),
+        }
+    }
+
+    /// Label of the address `instruction` jumps to, calls or loads into `I`.
+    pub fn operand_name(&self, instruction: Instruction) -> Option<&str> {
+        match instruction {
+            Instruction::JumpAddr(address)
+            | Instruction::CallAddr(address)
+            | Instruction::LoadIAddr(address)
+            | Instruction::LoadILong(address) => self.name(address),
+            _ => None,
+        }
+    }
+}
+
+fn parse_number(token: &str) -> Option<u16> {
+    match token.strip_prefix("0x").or(token.strip_prefix("0X")) {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => token.parse().ok(),
+    }
+}
@@ -1,17 +1,34 @@
-use std::cell::RefCell;
-
 use cgmath::{Vector2, Vector3};
 use winit::dpi::PhysicalSize;
 
 use crate::{
-    camera::Camera,
+    camera::{Camera, Rotation},
     mesh::InstanceData,
-    screen::{Screen, SCREEN_HEIGHT, SCREEN_WIDTH},
+    palette::Palette,
+    screen::{Screen, DEFAULT_PLANE_COLORS, SCREEN_HEIGHT, SCREEN_WIDTH},
+    state::MachineState,
 };
 
+/// Most of the screen's brightness that may change in a frame with
+/// [`World::set_anti_flash`], as a fraction of all pixels fully lit. A flash
+/// of the whole screen then takes a third of a second.
+const ANTI_FLASH_RATE: f32 = 0.05;
+
 pub struct World {
     pub camera: Camera,
+    /// Latest frame produced by the emulator.
     pub screen: Screen,
+    /// Machine state shown by the overlay panels, while any is open.
+    pub machine: Option<MachineState>,
+    /// Colors of the screen, `None` for the default shading.
+    palette: Option<Palette>,
+    /// Frames a pixel takes to fade out after turning off, 0 to turn off at once.
+    decay_frames: u32,
+    /// Limit how fast the screen as a whole gets brighter or darker.
+    anti_flash: bool,
+    /// For each pixel, the value it was last lit with and how bright it is
+    /// drawn, from 0 to 1.
+    glow: Vec<(u8, f32)>,
 }
 
 impl World {
@@ -19,24 +36,154 @@ impl World {
         let camera = Camera {
             position: Vector3::new(0.0f32, 0.0f32, -1.0f32),
             size: Vector2::new(surface_size.width as f32, surface_size.height as f32),
+            resolution: Vector2::new(SCREEN_WIDTH as f32, SCREEN_HEIGHT as f32),
+            letterbox: false,
+            rotation: Rotation::None,
+            bezel: None,
         };
 
         Self {
             camera,
             screen: Screen::new(),
+            machine: None,
+            palette: None,
+            decay_frames: 0,
+            anti_flash: false,
+            glow: Vec::new(),
         }
     }
 
-    pub fn get_instances(&self) -> Vec<InstanceData> {
-        let mut instances = Vec::with_capacity(SCREEN_WIDTH as usize * SCREEN_HEIGHT as usize);
-        for y in 0..SCREEN_HEIGHT {
-            for x in 0..SCREEN_WIDTH {
-                if self.screen.pixels[y as usize * SCREEN_WIDTH as usize + x as usize] {
-                    instances.push(InstanceData::new(Vector2::new(x as f32, y as f32)));
+    /// Lets pixels fade out over `frames` frames instead of turning off at
+    /// once, hiding the flicker of sprites erased and redrawn every frame.
+    pub fn set_decay(&mut self, frames: u32) {
+        self.decay_frames = frames;
+        self.glow.clear();
+        self.screen.mark_dirty();
+    }
+
+    /// Caps how much of the screen may change brightness in a frame, for
+    /// people sensitive to flashing. Small changes such as moving sprites
+    /// still show at once, but pixels fade in and out when most of the
+    /// screen flips, as in ROMs flashing the whole screen.
+    pub fn set_anti_flash(&mut self, anti_flash: bool) {
+        self.anti_flash = anti_flash;
+        self.glow.clear();
+        self.screen.mark_dirty();
+    }
+
+    pub fn palette(&self) -> Option<Palette> {
+        self.palette
+    }
+
+    pub fn set_palette(&mut self, palette: Option<Palette>) {
+        self.palette = palette;
+        self.screen.mark_dirty();
+    }
+
+    /// Color behind the pixels as `0xAARRGGBB`.
+    pub fn background(&self) -> u32 {
+        match self.palette {
+            Some(palette) if self.screen.colors.is_none() && self.screen.mega.is_none() => {
+                palette.background()
+            }
+            _ => 0xFF000000,
+        }
+    }
+
+    /// Copies the emulator's screen if it changed since the last copy,
+    /// following its resolution. The copy is left dirty until rendered.
+    pub fn set_screen(&mut self, screen: &mut Screen) {
+        if !screen.take_dirty() {
+            return;
+        }
+        self.screen.clone_from(screen);
+        self.screen.mark_dirty();
+        self.camera.resolution = Vector2::new(screen.width() as f32, screen.height() as f32);
+    }
+
+    /// Replaces the contents of `instances` with one instance per pixel drawn,
+    /// fading the pixels turned off by a frame, and with anti-flash those
+    /// turned on as well. Returns whether any is still fading, so the next
+    /// frame should be drawn even if the screen is unchanged.
+    pub fn get_instances(&mut self, instances: &mut Vec<InstanceData>) -> bool {
+        let size = self.screen.width() * self.screen.height();
+        let painted = self.screen.colors.is_some() || self.screen.mega.is_some();
+        if (self.decay_frames == 0 && !self.anti_flash) || painted {
+            self.glow.clear();
+        } else if self.glow.len() != size {
+            self.glow = vec![(0, 0.0); size];
+        }
+        let fade = 1.0 / self.decay_frames.max(1) as f32;
+        // How bright a pixel would be drawn without anti-flash.
+        let target = |pixel: u8, glow: &(u8, f32)| {
+            if pixel != 0 {
+                1.0
+            } else {
+                (glow.1 - fade).max(0.0)
+            }
+        };
+        // Part of every pixel's change in brightness drawn this frame.
+        let mut step = 1.0;
+        if self.anti_flash && !self.glow.is_empty() {
+            let change: f32 = self
+                .screen
+                .rows()
+                .flatten()
+                .zip(&self.glow)
+                .map(|(&pixel, glow)| (target(pixel, glow) - glow.1).abs())
+                .sum();
+            let budget = ANTI_FLASH_RATE * size as f32;
+            if change > budget {
+                step = budget / change;
+            }
+        }
+        let mut fading = false;
+        instances.clear();
+        for (y, row) in self.screen.rows().enumerate() {
+            for (x, &pixel) in row.iter().enumerate() {
+                let position = Vector2::new(x as f32, y as f32);
+                let (pixel, brightness) = match self.glow.get_mut(y * row.len() + x) {
+                    Some(glow) => {
+                        let target = target(pixel, glow);
+                        if pixel != 0 {
+                            glow.0 = pixel;
+                        }
+                        glow.1 = if step < 1.0 {
+                            glow.1 + (target - glow.1) * step
+                        } else {
+                            target
+                        };
+                        fading |= glow.1 != target || (pixel == 0 && glow.1 > 0.0);
+                        (glow.0, glow.1)
+                    }
+                    None => (pixel, 1.0),
+                };
+                if painted {
+                    // CHIP-8X and Mega-Chip paint the background as well.
+                    instances.push(InstanceData::with_color(
+                        position,
+                        self.screen.color(x, y, &DEFAULT_PLANE_COLORS),
+                    ));
+                    continue;
+                }
+                if pixel == 0 || brightness == 0.0 {
+                    continue;
                 }
+                let instance = if let Some(palette) = &self.palette {
+                    InstanceData::with_color(position, palette.color(pixel))
+                } else if pixel & 0b10 != 0 {
+                    // The default shading is for the first plane only.
+                    InstanceData::with_color(
+                        position,
+                        DEFAULT_PLANE_COLORS[(pixel & 0b11) as usize],
+                    )
+                } else {
+                    InstanceData::new(position)
+                };
+                instances.push(instance.with_brightness(brightness));
             }
         }
-        instances
+        fading
     }
 
     pub fn resize(&mut self, new_size: PhysicalSize<u32>) {
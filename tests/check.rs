@@ -0,0 +1,57 @@
+//! Tests of the compatibility check and its built-in test ROMs.
+
+use std::{env, fs, path::Path};
+
+use rusty_chip8::{
+    assembler,
+    check::{self, Outcome, CHECKED_PLATFORMS},
+    config::DEFAULT_PROGRAM_START,
+};
+
+fn suite_dir() -> &'static Path {
+    Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/roms/check"))
+}
+
+#[test]
+fn bundled_roms_pass_on_every_platform() {
+    let reports = check::run_bundled(&CHECKED_PLATFORMS, 600, 1000).unwrap();
+    assert_eq!(reports.len(), 2);
+    for report in reports {
+        assert_eq!(
+            report.outcomes,
+            [Outcome::Pass; CHECKED_PLATFORMS.len()],
+            "{}",
+            report.name
+        );
+    }
+}
+
+#[test]
+fn bundled_roms_are_built_from_their_sources() {
+    for name in ["flags", "quirks"] {
+        let source = fs::read_to_string(suite_dir().join(format!("{}.8o", name))).unwrap();
+        let rom = fs::read(suite_dir().join(format!("{}.ch8", name))).unwrap();
+        assert_eq!(
+            assembler::assemble(&source, DEFAULT_PROGRAM_START).unwrap(),
+            rom,
+            "{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn quirks_differ_from_another_platform() {
+    // Expect the VIP's quirks on every platform.
+    let dir = env::temp_dir().join(format!("rusty-chip8-check-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::copy(suite_dir().join("quirks.ch8"), dir.join("quirks.ch8")).unwrap();
+    fs::copy(suite_dir().join("quirks.vip.pbm"), dir.join("quirks.pbm")).unwrap();
+
+    let reports = check::run_suite(&dir, &CHECKED_PLATFORMS, 600, 1000);
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(
+        reports.unwrap()[0].outcomes,
+        [Outcome::Pass, Outcome::Fail, Outcome::Fail, Outcome::Fail]
+    );
+}
@@ -0,0 +1,72 @@
+//! Tests of the debugger's views of a running machine.
+
+use rusty_chip8::{
+    chip::Chip8,
+    debugger::{call_stack, CallFrame},
+};
+
+#[test]
+fn call_stack_of_nested_calls() {
+    let rom = [
+        0x22, 0x06, // 0x200: CALL 0x206
+        0x12, 0x02, // 0x202: JP 0x202
+        0x00, 0x00, // 0x204
+        0x22, 0x0C, // 0x206: CALL 0x20C
+        0x00, 0xEE, // 0x208: RET
+        0x00, 0x00, // 0x20A
+        0x60, 0x01, // 0x20C: LD V0, 0x01
+        0x00, 0xEE, // 0x20E: RET
+    ];
+    let mut chip = Chip8::with_seed(0);
+    chip.load_rom(&rom).unwrap();
+    chip.run_for(2).unwrap();
+
+    let sp = chip.sp as usize;
+    let frames = call_stack(&chip.stack[..sp], &chip.call_entries[..sp]);
+    assert_eq!(
+        frames,
+        [
+            CallFrame {
+                entry: 0x206,
+                caller: 0x200,
+                return_address: 0x202,
+            },
+            CallFrame {
+                entry: 0x20C,
+                caller: 0x206,
+                return_address: 0x208,
+            },
+        ]
+    );
+
+    chip.run_for(2).unwrap();
+    assert_eq!(chip.pc, frames[1].return_address);
+    chip.run_for(1).unwrap();
+    assert_eq!(chip.pc, frames[0].return_address);
+}
+
+#[test]
+fn call_stack_survives_self_modifying_code() {
+    let rom = [
+        0x22, 0x04, // 0x200: CALL 0x204
+        0x00, 0x00, // 0x202
+        0xA2, 0x00, // 0x204: LD I, 0x200
+        0x60, 0x00, // 0x206: LD V0, 0x00
+        0xF0, 0x55, // 0x208: LD [I], V0, overwriting the CALL
+        0x12, 0x0A, // 0x20A: JP 0x20A
+    ];
+    let mut chip = Chip8::with_seed(0);
+    chip.load_rom(&rom).unwrap();
+    chip.run_for(4).unwrap();
+
+    assert_eq!(chip.memory()[0x200], 0x00);
+    let sp = chip.sp as usize;
+    assert_eq!(
+        call_stack(&chip.stack[..sp], &chip.call_entries[..sp]),
+        [CallFrame {
+            entry: 0x204,
+            caller: 0x200,
+            return_address: 0x202,
+        }]
+    );
+}
@@ -0,0 +1,28 @@
+//! Tests of the instruction and frame hooks.
+
+use rusty_chip8::{chip::Chip8, instruction::Instruction};
+
+#[test]
+fn instruction_hook_sees_executed_instructions_only() {
+    let rom = [
+        0x60, 0x2A, // 0x200: LD V0, 0x2A
+        0x00, 0xEE, // 0x202: RET, with an empty stack
+    ];
+    let mut chip = Chip8::with_seed(0);
+    chip.load_rom(&rom).unwrap();
+
+    let mut seen = Vec::new();
+    let mut frames = 0;
+    let result = chip.run_frame_with(
+        2,
+        |state, instruction| seen.push((state.pc, state.registers[0], instruction)),
+        |_| frames += 1,
+    );
+
+    assert!(result.is_err());
+    assert_eq!(
+        seen,
+        [(0x202, 0x2A, Instruction::LoadRegByte { x: 0, kk: 0x2A })]
+    );
+    assert_eq!(frames, 0);
+}
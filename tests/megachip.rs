@@ -0,0 +1,84 @@
+//! Tests of the Mega-Chip display and its save states.
+
+use rusty_chip8::{
+    chip::Chip8, config::XO_CHIP_MEMORY_SIZE, platform::Platform, screen::Resolution,
+    state::MachineState,
+};
+
+const RED: u32 = 0xFFFF_0000;
+const BLUE: u32 = 0xFF00_00FF;
+
+/// Loads a red and a blue color, draws them side by side at (16, 16) twice,
+/// the second time colliding with red, and shows the picture.
+fn rom() -> Vec<u8> {
+    let mut rom = vec![
+        0x00, 0x11, // 0x200: MEGAON
+        0x01, 0x00, 0x02, 0x30, // 0x202: LDHI I, 0x000230
+        0x02, 0x02, // 0x206: LDPAL 0x02
+        0x01, 0x00, 0x02, 0x38, // 0x208: LDHI I, 0x000238
+        0x03, 0x02, // 0x20C: SPRW 0x02
+        0x04, 0x01, // 0x20E: SPRH 0x01
+        0x60, 0x10, // 0x210: LD V0, 0x10
+        0xD0, 0x01, // 0x212: DRW V0, V0, 1
+        0x81, 0xF0, // 0x214: LD V1, VF
+        0x09, 0x01, // 0x216: CCOL 0x01
+        0xD0, 0x01, // 0x218: DRW V0, V0, 1
+        0x00, 0xE0, // 0x21A: CLS
+        0x12, 0x1C, // 0x21C: JP 0x21C
+    ];
+    rom.resize(0x30, 0);
+    rom.extend([
+        0xFF, 0xFF, 0x00, 0x00, // 0x230: red
+        0xFF, 0x00, 0x00, 0xFF, // 0x234: blue
+        0x01, 0x02, // 0x238: sprite
+    ]);
+    rom
+}
+
+fn machine() -> Chip8 {
+    let mut chip = Chip8::with_config(Platform::MegaChip.config());
+    chip.seed_rng(0);
+    chip.load_rom(&rom()).unwrap();
+    chip
+}
+
+#[test]
+fn memory_grows_to_fit_the_rom() {
+    let mut chip = machine();
+    assert_eq!(chip.memory().len(), 2 * XO_CHIP_MEMORY_SIZE);
+
+    let rom = vec![0; 3 * XO_CHIP_MEMORY_SIZE];
+    chip.load_rom(&rom).unwrap();
+    assert_eq!(chip.memory().len(), 5 * XO_CHIP_MEMORY_SIZE);
+    assert_eq!(chip.config().memory_size, 5 * XO_CHIP_MEMORY_SIZE);
+}
+
+#[test]
+fn draws_palette_colors_and_detects_collisions() {
+    let mut chip = machine();
+    chip.run_for(12).unwrap();
+
+    assert_eq!(chip.display.resolution(), Resolution::Mega);
+    assert_eq!(chip.registers[0x1], 0);
+    assert_eq!(chip.registers[0xF], 1);
+    let plane_colors = [0; 4];
+    assert_eq!(chip.display.color(16, 16, &plane_colors), RED);
+    assert_eq!(chip.display.color(17, 16, &plane_colors), BLUE);
+    assert_eq!(chip.display.color(18, 16, &plane_colors), 0xFF00_0000);
+}
+
+#[test]
+fn save_state_round_trip() {
+    let mut chip = machine();
+    chip.run_for(12).unwrap();
+    let state = chip.snapshot();
+    assert!(!state.mega_screen.is_empty());
+
+    let decoded = MachineState::decode(&state.encode().unwrap()).unwrap();
+    assert_eq!(decoded, state);
+
+    let mut restored = machine();
+    restored.restore(&decoded).unwrap();
+    assert_eq!(restored.snapshot(), state);
+    assert_eq!(restored.display.color(17, 16, &[0; 4]), BLUE);
+}
@@ -0,0 +1,71 @@
+//! Golden-image tests of the render stack, drawing frames offscreen. Run with
+//! `UPDATE_GOLDEN=1` to write the images again after an intended change.
+
+use std::path::Path;
+
+use rusty_chip8::{
+    audio::Buzzer, chip::Chip8, emulator::Emulator, keypad::Keypad, offscreen::OffscreenRenderer,
+    platform::Platform, screen::Screen, screenshot,
+};
+use winit::dpi::PhysicalSize;
+
+/// Largest difference of a color channel from the golden image, leaving room
+/// for rounding in other drivers.
+const TOLERANCE: u8 = 2;
+
+/// Runs `rom` on `platform` for `frames` frames, then renders its screen and
+/// compares it with the PNG image `golden` in `tests/golden`.
+fn assert_matches_golden(rom: &str, platform: Platform, frames: usize, golden: &str) {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let rom = std::fs::read(root.join("roms").join(rom)).unwrap();
+    let mut chip = Chip8::with_buses(
+        platform.config(),
+        Screen::new(),
+        Keypad::new(),
+        Buzzer::new(),
+    );
+    chip.quirks = platform.quirks();
+    chip.seed_rng(0);
+    let mut emulator = Emulator::new(chip);
+    emulator.load_rom(&rom).unwrap();
+    for _ in 0..frames {
+        emulator.step_frame().unwrap();
+    }
+
+    let mut renderer =
+        match pollster::block_on(OffscreenRenderer::create(PhysicalSize::new(512, 256))) {
+            Ok(renderer) => renderer,
+            Err(err) => {
+                eprintln!("Skipping the golden-image test: {}", err);
+                return;
+            }
+        };
+    let frame = renderer.render(&mut emulator.chip.display, 0.0).unwrap();
+
+    let path = root.join("tests/golden").join(golden);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        screenshot::save_frame(&frame, &path).unwrap();
+        return;
+    }
+    let (width, height, expected) = screenshot::decode_png(&std::fs::read(&path).unwrap()).unwrap();
+    assert_eq!((width as u32, height as u32), frame.dimensions());
+    for (i, (pixel, expected)) in frame.pixels().zip(expected.chunks_exact(3)).enumerate() {
+        let close = pixel.0[..3]
+            .iter()
+            .zip(expected)
+            .all(|(&actual, &expected)| actual.abs_diff(expected) <= TOLERANCE);
+        assert!(
+            close,
+            "pixel ({}, {}) is {:?} instead of {:?}",
+            i % width,
+            i / width,
+            &pixel.0[..3],
+            expected
+        );
+    }
+}
+
+#[test]
+fn ibm_logo() {
+    assert_matches_golden("ibm-logo.ch8", Platform::Vip, 60, "ibm-logo.png");
+}